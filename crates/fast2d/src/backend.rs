@@ -16,6 +16,45 @@ cfg_if! {
     }
 }
 
+mod layers;
+pub use layers::{DEFAULT_LAYER, ObjectId, RegionMode};
+pub(crate) use layers::Layers;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+pub(crate) use layers::Layer;
+
+mod spatial_index;
+
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+mod tessellation_cache;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+pub(crate) use tessellation_cache::{Geometry, TessellationCache};
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+mod text_cache;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+pub(crate) use text_cache::TextCache;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+mod text_shaping;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+pub(crate) use text_shaping::{glyphon_family, text_size};
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+mod font_database;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+pub use font_database::{registered_fonts, resolve_font, unregister_font};
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+pub(crate) use font_database::with_font_database;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+mod sample_buffers;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+pub(crate) use sample_buffers::SampleBuffers;
+
+mod icons;
+pub use icons::{register_icon, unregister_icon};
+pub(crate) use icons::with_icon;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+pub(crate) use icons::with_icon_glyph;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+mod icon_rasterizer;
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Point {
     pub x: f32,
@@ -36,6 +75,63 @@ pub struct RoundedCorners {
     pub bottom_right: f32,
 }
 
+/// Describes a font face registered with [`register_fonts`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FontDescriptor {
+    /// The family name to use with [`Family::name`](crate::Family::name).
+    pub family: String,
+    /// The numeric font weight (100 = Thin, 400 = Regular, 700 = Bold, ...).
+    pub weight: u16,
+    /// Whether the face is italic or oblique.
+    pub italic: bool,
+}
+
+/// Returns the sorted, deduplicated names of all registered font families.
+pub fn registered_families() -> Vec<String> {
+    let mut families: Vec<String> = registered_fonts().into_iter().map(|font| font.family).collect();
+    families.sort();
+    families.dedup();
+    families
+}
+
+/// Memory limits of the caches of a canvas in bytes, see [`CanvasWrapper::set_memory_budget`].
+///
+/// Only the `webgl`, `webgpu` and `native` backends keep caches the budget limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    /// Size of the glyph atlas textures, the atlas is created again with only the glyphs in use when it's larger.
+    pub glyph_atlas: u64,
+    /// Size of the tessellated shapes kept for reuse, the least recently used shapes are evicted beyond it.
+    pub tessellation_cache: u64,
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self { glyph_atlas: 32 << 20, tessellation_cache: 16 << 20 }
+    }
+}
+
+/// GPU memory used by a canvas in bytes, see [`CanvasWrapper::gpu_memory_usage`].
+///
+/// The `canvas` and `webgpu-blade` backends report zero usage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GpuMemoryUsage {
+    /// The multisampled color and stencil textures, sized like the canvas.
+    pub render_targets: u64,
+    /// Vertex, index and instance buffers of the prepared layers, and the samples of sample plots.
+    pub buffers: u64,
+    /// Glyph atlas textures, estimated from the glyphs drawn since the atlas was created,
+    /// as glyphon doesn't report the size of its textures.
+    pub glyph_atlas_estimate: u64,
+}
+
+impl GpuMemoryUsage {
+    /// Returns the sum of all GPU memory used by the canvas.
+    pub fn total(&self) -> u64 {
+        self.render_targets + self.buffers + self.glyph_atlas_estimate
+    }
+}
+
 /// Errors that can happen when registering fonts with [`register_fonts`] function.
 #[derive(Debug)]
 pub enum RegisterFontsError {
@@ -45,6 +141,7 @@ pub enum RegisterFontsError {
     FontParseFailed,
     FontFaceError(String),
     AddFontError(String),
+    FontFileError(String),
     NoValidFontLoaded,
 }
 
@@ -57,9 +154,32 @@ impl std::fmt::Display for RegisterFontsError {
             Self::FontParseFailed => write!(f, "Failed to parse font data"),
             Self::FontFaceError(error) => write!(f, "FontFace error: {error}"),
             Self::AddFontError(error) => write!(f, "Add font error: {error}"),
+            Self::FontFileError(error) => write!(f, "Font file error: {error}"),
             Self::NoValidFontLoaded => write!(f, "No valid font loaded"),
         }
     }
 }
 
 impl std::error::Error for RegisterFontsError {}
+
+/// Errors that can happen when registering icons with [`register_icon`] function.
+#[derive(Debug)]
+pub enum RegisterIconError {
+    EmptyIcon,
+    InvalidPath(String),
+    InvalidBitmap(String),
+    TooManyIcons,
+}
+
+impl std::fmt::Display for RegisterIconError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyIcon => write!(f, "Icon has zero size"),
+            Self::InvalidPath(error) => write!(f, "Invalid path data: {error}"),
+            Self::InvalidBitmap(error) => write!(f, "Invalid bitmap: {error}"),
+            Self::TooManyIcons => write!(f, "Too many icons registered"),
+        }
+    }
+}
+
+impl std::error::Error for RegisterIconError {}
@@ -7,5 +7,8 @@ mod canvas_wrapper;
 mod register_fonts;
 
 use std::sync::{OnceLock, Mutex};
+use std::sync::atomic::AtomicU64;
 
-pub static FONT_SYSTEM: OnceLock<Mutex<glyphon::FontSystem>> = OnceLock::new();
\ No newline at end of file
+pub static FONT_SYSTEM: OnceLock<Mutex<glyphon::FontSystem>> = OnceLock::new();
+/// Incremented whenever fonts are registered or removed.
+pub(crate) static FONT_GENERATION: AtomicU64 = AtomicU64::new(0);
@@ -1,8 +1,11 @@
-use crate::Object2d;
+use crate::{Camera, Object2d, ObjectId, RegionMode, Transform, DEFAULT_LAYER};
+use crate::backend::{GpuMemoryUsage, Layers, MemoryBudget};
+use crate::object2d::{flatten, Bounds, FlatStep};
 use web_sys::HtmlCanvasElement;
 use wgpu::util::DeviceExt;
-use glyphon::{Shaping, Buffer as GlyphonBuffer, Attrs, Metrics, Family as GlyphonFamily, TextRenderer, TextAtlas, SwashCache, Cache, Viewport, Resolution, ColorMode};
+use glyphon::{TextRenderer, TextAtlas, SwashCache, Cache, Viewport, Resolution, ColorMode};
 use crate::backend::backend_blade::FONT_SYSTEM;
+use crate::backend::text_shaping::shape_text;
 
 /// Rectangle vertex for Blade-style rendering
 #[repr(C)]
@@ -75,7 +78,7 @@ impl LineVertex {
 /// A Blade-inspired WebGPU canvas wrapper for browser rendering.
 /// Uses WebGPU directly with Blade Graphics patterns and architecture.
 pub struct CanvasWrapper {
-    objects: Vec<Object2d>,
+    layers: Layers<()>,
     canvas: HtmlCanvasElement,
     device: wgpu::Device,
     queue: wgpu::Queue,
@@ -91,6 +94,8 @@ pub struct CanvasWrapper {
     swash_cache: Option<SwashCache>,
     viewport: Option<Viewport>,
     current_size: (u32, u32),
+    camera: Camera,
+    memory_budget: MemoryBudget,
 }
 
 impl CanvasWrapper {
@@ -195,7 +200,7 @@ impl CanvasWrapper {
         web_sys::console::log_1(&"✅ Blade-style WebGPU pipelines ready!".into());
         
         Self {
-            objects: Vec::new(),
+            layers: Layers::new(),
             canvas,
             device,
             queue,
@@ -211,18 +216,70 @@ impl CanvasWrapper {
             swash_cache: Some(swash_cache),
             viewport: Some(viewport),
             current_size: (width, height),
+            camera: Camera::IDENTITY,
+            memory_budget: MemoryBudget::default(),
         }
     }
 
-    /// Updates objects and renders using Blade-inspired WebGPU approach
+    /// Updates objects of the default layer and renders using Blade-inspired WebGPU approach
     pub fn update_objects(&mut self, updater: impl FnOnce(&mut Vec<Object2d>)) {
-        updater(&mut self.objects);
-        
-        web_sys::console::log_1(&format!("🎨 Blade WebGPU rendering {} objects", self.objects.len()).into());
-        
+        self.update_layer(DEFAULT_LAYER, updater);
+    }
+
+    /// Updates objects of the named layer and renders, a missing layer is added with z-index 0.
+    ///
+    /// Layers are not cached by this backend, all of them are drawn again.
+    pub fn update_layer(&mut self, name: &str, updater: impl FnOnce(&mut Vec<Object2d>)) {
+        self.layers.update(name, updater);
+        self.render_blade_style();
+    }
+
+    /// Sets the z-index of the named layer and renders, layers with a higher z-index are drawn over the others.
+    pub fn set_layer_z_index(&mut self, name: &str, z_index: i32) {
+        self.layers.set_z_index(name, z_index);
         self.render_blade_style();
     }
 
+    /// Removes the named layer and renders, the default layer is only cleared.
+    pub fn remove_layer(&mut self, name: &str) {
+        if self.layers.remove(name) {
+            self.render_blade_style();
+        }
+    }
+
+    /// Returns the objects of the named layer, or `None` if there is no such layer.
+    pub fn layer_objects(&self, name: &str) -> Option<&Vec<Object2d>> {
+        self.layers.objects(name)
+    }
+
+    /// Adds the object on top of the named layer and renders, returns the id for updating or removing it.
+    pub fn insert_object(&mut self, layer: &str, object: impl Into<Object2d>) -> ObjectId {
+        let id = self.layers.insert(layer, object.into());
+        self.render_blade_style();
+        id
+    }
+
+    /// Updates the object with the id and renders, returns false if there is no such object.
+    pub fn update_object(&mut self, id: ObjectId, updater: impl FnOnce(&mut Object2d)) -> bool {
+        if !self.layers.update_object(id, updater) {
+            return false;
+        }
+        self.render_blade_style();
+        true
+    }
+
+    /// Removes the object with the id and renders, returns `None` if there is no such object.
+    pub fn remove_object(&mut self, id: ObjectId) -> Option<Object2d> {
+        let object = self.layers.remove_object(id)?;
+        self.render_blade_style();
+        Some(object)
+    }
+
+    /// Returns the object with the id, or `None` if there is no such object.
+    pub fn object(&self, id: ObjectId) -> Option<&Object2d> {
+        self.layers.object(id)
+    }
+
     /// Handles resizing using Blade-inspired approach
     pub fn resized(&mut self, width: u32, height: u32) {
         if width == 0 || height == 0 || (width == self.current_size.0 && height == self.current_size.1) {
@@ -251,6 +308,73 @@ impl CanvasWrapper {
         // Re-render
         self.render_blade_style();
     }
+
+    /// Returns the ids of the objects under the point `(x, y)` in canvas pixels, topmost first, e.g. for hover and click.
+    ///
+    /// The point is mapped to world coordinates by the camera, see [`Object2d::contains_point`] for what counts as a hit.
+    /// Objects added with [`Self::update_layer`] are reported too, their ids are valid until the layer is updated again.
+    pub fn hit_test(&self, x: f32, y: f32) -> Vec<ObjectId> {
+        let (x, y) = self.camera.screen_to_world(x, y);
+        self.layers.hit_test(x, y)
+    }
+
+    /// Returns the ids of the objects in the rectangle in canvas pixels, topmost first, e.g. for drag-to-select.
+    ///
+    /// The rectangle is mapped to world coordinates by the camera and may have a negative width or height,
+    /// e.g. when dragging up or left. Objects are compared by their bounding boxes, found through a spatial index
+    /// of each layer, so queries stay fast in large scenes.
+    ///
+    /// # Arguments
+    /// * `x`, `y`, `width`, `height` - The rectangle, e.g. from the drag start to the mouse.
+    /// * `mode` - Whether objects intersecting the rectangle or only those inside it are returned.
+    pub fn query_region(&self, x: f32, y: f32, width: f32, height: f32, mode: RegionMode) -> Vec<ObjectId> {
+        let area = Bounds::from_corners(self.camera.screen_to_world(x, y), self.camera.screen_to_world(x + width, y + height));
+        self.layers.query_region(area, mode)
+    }
+
+    /// Returns the bounds covering the objects of all layers in world coordinates,
+    /// or `None` if nothing covers an area, e.g. to show the whole scene with [`Self::zoom_to_fit`].
+    pub fn content_bounds(&self) -> Option<Bounds> {
+        self.layers.bounds()
+    }
+
+    /// Returns the GPU memory used by the canvas, always zero as this backend doesn't track it.
+    pub fn gpu_memory_usage(&self) -> GpuMemoryUsage {
+        GpuMemoryUsage::default()
+    }
+
+    /// Returns the memory budget set with [`Self::set_memory_budget`].
+    pub fn memory_budget(&self) -> MemoryBudget {
+        self.memory_budget
+    }
+
+    /// Sets the memory limits of the glyph atlas and the tessellated shapes kept for reuse.
+    ///
+    /// The budget is only kept for [`Self::memory_budget`], this backend has no such caches.
+    ///
+    /// # Arguments
+    /// * `budget` - The limits in bytes, see [`MemoryBudget::default`] for the defaults.
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        self.memory_budget = budget;
+    }
+
+    /// Returns the camera the scene is viewed through.
+    pub fn camera(&self) -> Camera {
+        self.camera
+    }
+
+    /// Sets the camera the scene is viewed through and redraws, e.g. after a pan or wheel zoom.
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+        self.render_blade_style();
+    }
+
+    /// Zooms and pans the camera so the world rectangle fills the canvas, then redraws.
+    pub fn zoom_to_fit(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let mut camera = self.camera;
+        camera.zoom_to_fit(x, y, width, height, self.current_size.0 as f32, self.current_size.1 as f32);
+        self.set_camera(camera);
+    }
     
     /// Render using Blade-inspired WebGPU patterns
     fn render_blade_style(&mut self) {
@@ -293,8 +417,6 @@ impl CanvasWrapper {
         // Submit commands (Blade-style)
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
-        
-        web_sys::console::log_1(&"✅ Blade WebGPU frame rendered".into());
     }
     
     /// Create triangle pipeline using Blade-inspired shader patterns
@@ -628,33 +750,64 @@ impl CanvasWrapper {
     fn render_objects(&self, render_pass: &mut wgpu::RenderPass) {
         let canvas_width = self.current_size.0 as f32;
         let canvas_height = self.current_size.1 as f32;
-        for object in &self.objects {
+        // The camera is applied to vertices on the CPU, like the object and group transforms
+        let camera = self.camera.transform();
+        // Only the objects in view are drawn, found through the spatial index of each layer
+        let visible_area = self.camera.view_bounds(canvas_width, canvas_height);
+        let visible_objects = self.layers.iter().flat_map(|layer| {
+            layer.visible(&visible_area).into_iter().map(move |index| &layer.objects[index])
+        });
+        // Groups are flattened into objects with their accumulated transform, opacity and clip, layer by layer
+        for flat in visible_objects.flat_map(|object| flatten(std::slice::from_ref(object))) {
+            // Masks are not drawn into a stencil, their bounding box is already part of the clip
+            let FlatStep::Draw(object) = flat.step;
+            let (x, y, width, height) = match flat.clip {
+                Some(clip) => clip.mapped(&camera).scissor_rect(self.current_size.0, self.current_size.1),
+                None => (0, 0, self.current_size.0, self.current_size.1),
+            };
+            if width == 0 || height == 0 {
+                continue;
+            }
+            render_pass.set_scissor_rect(x, y, width, height);
+            let transform = flat.transform.then(camera);
             match object {
                 Object2d::Rectangle(rect) => {
-                    self.render_rectangle(render_pass, rect, canvas_width, canvas_height);
+                    self.render_rectangle(render_pass, rect, &transform, flat.opacity, canvas_width, canvas_height);
                 }
                 Object2d::Circle(circle) => {
-                    self.render_circle(render_pass, circle, canvas_width, canvas_height);
+                    self.render_circle(render_pass, circle, &transform, flat.opacity, canvas_width, canvas_height);
                 }
                 Object2d::Line(line) => {
-                    self.render_line(render_pass, line, canvas_width, canvas_height);
+                    self.render_line(render_pass, line, &transform, flat.opacity, canvas_width, canvas_height);
+                }
+                Object2d::SamplePlot(plot) => {
+                    // Plots are drawn as lines with level of detail, without a storage buffer,
+                    // only the samples in view are drawn, none if the transform collapses the plot
+                    let Some(inverse) = flat.transform.inverse() else {
+                        continue;
+                    };
+                    let local = visible_area.transformed(&inverse);
+                    for line in plot.to_lines(plot.sample_range(local.left, local.right)) {
+                        self.render_line(render_pass, &line, &transform, flat.opacity, canvas_width, canvas_height);
+                    }
                 }
                 Object2d::Text(text) => {
                     self.render_text(render_pass, text, canvas_width, canvas_height);
                 }
+                Object2d::Group(_) => {}
             }
         }
     }
     
     /// Render rectangle using Blade WebGPU
-    fn render_rectangle(&self, render_pass: &mut wgpu::RenderPass, rect: &crate::Rectangle, _canvas_width: f32, _canvas_height: f32) {
+    fn render_rectangle(&self, render_pass: &mut wgpu::RenderPass, rect: &crate::Rectangle, transform: &Transform, opacity: f32, _canvas_width: f32, _canvas_height: f32) {
         if let Some(ref pipeline) = self.rectangle_pipeline {
             // Create rectangle vertices (2 triangles = 6 vertices)
             let color = [
                 rect.color.r as f32 / 255.0,
                 rect.color.g as f32 / 255.0,
                 rect.color.b as f32 / 255.0,
-                rect.color.a,
+                rect.color.a * opacity,
             ];
             
             let x = rect.position.x;
@@ -662,15 +815,21 @@ impl CanvasWrapper {
             let w = rect.size.width;
             let h = rect.size.height;
             
+            // Corners are mapped through the accumulated transform
+            let corner = |x: f32, y: f32| {
+                let (x, y) = transform.transform_point(x, y);
+                [x, y]
+            };
+            
             let vertices = [
                 // Triangle 1
-                RectangleVertex { position: [x, y], color },         // Bottom-left
-                RectangleVertex { position: [x + w, y], color },     // Bottom-right
-                RectangleVertex { position: [x, y + h], color },     // Top-left
+                RectangleVertex { position: corner(x, y), color },         // Bottom-left
+                RectangleVertex { position: corner(x + w, y), color },     // Bottom-right
+                RectangleVertex { position: corner(x, y + h), color },     // Top-left
                 // Triangle 2
-                RectangleVertex { position: [x + w, y], color },     // Bottom-right
-                RectangleVertex { position: [x + w, y + h], color }, // Top-right
-                RectangleVertex { position: [x, y + h], color },     // Top-left
+                RectangleVertex { position: corner(x + w, y), color },     // Bottom-right
+                RectangleVertex { position: corner(x + w, y + h), color }, // Top-right
+                RectangleVertex { position: corner(x, y + h), color },     // Top-left
             ];
             
             // Create vertex buffer
@@ -687,18 +846,20 @@ impl CanvasWrapper {
     }
     
     /// Render circle using Blade WebGPU
-    fn render_circle(&self, render_pass: &mut wgpu::RenderPass, circle: &crate::Circle, _canvas_width: f32, _canvas_height: f32) {
+    fn render_circle(&self, render_pass: &mut wgpu::RenderPass, circle: &crate::Circle, transform: &Transform, opacity: f32, _canvas_width: f32, _canvas_height: f32) {
         if let Some(ref pipeline) = self.circle_pipeline {
             // Create circle as quad (2 triangles = 6 vertices)
             let color = [
                 circle.color.r as f32 / 255.0,
                 circle.color.g as f32 / 255.0,
                 circle.color.b as f32 / 255.0,
-                circle.color.a,
+                circle.color.a * opacity,
             ];
             
-            let center = [circle.center.x, circle.center.y];
-            let radius = circle.radius;
+            // The circle stays round, scaled by the largest scale factor of the transform
+            let (center_x, center_y) = transform.transform_point(circle.center.x, circle.center.y);
+            let center = [center_x, center_y];
+            let radius = circle.radius * transform.max_scale();
             
             // Create bounding box for the circle
             let x = center[0] - radius;
@@ -731,20 +892,21 @@ impl CanvasWrapper {
     }
     
     /// Render line using Blade WebGPU
-    fn render_line(&self, render_pass: &mut wgpu::RenderPass, line: &crate::Line, _canvas_width: f32, _canvas_height: f32) {
+    fn render_line(&self, render_pass: &mut wgpu::RenderPass, line: &crate::Line, transform: &Transform, opacity: f32, _canvas_width: f32, _canvas_height: f32) {
         if let Some(ref pipeline) = self.line_pipeline {
             let color = [
                 line.color.r as f32 / 255.0,
                 line.color.g as f32 / 255.0,
                 line.color.b as f32 / 255.0,
-                line.color.a,
+                line.color.a * opacity,
             ];
             
-            // Convert line points to vertices
+            // Convert line points to vertices, lines with level of detail reduced to a few points per pixel column
             let mut vertices = Vec::new();
-            for point in &line.points {
+            for point in line.lod_points(transform).iter() {
+                let (x, y) = transform.transform_point(point.x, point.y);
                 vertices.push(LineVertex {
-                    position: [point.x, point.y],
+                    position: [x, y],
                     color,
                 });
             }
@@ -785,51 +947,9 @@ impl CanvasWrapper {
             }
         };
         
-        // Set up text metrics and buffer
-        let line_height_pixels = text.font_size * text.line_height_multiplier;
-        let mut buffer = GlyphonBuffer::new(&mut font_system, Metrics::new(text.font_size, line_height_pixels));
-        buffer.set_size(&mut font_system, Some(text.width), Some(text.height));
-
-        // Convert font family to glyphon format
-        let glyphon_family = match &text.family {
-            crate::object2d::Family::Name(name) => GlyphonFamily::Name(name.as_ref()),
-            crate::object2d::Family::SansSerif => GlyphonFamily::SansSerif,
-            crate::object2d::Family::Serif => GlyphonFamily::Serif,
-            crate::object2d::Family::Monospace => GlyphonFamily::Monospace,
-            crate::object2d::Family::Cursive => GlyphonFamily::Cursive,
-            crate::object2d::Family::Fantasy => GlyphonFamily::Fantasy,
-        };
-
-        // Set up text attributes (color, weight, style)
-        let glyphon_color = glyphon::Color::rgba(
-            text.color.r, 
-            text.color.g, 
-            text.color.b, 
-            (text.color.a * 255.0) as u8
-        );
-        let attrs = Attrs::new()
-            .family(glyphon_family)
-            .color(glyphon_color)
-            .weight({
-                use crate::object2d::FontWeight::*;
-                match text.weight {
-                    Thin => glyphon::fontdb::Weight::THIN,
-                    ExtraLight => glyphon::fontdb::Weight::EXTRA_LIGHT,
-                    Light => glyphon::fontdb::Weight::LIGHT,
-                    Regular => glyphon::fontdb::Weight::NORMAL,
-                    Medium => glyphon::fontdb::Weight::MEDIUM,
-                    SemiBold => glyphon::fontdb::Weight::SEMIBOLD,
-                    Bold => glyphon::fontdb::Weight::BOLD,
-                    ExtraBold => glyphon::fontdb::Weight::EXTRA_BOLD,
-                    Black => glyphon::fontdb::Weight::BLACK,
-                }
-            })
-            .style(if text.italic { glyphon::fontdb::Style::Italic } else { glyphon::fontdb::Style::Normal });
-        
-        buffer.set_text(&mut font_system, &text.text, &attrs, Shaping::Advanced);
+        // Shaped with the whole family fallback chain, like the wgpu backends
+        let _buffer = shape_text(&mut font_system, text);
 
-        // For now, just log that text would be rendered
         // TODO: Implement proper text rendering with mutable access to components
-        web_sys::console::log_1(&format!("🔤 Would render text: '{}' at ({}, {})", text.text, text.left, text.top).into());
     }
 }
\ No newline at end of file
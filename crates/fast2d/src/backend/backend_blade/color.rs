@@ -26,4 +26,13 @@ impl Color {
             self.a,
         ]
     }
+
+    pub(crate) fn to_glyphon_color(self) -> glyphon::Color {
+        glyphon::Color::rgba(
+            self.r,
+            self.g,
+            self.b,
+            (self.a * 255.0).clamp(0.0, 255.0) as u8,
+        )
+    }
 }
@@ -1,7 +1,5 @@
-use std::sync::Mutex;
 use glyphon::FontSystem;
-use crate::backend::{backend_blade::FONT_SYSTEM, RegisterFontsError};
-use web_sys::wasm_bindgen::UnwrapThrowExt;
+use crate::backend::{with_font_database, RegisterFontsError, FontDescriptor};
 
 /// Registers fonts for the Blade WebGPU backend.
 ///
@@ -11,37 +9,16 @@ use web_sys::wasm_bindgen::UnwrapThrowExt;
 /// * `fonts` - Font data as a Vec of Vec<u8> (e.g., TTF or OTF).
 ///
 /// # Returns
-/// * `Ok(())` if at least one valid font is loaded or added.
+/// * `Ok(Vec<FontDescriptor>)` describing the newly loaded faces if at least one valid font is loaded or added.
 /// * `Err(RegisterFontsError)` if no valid font is loaded, or if no fonts are provided.
-pub fn register_fonts(fonts: Vec<Vec<u8>>) -> Result<(), RegisterFontsError> {
+pub fn register_fonts(fonts: Vec<Vec<u8>>) -> Result<Vec<FontDescriptor>, RegisterFontsError> {
     if fonts.is_empty() {
         return Err(RegisterFontsError::NoFontsProvided);
     }
-
-    // If already initialized, just add new fonts
-    if let Some(font_system_mutex) = FONT_SYSTEM.get() {
-        let mut font_system = font_system_mutex.lock().unwrap_throw();
-        let db = font_system.db_mut();
+    with_font_database(FontSystem::new, |db| {
         for font_data in fonts {
             db.load_font_data(font_data);
         }
-        if db.faces().next().is_none() {
-            return Err(RegisterFontsError::NoValidFontLoaded);
-        }
-        web_sys::console::log_1(&"✅ Blade WebGPU: Additional fonts registered".into());
-        return Ok(());
-    }
-
-    // Not initialized yet: create and load
-    let mut font_system = FontSystem::new();
-    let db = font_system.db_mut();
-    for font_data in fonts {
-        db.load_font_data(font_data);
-    }
-    if db.faces().next().is_none() {
-        return Err(RegisterFontsError::NoValidFontLoaded);
-    }
-    FONT_SYSTEM.set(Mutex::new(font_system)).unwrap_throw();
-    web_sys::console::log_1(&"✅ Blade WebGPU: Font system initialized".into());
-    Ok(())
-}
\ No newline at end of file
+        Ok(())
+    })
+}
@@ -1,5 +1,5 @@
 mod register_fonts;
-pub use register_fonts::register_fonts;
+pub use register_fonts::{register_fonts, registered_fonts, resolve_font, unregister_font};
 
 mod canvas_wrapper;
 pub use canvas_wrapper::CanvasWrapper;
@@ -7,5 +7,7 @@ pub use canvas_wrapper::CanvasWrapper;
 mod color;
 pub use color::Color;
 
+mod icon;
+
 mod draw;
-pub use draw::draw;
+pub(crate) use draw::{draw_layers, text_size, LayerCanvas};
@@ -1,12 +1,19 @@
-use crate::Object2d;
+use crate::{Camera, Object2d, ObjectId, RegionMode, DEFAULT_LAYER};
+use crate::backend::{GpuMemoryUsage, Layers, MemoryBudget};
+use crate::object2d::Bounds;
 use web_sys::{HtmlCanvasElement, CanvasRenderingContext2d};
 use web_sys::wasm_bindgen::{UnwrapThrowExt, JsCast};
+use super::LayerCanvas;
 
-/// A wrapper around an HTML Canvas 2D context, managing a list of 2D objects and rendering them.
+/// A wrapper around an HTML Canvas 2D context, managing layers of 2D objects and rendering them.
+///
+/// Each layer is drawn into its own offscreen canvas, which is only redrawn when the layer changes.
 pub struct CanvasWrapper {
-    objects: Vec<Object2d>,
+    layers: Layers<LayerCanvas>,
     canvas: HtmlCanvasElement,
     context: CanvasRenderingContext2d,
+    camera: Camera,
+    memory_budget: MemoryBudget,
 }
 
 impl CanvasWrapper {
@@ -27,19 +34,98 @@ impl CanvasWrapper {
             .dyn_into::<CanvasRenderingContext2d>()
             .unwrap_throw();
         Self {
-            objects: Vec::new(),
+            layers: Layers::new(),
             canvas,
             context,
+            camera: Camera::IDENTITY,
+            memory_budget: MemoryBudget::default(),
         }
     }
 
-    /// Updates the list of 2D objects and redraws the canvas.
+    /// Updates the list of 2D objects in the default layer and redraws the canvas.
     ///
     /// # Arguments
     /// * `updater` - A closure that mutates the internal vector of `Object2d`.
     pub fn update_objects(&mut self, updater: impl FnOnce(&mut Vec<Object2d>)) {
-        updater(&mut self.objects);
-        super::draw(&self.context, &self.objects);
+        self.update_layer(DEFAULT_LAYER, updater);
+    }
+
+    /// Updates the list of 2D objects in the named layer and redraws the canvas.
+    ///
+    /// A missing layer is added with z-index 0. Only this layer is drawn again, e.g. a fast-moving cursor
+    /// in its own layer is redrawn without drawing the static layers below it.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the layer.
+    /// * `updater` - A closure that mutates the vector of `Object2d` of the layer.
+    pub fn update_layer(&mut self, name: &str, updater: impl FnOnce(&mut Vec<Object2d>)) {
+        self.layers.update(name, updater);
+        self.redraw();
+    }
+
+    /// Sets the z-index of the named layer and redraws the canvas.
+    ///
+    /// Layers with a higher z-index are drawn over layers with a lower one,
+    /// layers with the same z-index in the order they were added. The default layer has z-index 0.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the layer, a missing layer is added.
+    /// * `z_index` - The new z-index.
+    pub fn set_layer_z_index(&mut self, name: &str, z_index: i32) {
+        self.layers.set_z_index(name, z_index);
+        self.redraw();
+    }
+
+    /// Removes the named layer and redraws the canvas, the default layer is only cleared.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the layer.
+    pub fn remove_layer(&mut self, name: &str) {
+        if self.layers.remove(name) {
+            self.redraw();
+        }
+    }
+
+    /// Adds the object on top of the named layer and redraws the canvas.
+    ///
+    /// A missing layer is added with z-index 0. Unlike objects added with [`Self::update_layer`], the object can be
+    /// updated or removed through the returned id.
+    ///
+    /// # Arguments
+    /// * `layer` - The name of the layer.
+    /// * `object` - The object to add.
+    pub fn insert_object(&mut self, layer: &str, object: impl Into<Object2d>) -> ObjectId {
+        let id = self.layers.insert(layer, object.into());
+        self.redraw();
+        id
+    }
+
+    /// Updates the object with the id and redraws the canvas, returns false if there is no such object.
+    ///
+    /// # Arguments
+    /// * `id` - The id returned by [`Self::insert_object`].
+    /// * `updater` - A closure that mutates the object.
+    pub fn update_object(&mut self, id: ObjectId, updater: impl FnOnce(&mut Object2d)) -> bool {
+        if !self.layers.update_object(id, updater) {
+            return false;
+        }
+        self.redraw();
+        true
+    }
+
+    /// Removes the object with the id and redraws the canvas, returns `None` if there is no such object.
+    ///
+    /// # Arguments
+    /// * `id` - The id returned by [`Self::insert_object`].
+    pub fn remove_object(&mut self, id: ObjectId) -> Option<Object2d> {
+        let object = self.layers.remove_object(id)?;
+        self.redraw();
+        Some(object)
+    }
+
+    /// Returns the object with the id, or `None` if there is no such object.
+    pub fn object(&self, id: ObjectId) -> Option<&Object2d> {
+        self.layers.object(id)
     }
 
     /// Handles resizing of the canvas and redraws the contents.
@@ -50,6 +136,92 @@ impl CanvasWrapper {
     pub fn resized(&mut self, width: u32, height: u32) {
         self.canvas.set_width(width);
         self.canvas.set_height(height);
-        super::draw(&self.context, &self.objects);
+        self.layers.invalidate();
+        self.redraw();
+    }
+
+    /// Returns the objects of the named layer, or `None` if there is no such layer.
+    pub fn layer_objects(&self, name: &str) -> Option<&Vec<Object2d>> {
+        self.layers.objects(name)
+    }
+
+    /// Returns the ids of the objects under the point `(x, y)` in canvas pixels, topmost first, e.g. for hover and click.
+    ///
+    /// The point is mapped to world coordinates by the camera, see [`Object2d::contains_point`] for what counts as a hit.
+    /// Objects added with [`Self::update_layer`] are reported too, their ids are valid until the layer is updated again.
+    pub fn hit_test(&self, x: f32, y: f32) -> Vec<ObjectId> {
+        let (x, y) = self.camera.screen_to_world(x, y);
+        self.layers.hit_test(x, y)
+    }
+
+    /// Returns the ids of the objects in the rectangle in canvas pixels, topmost first, e.g. for drag-to-select.
+    ///
+    /// The rectangle is mapped to world coordinates by the camera and may have a negative width or height,
+    /// e.g. when dragging up or left. Objects are compared by their bounding boxes, found through a spatial index
+    /// of each layer, so queries stay fast in large scenes.
+    ///
+    /// # Arguments
+    /// * `x`, `y`, `width`, `height` - The rectangle, e.g. from the drag start to the mouse.
+    /// * `mode` - Whether objects intersecting the rectangle or only those inside it are returned.
+    pub fn query_region(&self, x: f32, y: f32, width: f32, height: f32, mode: RegionMode) -> Vec<ObjectId> {
+        let area = Bounds::from_corners(self.camera.screen_to_world(x, y), self.camera.screen_to_world(x + width, y + height));
+        self.layers.query_region(area, mode)
+    }
+
+    /// Returns the bounds covering the objects of all layers in world coordinates,
+    /// or `None` if nothing covers an area, e.g. to show the whole scene with [`Self::zoom_to_fit`].
+    pub fn content_bounds(&self) -> Option<Bounds> {
+        self.layers.bounds()
+    }
+
+    /// Returns the GPU memory used by the canvas, always zero as this backend doesn't track it.
+    pub fn gpu_memory_usage(&self) -> GpuMemoryUsage {
+        GpuMemoryUsage::default()
+    }
+
+    /// Returns the memory budget set with [`Self::set_memory_budget`].
+    pub fn memory_budget(&self) -> MemoryBudget {
+        self.memory_budget
+    }
+
+    /// Sets the memory limits of the glyph atlas and the tessellated shapes kept for reuse.
+    ///
+    /// The budget is only kept for [`Self::memory_budget`], this backend has no such caches.
+    ///
+    /// # Arguments
+    /// * `budget` - The limits in bytes, see [`MemoryBudget::default`] for the defaults.
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        self.memory_budget = budget;
+    }
+
+    /// Returns the camera the scene is viewed through.
+    pub fn camera(&self) -> Camera {
+        self.camera
+    }
+
+    /// Sets the camera the scene is viewed through and redraws, e.g. after a pan or wheel zoom.
+    ///
+    /// # Arguments
+    /// * `camera` - The new camera, see [`Camera::pan`] and [`Camera::zoom_at`].
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+        // Layers are drawn in canvas pixels, so all of them are drawn again
+        self.layers.invalidate();
+        self.redraw();
+    }
+
+    /// Zooms and pans the camera so the world rectangle fills the canvas, then redraws.
+    ///
+    /// # Arguments
+    /// * `x`, `y`, `width`, `height` - The content bounds in world coordinates.
+    pub fn zoom_to_fit(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let mut camera = self.camera;
+        camera.zoom_to_fit(x, y, width, height, self.canvas.width() as f32, self.canvas.height() as f32);
+        self.set_camera(camera);
+    }
+
+    /// Draws the changed layers and copies all layers to the canvas.
+    fn redraw(&mut self) {
+        super::draw_layers(&self.context, &mut self.layers, &self.camera, self.canvas.width(), self.canvas.height());
     }
 }
@@ -1,13 +1,125 @@
-use web_sys::wasm_bindgen::UnwrapThrowExt;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, Path2d};
+use web_sys::wasm_bindgen::{JsCast, UnwrapThrowExt};
+use crate::object2d::{Bounds, MaskShape};
+use crate::backend::Layers;
 
-/// Draws a list of 2D objects onto the given CanvasRenderingContext2d.
-pub fn draw(ctx: &web_sys::CanvasRenderingContext2d, objects: &[crate::Object2d]) {
+/// An offscreen canvas holding the drawing of a layer, copied to the visible canvas until the layer changes.
+pub(crate) struct LayerCanvas {
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+}
+
+impl LayerCanvas {
+    /// Creates an offscreen canvas of the given size.
+    fn new(width: u32, height: u32) -> Self {
+        let document = web_sys::window().unwrap_throw().document().unwrap_throw();
+        let canvas: HtmlCanvasElement = document.create_element("canvas").unwrap_throw().dyn_into().unwrap_throw();
+        canvas.set_width(width);
+        canvas.set_height(height);
+        let context = canvas
+            .get_context("2d")
+            .unwrap_throw()
+            .unwrap_throw()
+            .dyn_into::<CanvasRenderingContext2d>()
+            .unwrap_throw();
+        Self { canvas, context }
+    }
+}
+
+/// Draws the layers in order onto the given context, drawing changed layers into their offscreen canvases first.
+///
+/// Only the objects whose bounds are in view are drawn into the offscreen canvases.
+pub(crate) fn draw_layers(ctx: &CanvasRenderingContext2d, layers: &mut Layers<LayerCanvas>, camera: &crate::Camera, width: u32, height: u32) {
+    ctx.clear_rect(0.0, 0.0, width as f64, height as f64);
+    let view = camera.view_bounds(width as f32, height as f32);
+    for layer in layers.iter_mut() {
+        let layer_canvas = layer.cache.take().unwrap_or_else(|| LayerCanvas::new(width, height));
+        if layer.dirty {
+            // Resizing clears the canvas as well
+            if layer_canvas.canvas.width() != width || layer_canvas.canvas.height() != height {
+                layer_canvas.canvas.set_width(width);
+                layer_canvas.canvas.set_height(height);
+            } else {
+                layer_canvas.context.clear_rect(0.0, 0.0, width as f64, height as f64);
+            }
+            let visible = layer.visible(&view);
+            draw(&layer_canvas.context, visible.iter().map(|&index| &layer.objects[index]), camera, width, height);
+            layer.mark_prepared();
+        }
+        ctx.draw_image_with_html_canvas_element(&layer_canvas.canvas, 0.0, 0.0).unwrap_throw();
+        layer.cache = Some(layer_canvas);
+    }
+}
+
+/// Draws a list of 2D objects onto the given CanvasRenderingContext2d of the given size, viewed through the camera.
+fn draw<'a>(
+    ctx: &web_sys::CanvasRenderingContext2d,
+    objects: impl IntoIterator<Item = &'a crate::Object2d>,
+    camera: &crate::Camera,
+    width: u32,
+    height: u32,
+) {
     // Set default styles
     ctx.set_fill_style_str("black");
     ctx.set_stroke_style_str("black");
     ctx.set_line_width(1.0);
+    // The camera is the base transform, object and group transforms are applied on top of it
+    ctx.save();
+    ctx.set_transform(
+        camera.scale_x as f64, 0.0, 0.0, camera.scale_y as f64,
+        (-camera.offset_x * camera.scale_x) as f64, (-camera.offset_y * camera.scale_y) as f64,
+    ).unwrap_throw();
+    draw_objects(ctx, objects, camera.transform(), &Bounds::new(0.0, 0.0, width as f32, height as f32));
+    ctx.restore();
+}
 
+/// Strokes the line if it has at least two points and its color is visible.
+fn stroke_line(ctx: &web_sys::CanvasRenderingContext2d, line: &crate::Line, to_pixels: &crate::Transform) {
+    if line.points.len() < 2 || line.color.a <= 0.0 {
+        return;
+    }
+    // Lines with level of detail are reduced to a few points per pixel column
+    let points = line.lod_points(to_pixels);
+    let stroke_color = line.color.to_canvas_rgba();
+    ctx.set_stroke_style_str(&stroke_color);
+    ctx.set_line_width(line.width as f64);
+    ctx.begin_path();
+    ctx.move_to(points[0].x as f64, points[0].y as f64);
+    for point in &points[1..] {
+        ctx.line_to(point.x as f64, point.y as f64);
+    }
+    ctx.stroke();
+}
+
+/// Draws the objects in order, groups recursively within their transform, opacity and clip rectangle.
+///
+/// `to_pixels` maps the coordinates of the objects to canvas pixels, like the transform of the context,
+/// `area` is the area of the canvas in pixels.
+fn draw_objects<'a>(
+    ctx: &web_sys::CanvasRenderingContext2d,
+    objects: impl IntoIterator<Item = &'a crate::Object2d>,
+    to_pixels: crate::Transform,
+    area: &Bounds,
+) {
     for obj in objects {
+        // Each object is drawn in its own coordinate system, the previous one is restored afterwards
+        let transform = match obj {
+            crate::Object2d::Rectangle(rect) => rect.transform,
+            crate::Object2d::Circle(circle) => circle.transform,
+            crate::Object2d::Line(line) => line.transform,
+            crate::Object2d::SamplePlot(plot) => plot.transform,
+            crate::Object2d::Text(text) => text.transform,
+            crate::Object2d::Group(group) => group.transform,
+        };
+        let has_transform = !transform.is_identity();
+        let to_pixels = transform.then(to_pixels);
+        if has_transform {
+            ctx.save();
+            ctx.transform(
+                transform.a as f64, transform.b as f64, transform.c as f64,
+                transform.d as f64, transform.e as f64, transform.f as f64,
+            ).unwrap_throw();
+        }
         match obj {
             crate::Object2d::Rectangle(rect) => {
                 // Border logic
@@ -116,74 +228,149 @@ pub fn draw(ctx: &web_sys::CanvasRenderingContext2d, objects: &[crate::Object2d]
                     ctx.stroke();
                 }
             }
-            crate::Object2d::Line(line) => {
-                // Draw polyline if at least two points and color is visible
-                if line.points.len() >= 2 && line.color.a > 0.0 {
-                    let stroke_color = line.color.to_canvas_rgba();
-                    ctx.set_stroke_style_str(&stroke_color);
-                    ctx.set_line_width(line.width as f64);
-                    ctx.begin_path();
-                    ctx.move_to(line.points[0].x as f64, line.points[0].y as f64);
-                    for i in 1..line.points.len() {
-                        ctx.line_to(line.points[i].x as f64, line.points[i].y as f64);
+            crate::Object2d::Line(line) => stroke_line(ctx, line, &to_pixels),
+            // The canvas has no shaders, plots are drawn as lines with level of detail
+            crate::Object2d::SamplePlot(plot) => {
+                // Only the samples in the canvas area are drawn, none if the transform collapses the plot
+                if let Some(inverse) = to_pixels.inverse() {
+                    let local = area.transformed(&inverse);
+                    for line in plot.to_lines(plot.sample_range(local.left, local.right)) {
+                        stroke_line(ctx, &line, &to_pixels);
                     }
-                    ctx.stroke();
                 }
             }
             crate::Object2d::Text(text) => {
                 // Draw text if color is visible
                 if text.color.a > 0.0 {
+                    // Text is clipped by its bounds, within the clip rectangles of its groups
+                    let bounded = text.width < f32::MAX || text.height < f32::MAX;
+                    if bounded {
+                        ctx.save();
+                        ctx.begin_path();
+                        ctx.rect(text.left as f64, text.top as f64, text.width as f64, text.height as f64);
+                        ctx.clip();
+                    }
                     let fill_color = text.color.to_canvas_rgba();
                     ctx.set_fill_style_str(&fill_color);
-                    // Compose CSS font string
-                    let font_style = if text.italic { "italic" } else { "normal" };
-                    let font_weight = font_weight_to_css(&text.weight);
-                    let font_str = format!("{font_style} {font_weight} {font_size}px {family}", font_style=font_style, font_weight=font_weight, font_size=text.font_size, family=text.family);
-                    ctx.set_font(&font_str);
-                    let max_width = text.width;
+                    ctx.set_font(&css_font(text));
                     let line_height = text.font_size * text.line_height_multiplier;
-                    // Word wrapping: split text into lines that fit max_width
-                    let words: Vec<&str> = text.text.split_whitespace().collect();
-                    let mut lines: Vec<String> = Vec::new();
-                    let mut current_line = String::new();
-                    for word in words {
-                        let test_line = if current_line.is_empty() {
-                            word.to_string()
-                        } else {
-                            format!("{current_line} {word}")
-                        };
-                        let metrics = ctx.measure_text(&test_line).unwrap_throw();
-                        if metrics.width() <= max_width as f64 || current_line.is_empty() {
-                            current_line = test_line;
-                        } else {
-                            lines.push(current_line);
-                            current_line = word.to_string();
-                        }
-                    }
-                    if !current_line.is_empty() {
-                        lines.push(current_line);
-                    }
+                    let lines = wrap_lines(ctx, text);
                     // Draw each line, adjusting for font ascent
                     let mut y = text.top;
                     for line in lines {
-                        let metrics = ctx.measure_text(&line).unwrap_throw();
+                        // Icons are not part of the font, measure the font metrics without them
+                        let metrics = ctx.measure_text(&super::icon::strip_icons(&line)).unwrap_throw();
                         let ascent = metrics.actual_bounding_box_ascent();
                         let font_box_ascent = metrics.font_bounding_box_ascent();
                         // Some browsers have a gap between font box and actual ascent
                         let gap = font_box_ascent - ascent;
                         let line_gap = if gap > 0.0 && gap < 1.0 { gap } else { 0.0 };
-                        ctx.fill_text(&line, text.left as f64, y as f64 + ascent + line_gap).unwrap_throw();
+                        super::icon::fill_line(ctx, text, &line, y as f64 + ascent + line_gap, y as f64);
                         y += line_height;
                         if y > text.top + text.height {
                             break;
                         }
                     }
+                    if bounded {
+                        ctx.restore();
+                    }
                 }
             }
+            crate::Object2d::Group(group) => {
+                // Opacity and clip are restored with the rest of the context state after the children
+                ctx.save();
+                ctx.set_global_alpha(ctx.global_alpha() * group.opacity as f64);
+                if let Some((position, size)) = group.clip {
+                    ctx.begin_path();
+                    ctx.rect(position.x as f64, position.y as f64, size.width as f64, size.height as f64);
+                    ctx.clip();
+                }
+                // Masks intersect with the clip rectangle and the masks of parent groups
+                if let Some(mask) = &group.mask {
+                    match &mask.shape {
+                        MaskShape::Rectangle { position, size, rounded_corners } => {
+                            draw_rounded_rect_path(ctx, position.x, position.y, size.width, size.height, rounded_corners);
+                            ctx.clip();
+                        }
+                        MaskShape::Circle { center, radius } => {
+                            ctx.begin_path();
+                            ctx.arc(center.x as f64, center.y as f64, *radius as f64, 0.0, std::f64::consts::PI * 2.0).unwrap_throw();
+                            ctx.clip();
+                        }
+                        MaskShape::Path { data, .. } => {
+                            // The path data was validated when the mask was created
+                            ctx.clip_with_path_2d(&Path2d::new_with_path_string(data).unwrap_throw());
+                        }
+                    }
+                }
+                draw_objects(ctx, &group.children, to_pixels, area);
+                ctx.restore();
+            }
+        }
+        if has_transform {
+            ctx.restore();
         }
     }
 }
 
+/// Returns the CSS font of the text.
+fn css_font(text: &crate::Text) -> String {
+    let font_style = if text.italic { "italic" } else { "normal" };
+    let font_weight = font_weight_to_css(&text.weight);
+    // Registered color fonts come last, so emoji are drawn with them instead of the browser's emoji font
+    let mut families = text.families.clone();
+    for color_family in super::register_fonts::color_font_families() {
+        let color_family = crate::object2d::Family::name(color_family);
+        if !families.contains(&color_family) {
+            families.push(color_family);
+        }
+    }
+    format!("{font_style} {font_weight} {font_size}px {family}", font_style=font_style, font_weight=font_weight, font_size=text.font_size, family=crate::object2d::Family::css_list(&families))
+}
+
+/// Splits the text into lines fitting its width, measured with the font set on the context.
+fn wrap_lines(ctx: &CanvasRenderingContext2d, text: &crate::Text) -> Vec<String> {
+    let max_width = text.width;
+    // Word wrapping: split text into lines that fit max_width
+    let words: Vec<&str> = text.text.split_whitespace().collect();
+    let mut lines: Vec<String> = Vec::new();
+    let mut current_line = String::new();
+    for word in words {
+        let test_line = if current_line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{current_line} {word}")
+        };
+        let line_width = super::icon::measure_line(ctx, &test_line, text.font_size);
+        if line_width <= max_width as f64 || current_line.is_empty() {
+            current_line = test_line;
+        } else {
+            lines.push(current_line);
+            current_line = word.to_string();
+        }
+    }
+    if !current_line.is_empty() {
+        lines.push(current_line);
+    }
+    lines
+}
+
+thread_local! {
+    /// A context of a detached canvas, used to measure text outside of drawing.
+    static MEASURE_CONTEXT: CanvasRenderingContext2d = LayerCanvas::new(1, 1).context;
+}
+
+/// Returns the width and height of the text laid out into lines, limited by its size.
+pub(crate) fn text_size(text: &crate::Text) -> (f32, f32) {
+    MEASURE_CONTEXT.with(|ctx| {
+        ctx.set_font(&css_font(text));
+        let lines = wrap_lines(ctx, text);
+        let width = lines.iter().map(|line| super::icon::measure_line(ctx, line, text.font_size) as f32).fold(0.0, f32::max);
+        let height = lines.len() as f32 * text.font_size * text.line_height_multiplier;
+        (width.min(text.width), height.min(text.height))
+    })
+}
+
 /// Draws a rounded rectangle path on the canvas context.
 /// This does not fill or stroke, just creates the path.
 fn draw_rounded_rect_path(
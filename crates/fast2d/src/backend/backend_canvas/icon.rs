@@ -0,0 +1,135 @@
+//! Drawing of inline text icons on the canvas.
+//!
+//! Icons are rendered once per size and color into offscreen canvases and drawn with `drawImage`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData, Path2d};
+use web_sys::wasm_bindgen::{Clamped, JsCast, UnwrapThrowExt};
+use crate::backend::{with_icon, Color};
+use crate::object2d::{icon_id, Icon, Text};
+
+/// Maximum number of rendered icon images kept in the cache.
+const MAX_CACHED_IMAGES: usize = 256;
+
+/// Key of a rendered icon image: icon revision, pixel size and fill color.
+type ImageKey = (u64, u32, u32, String);
+
+thread_local! {
+    static ICON_IMAGES: RefCell<HashMap<ImageKey, HtmlCanvasElement>> = RefCell::new(HashMap::new());
+}
+
+/// A part of a text line, either text or an icon.
+enum Segment<'a> {
+    Text(&'a str),
+    Icon(u16),
+}
+
+/// Splits the line into text and icon segments.
+fn segments(line: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for (index, character) in line.char_indices() {
+        if let Some(id) = icon_id(character) {
+            if start < index {
+                segments.push(Segment::Text(&line[start..index]));
+            }
+            segments.push(Segment::Icon(id));
+            start = index + character.len_utf8();
+        }
+    }
+    if start < line.len() {
+        segments.push(Segment::Text(&line[start..]));
+    }
+    segments
+}
+
+/// Returns the width of the icon drawn with the given font size.
+fn icon_width(id: u16, font_size: f32) -> f64 {
+    // Unregistered icons keep a square space like on the GPU backends
+    (font_size * with_icon(id, |icon| icon.icon.aspect_ratio()).unwrap_or(1.0)) as f64
+}
+
+/// Returns the text of the line without icons, used to measure font metrics.
+pub fn strip_icons(line: &str) -> String {
+    line.chars().filter(|character| icon_id(*character).is_none()).collect()
+}
+
+/// Measures the width of a line of text including its icons.
+pub fn measure_line(ctx: &CanvasRenderingContext2d, line: &str, font_size: f32) -> f64 {
+    segments(line).into_iter().map(|segment| match segment {
+        Segment::Text(text) => ctx.measure_text(text).unwrap_throw().width(),
+        Segment::Icon(id) => icon_width(id, font_size),
+    }).sum()
+}
+
+/// Fills a line of the text object, drawing its icons vertically centered in the line box.
+pub fn fill_line(ctx: &CanvasRenderingContext2d, text: &Text, line: &str, baseline: f64, line_top: f64) {
+    let font_size = text.font_size;
+    let line_height = (text.font_size * text.line_height_multiplier) as f64;
+    let mut x = text.left as f64;
+    for segment in segments(line) {
+        match segment {
+            Segment::Text(segment) => {
+                ctx.fill_text(segment, x, baseline).unwrap_throw();
+                x += ctx.measure_text(segment).unwrap_throw().width();
+            }
+            Segment::Icon(id) => {
+                let width = icon_width(id, font_size);
+                let height = font_size as f64;
+                let top = line_top + (line_height - height) / 2.0;
+                if let Some(image) = icon_image(id, width, height, &text.color) {
+                    ctx.draw_image_with_html_canvas_element_and_dw_and_dh(&image, x, top, width, height).unwrap_throw();
+                }
+                x += width;
+            }
+        }
+    }
+}
+
+/// Returns the icon rendered into an offscreen canvas, from the cache when possible.
+fn icon_image(id: u16, width: f64, height: f64, color: &Color) -> Option<HtmlCanvasElement> {
+    // Bitmaps are rendered once at their own size and scaled by drawImage
+    let key: ImageKey = with_icon(id, |registered| match registered.icon {
+        Icon::Path { .. } => (registered.revision, width.ceil() as u32, height.ceil() as u32, color.to_canvas_rgba()),
+        Icon::Bitmap { .. } => (registered.revision, 0, 0, String::new()),
+    })?;
+    if let Some(image) = ICON_IMAGES.with_borrow(|images| images.get(&key).cloned()) {
+        return Some(image);
+    }
+
+    let image = with_icon(id, |registered| render_icon(&registered.icon, &key)).flatten()?;
+    ICON_IMAGES.with_borrow_mut(|images| {
+        // Drop all images at once instead of tracking their use, re-rendering is cheap
+        if images.len() >= MAX_CACHED_IMAGES {
+            images.clear();
+        }
+        images.insert(key, image.clone());
+    });
+    Some(image)
+}
+
+/// Renders the icon into a new offscreen canvas, path icons at the size and color of the key.
+fn render_icon(icon: &Icon, key: &ImageKey) -> Option<HtmlCanvasElement> {
+    let document = web_sys::window()?.document()?;
+    let image: HtmlCanvasElement = document.create_element("canvas").ok()?.dyn_into().ok()?;
+    match icon {
+        Icon::Path { data, width: view_box_width, height: view_box_height } => {
+            let (_, width, height, color) = key;
+            image.set_width((*width).max(1));
+            image.set_height((*height).max(1));
+            let image_ctx: CanvasRenderingContext2d = image.get_context("2d").ok()??.dyn_into().ok()?;
+            image_ctx.scale(*width as f64 / *view_box_width as f64, *height as f64 / *view_box_height as f64).ok()?;
+            image_ctx.set_fill_style_str(color);
+            image_ctx.fill_with_path_2d(&Path2d::new_with_path_string(data).ok()?);
+        }
+        Icon::Bitmap { width, height, rgba } => {
+            image.set_width(*width);
+            image.set_height(*height);
+            let image_ctx: CanvasRenderingContext2d = image.get_context("2d").ok()??.dyn_into().ok()?;
+            let image_data = ImageData::new_with_u8_clamped_array_and_sh(Clamped(rgba), *width, *height).ok()?;
+            image_ctx.put_image_data(&image_data, 0.0, 0.0).ok()?;
+        }
+    }
+    Some(image)
+}
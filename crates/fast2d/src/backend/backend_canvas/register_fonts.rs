@@ -1,6 +1,24 @@
+use std::cell::RefCell;
 use web_sys::{window, FontFace, FontFaceDescriptors};
-use crate::backend::RegisterFontsError;
-use ttf_parser::{Face, name_id};
+use crate::backend::{RegisterFontsError, FontDescriptor};
+use crate::object2d::{Family, FontWeight};
+use ttf_parser::{Face, Tag, name_id};
+
+/// A font face added to the document by [`register_fonts`].
+struct RegisteredFont {
+    descriptor: FontDescriptor,
+    font_face: FontFace,
+    /// Whether the font has color glyphs (e.g. an emoji font)
+    color: bool,
+}
+
+thread_local! {
+    /// Registered font faces, kept to support introspection and removal.
+    static REGISTERED_FONTS: RefCell<Vec<RegisteredFont>> = const { RefCell::new(Vec::new()) };
+}
+
+/// OpenType tables with color glyphs: COLR layers, CBDT color bitmaps, Apple sbix bitmaps and SVG glyphs.
+const COLOR_GLYPH_TABLES: [&[u8; 4]; 4] = [b"COLR", b"CBDT", b"sbix", b"SVG "];
 
 /// Registers fonts for the Canvas backend.
 ///
@@ -10,9 +28,9 @@ use ttf_parser::{Face, name_id};
 /// * `fonts` - Font data as a Vec of Vec<u8> (e.g., TTF or OTF).
 ///
 /// # Returns
-/// * `Ok(())` if at least one valid font is loaded or added.
+/// * `Ok(Vec<FontDescriptor>)` describing the newly loaded faces if at least one valid font is loaded or added.
 /// * `Err(RegisterFontsError)` if no valid font is loaded, no fonts are provided, or browser APIs are unavailable.
-pub fn register_fonts(fonts: Vec<Vec<u8>>) -> Result<(), RegisterFontsError> {
+pub fn register_fonts(fonts: Vec<Vec<u8>>) -> Result<Vec<FontDescriptor>, RegisterFontsError> {
     if fonts.is_empty() {
         return Err(RegisterFontsError::NoFontsProvided);
     }
@@ -20,57 +38,125 @@ pub fn register_fonts(fonts: Vec<Vec<u8>>) -> Result<(), RegisterFontsError> {
     let window = window().ok_or(RegisterFontsError::NoWindow)?;
     let document = window.document().ok_or(RegisterFontsError::NoDocument)?;
     let font_face_set = document.fonts();
-    let mut any_loaded = false;
+    let mut loaded_fonts = Vec::new();
 
     for font_bytes in fonts {
         let face = Face::parse(&font_bytes, 0)
             .map_err(|_| RegisterFontsError::FontParseFailed)?;
 
-        let mut family = None;
-        let mut weight = None;
-        let mut style = None;
-        for name in face.names() {
-            if name.name_id == name_id::FAMILY && family.is_none() {
-                family = name.to_string();
-            }
-            if name.name_id == name_id::SUBFAMILY && style.is_none() {
-                let subfamily = name.to_string().unwrap_or_default().to_lowercase();
-                style = Some(if subfamily.contains("italic") { "italic" } else { "normal" });
-                weight = Some(
-                    if subfamily.contains("bold") {
-                        "bold"
-                    } else if subfamily.contains("light") {
-                        "300"
-                    } else if subfamily.contains("medium") {
-                        "500"
-                    } else if subfamily.contains("semibold") {
-                        "600"
-                    } else if subfamily.contains("black") {
-                        "900"
-                    } else {
-                        "400"
-                    }
-                );
-            }
-        }
-        let family = family.ok_or(RegisterFontsError::FontParseFailed)?;
-        let style = style.ok_or(RegisterFontsError::FontParseFailed)?;
-        let weight = weight.ok_or(RegisterFontsError::FontParseFailed)?;
+        // Prefer the typographic family so all weights and styles share one family name
+        let family = [name_id::TYPOGRAPHIC_FAMILY, name_id::FAMILY].into_iter()
+            .find_map(|id| face.names().into_iter().find(|name| name.name_id == id).and_then(|name| name.to_string()))
+            .ok_or(RegisterFontsError::FontParseFailed)?;
+        let descriptor = FontDescriptor {
+            family,
+            weight: face.weight().to_number(),
+            italic: face.is_italic() || face.is_oblique(),
+        };
+        let color = COLOR_GLYPH_TABLES.iter().any(|tag| face.raw_face().table(Tag::from_bytes(tag)).is_some());
 
         let buffer = web_sys::js_sys::Uint8Array::from(font_bytes.as_slice());
         let array_buffer = buffer.buffer();
         let descriptors = FontFaceDescriptors::new();
-        descriptors.set_style(style);
-        descriptors.set_weight(weight);
-        let font_face = FontFace::new_with_array_buffer_and_descriptors(&family, &array_buffer, &descriptors)
+        descriptors.set_style(if descriptor.italic { "italic" } else { "normal" });
+        descriptors.set_weight(&descriptor.weight.to_string());
+        let font_face = FontFace::new_with_array_buffer_and_descriptors(&descriptor.family, &array_buffer, &descriptors)
             .map_err(|error| RegisterFontsError::FontFaceError(format!("{:?}", error)))?;
         font_face_set.add(&font_face)
             .map_err(|error| RegisterFontsError::AddFontError(format!("{:?}", error)))?;
-        any_loaded = true;
+        REGISTERED_FONTS.with_borrow_mut(|fonts| fonts.push(RegisteredFont { descriptor: descriptor.clone(), font_face, color }));
+        loaded_fonts.push(descriptor);
     }
 
-    if !any_loaded && font_face_set.size() == 0 {
+    if loaded_fonts.is_empty() && font_face_set.size() == 0 {
         return Err(RegisterFontsError::NoValidFontLoaded);
     }
-    Ok(())
+    Ok(loaded_fonts)
+}
+
+/// Returns descriptors of all font faces registered with [`register_fonts`].
+pub fn registered_fonts() -> Vec<FontDescriptor> {
+    REGISTERED_FONTS.with_borrow(|fonts| fonts.iter().map(|font| font.descriptor.clone()).collect())
+}
+
+/// Returns the names of registered families with color glyphs, used as the last entries
+/// of the CSS font list so emoji are drawn with the registered color font.
+pub(crate) fn color_font_families() -> Vec<String> {
+    REGISTERED_FONTS.with_borrow(|fonts| {
+        let mut families: Vec<String> = Vec::new();
+        for font in fonts.iter().filter(|font| font.color) {
+            if !families.contains(&font.descriptor.family) {
+                families.push(font.descriptor.family.clone());
+            }
+        }
+        families
+    })
+}
+
+/// Returns the registered face that would be used to render text with the given family, weight and style.
+///
+/// Returns `None` if no face of the family is registered, e.g. because of a typo in the family name.
+/// The returned face may differ in weight or style when no exact match exists.
+/// Generic families are resolved by the browser and always return `None` on this backend.
+pub fn resolve_font(family: &Family, weight: FontWeight, italic: bool) -> Option<FontDescriptor> {
+    let Family::Name(name) = family else {
+        return None;
+    };
+    REGISTERED_FONTS.with_borrow(|fonts| {
+        let candidates: Vec<&FontDescriptor> = fonts.iter()
+            .map(|font| &font.descriptor)
+            .filter(|descriptor| descriptor.family.eq_ignore_ascii_case(name))
+            .collect();
+        best_match(&candidates, weight.value(), italic).cloned()
+    })
+}
+
+/// Removes all registered faces matching the descriptor (family, weight and style) from the document.
+///
+/// # Returns
+/// `true` if at least one face was removed.
+pub fn unregister_font(descriptor: &FontDescriptor) -> bool {
+    let Some(document) = window().and_then(|window| window.document()) else {
+        return false;
+    };
+    let font_face_set = document.fonts();
+    REGISTERED_FONTS.with_borrow_mut(|fonts| {
+        let font_count = fonts.len();
+        fonts.retain(|font| {
+            if font.descriptor != *descriptor {
+                return true;
+            }
+            font_face_set.delete(&font.font_face);
+            false
+        });
+        fonts.len() != font_count
+    })
+}
+
+/// Picks the face for the weight and style in the order of the CSS font matching algorithm,
+/// the same order the font database of the wgpu backends uses: the style first, then the weight.
+fn best_match<'a>(candidates: &[&'a FontDescriptor], weight: u16, italic: bool) -> Option<&'a FontDescriptor> {
+    // Italic and oblique faces are both italic here, a face of the other style is used only without a match
+    let italic = if candidates.iter().any(|descriptor| descriptor.italic == italic) { italic } else { !italic };
+    let weights: Vec<u16> = candidates.iter()
+        .filter(|descriptor| descriptor.italic == italic)
+        .map(|descriptor| descriptor.weight)
+        .collect();
+    let lighter = weights.iter().copied().filter(|available| *available < weight).max();
+    let heavier = weights.iter().copied().filter(|available| *available > weight).min();
+    let matching_weight = if weights.contains(&weight) {
+        weight
+    } else if (400..450).contains(&weight) && weights.contains(&500) {
+        500
+    } else if (450..=500).contains(&weight) && weights.contains(&400) {
+        400
+    } else if weight <= 500 {
+        // Light and regular weights prefer the closest lighter face, bold weights the closest heavier face
+        lighter.or(heavier)?
+    } else {
+        heavier.or(lighter)?
+    };
+    candidates.iter()
+        .find(|descriptor| descriptor.italic == italic && descriptor.weight == matching_weight)
+        .copied()
 }
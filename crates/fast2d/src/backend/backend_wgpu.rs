@@ -8,15 +8,20 @@ mod color;
 pub use color::Color;
 
 mod draw;
-pub use draw::draw;
+pub(crate) use draw::{draw, gpu_memory_usage, PreparedLayer};
 
 mod graphics;
-pub use graphics::{Graphics, resize_graphics, create_graphics};
+pub use graphics::{Graphics, resize_graphics, set_camera, create_graphics};
 
 use std::sync::{OnceLock, Mutex};
+use std::sync::atomic::AtomicU64;
 
 pub static FONT_SYSTEM: OnceLock<Mutex<glyphon::FontSystem>> = OnceLock::new();
+/// Incremented whenever fonts are registered or removed, so text shaped with the previous fonts is shaped again.
+pub(crate) static FONT_GENERATION: AtomicU64 = AtomicU64::new(0);
 pub const MSAA_SAMPLE_COUNT: u32 = 4;
+/// Format of the stencil buffer used to draw group masks, supported by all backends including WebGL.
+pub const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -46,3 +51,64 @@ impl ColoredVertex {
         }
     }
 }
+
+/// A rectangle or circle drawn as an instance of a quad, shaded by its signed distance in the shader.
+///
+/// Circles are rectangles with all corner radii equal to half their size.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShapeInstance {
+    /// Columns `(a, b)` and `(c, d)` of the transform to world coordinates
+    pub transform: [f32; 4],
+    /// Translation of the transform to world coordinates
+    pub translation: [f32; 2],
+    /// Top-left corner before the transform
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    /// Corner radii: top-left, top-right, bottom-right, bottom-left
+    pub radii: [f32; 4],
+    pub color: [f32; 4],
+    pub border_color: [f32; 4],
+    /// Width of the border inside the outline, 0.0 without a border
+    pub border_width: f32,
+}
+
+impl ShapeInstance {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+            0 => Float32x4, 1 => Float32x2, 2 => Float32x2, 3 => Float32x2,
+            4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ShapeInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// The uniforms of a sample plot, drawn from its samples in a storage buffer by the plot shader.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PlotUniforms {
+    /// Columns `(a, b)` and `(c, d)` of the transform to world coordinates
+    pub transform: [f32; 4],
+    /// Translation of the transform to world coordinates
+    pub translation: [f32; 2],
+    /// Position of the first sample with the value 0, before the transform
+    pub origin: [f32; 2],
+    pub x_step: f32,
+    pub y_scale: f32,
+    pub half_width: f32,
+    /// 0 for a polyline, 1 for steps
+    pub style: u32,
+    pub color: [f32; 4],
+    /// Index of the first sample drawn
+    pub first_sample: u32,
+    /// Index of the last sample drawn
+    pub last_sample: u32,
+    /// Samples reduced to their range by each instance, 1 to draw each segment
+    pub samples_per_instance: u32,
+    /// Padding to a multiple of 16 bytes, required for uniform buffers
+    pub _padding: u32,
+}
@@ -1,21 +1,294 @@
 use super::*;
-use crate::{Rectangle, Circle};
-use lyon::math::{point, Box2D};
+use crate::{Bounds, Camera, Rectangle, Circle, Line, Text, Transform, Mask, ObjectId, PlotStyle, SamplePlot};
+use crate::backend::{icon_rasterizer, text_shaping, Geometry, GpuMemoryUsage, Layer, Layers, TessellationCache};
+use crate::object2d::{flatten, ClipBox, FlatObject, FlatStep, MaskShape};
+use lyon::math::{point, Box2D, Point as LyonPoint};
 use lyon::path::{Path, Winding};
 use lyon::path::builder::BorderRadii as LyonBorderRadii;
-use lyon::tessellation::{
-    FillTessellator, FillOptions, VertexBuffers, FillVertex, BuffersBuilder,
-    StrokeTessellator, StrokeOptions, StrokeVertex, LineCap, LineJoin,
-};
+use lyon::tessellation::{FillOptions, FillRule, VertexBuffers, StrokeOptions, LineCap, LineJoin};
 use wgpu::TextureViewDescriptor;
-use wgpu::util::DeviceExt;
-use glyphon::{Shaping, Buffer as GlyphonBuffer, TextArea, Attrs, TextBounds, Metrics, Family as GlyphonFamily};
+use glyphon::{Buffer as GlyphonBuffer, Command, CustomGlyph, FontSystem, SwashCache, TextArea, TextBounds, TextRenderer};
 use bytemuck;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
 use web_sys::console;
 use web_sys::wasm_bindgen::{JsValue, UnwrapThrowExt};
 
-// The main draw function for rendering all 2D objects using wgpu
-pub fn draw(gfx: &mut Graphics, objects: &[crate::Object2d]) {
+/// A run of consecutive objects drawn with the same kind of draw calls.
+enum DrawStep {
+    /// Triangles and instances drawn with the same clip rectangle and stencil step
+    Shapes(Vec<(Primitives, Option<ClipBox>, StencilStep)>),
+    /// Text areas drawn by the text renderer with the same index
+    Text(usize),
+    /// A sample plot of an object drawn by the plot shader with the clip rectangle and the mask depth
+    Plot(ObjectId, usize, Option<ClipBox>, u32),
+}
+
+/// Shapes drawn with one draw call, a range of the indices or instances of a layer or an object.
+#[derive(Clone, PartialEq)]
+enum Primitives {
+    /// Indices of tessellated triangles
+    Triangles(Range<u32>),
+    /// Rectangles and circles drawn as instances
+    Instances(Range<u32>),
+}
+
+impl Primitives {
+    /// Returns the range of indices or instances.
+    fn range(&self) -> &Range<u32> {
+        match self {
+            Primitives::Triangles(range) | Primitives::Instances(range) => range,
+        }
+    }
+
+    /// Returns the primitives moved behind the first index and instance, when merging objects into a layer.
+    fn offset(&self, first_index: u32, first_instance: u32) -> Self {
+        match self {
+            Primitives::Triangles(range) => Primitives::Triangles(range.start + first_index..range.end + first_index),
+            Primitives::Instances(range) => Primitives::Instances(range.start + first_instance..range.end + first_instance),
+        }
+    }
+
+    /// Extends the range by the primitives of the same kind directly following it, returns false if they don't.
+    fn extend(&mut self, next: &Primitives) -> bool {
+        match (self, next) {
+            (Primitives::Triangles(range), Primitives::Triangles(next)) | (Primitives::Instances(range), Primitives::Instances(next))
+                if range.end == next.start => {
+                range.end = next.end;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// How a batch of indices uses the mask stencil, with the depth of the masks around it.
+#[derive(Clone, Copy, PartialEq)]
+enum StencilStep {
+    /// Draw colors where all active masks cover the pixel
+    Test(u32),
+    /// Add a mask within the active masks
+    Push(u32),
+    /// Remove the mask added with the same indices
+    Pop(u32),
+}
+
+/// The GPU buffers of a layer, kept and written again when the layer is prepared again.
+struct LayerBuffers {
+    vertices: graphics::GrowableBuffer,
+    indices: graphics::GrowableBuffer,
+    /// Instances of the rectangles and circles
+    instances: graphics::GrowableBuffer,
+}
+
+impl LayerBuffers {
+    /// Creates the buffers of a layer, allocated when the layer is first written.
+    fn new() -> Self {
+        Self {
+            vertices: graphics::GrowableBuffer::new("Vertex Buffer", wgpu::BufferUsages::VERTEX),
+            indices: graphics::GrowableBuffer::new("Index Buffer", wgpu::BufferUsages::INDEX),
+            instances: graphics::GrowableBuffer::new("Instance Buffer", wgpu::BufferUsages::VERTEX),
+        }
+    }
+}
+
+/// A layer prepared for drawing, kept until its objects change.
+pub(crate) struct PreparedLayer {
+    /// Vertices, indices and instances of the shapes
+    buffers: LayerBuffers,
+    /// Shapes and text in drawing order
+    steps: Vec<DrawStep>,
+    /// Text renderers prepared with the text areas of the text steps
+    text_renderers: Vec<TextRenderer>,
+    /// The objects of the layer prepared separately, reused while they don't change
+    objects: HashMap<ObjectId, PreparedObject>,
+    /// Ids of the prepared objects in drawing order
+    order: Vec<ObjectId>,
+    /// The camera the layer was prepared with
+    camera: Camera,
+    /// The camera the text areas were last mapped to screen pixels with
+    text_camera: Camera,
+    /// The world area whose objects were prepared, larger than the view so the layer is reused while panning
+    area: Bounds,
+    /// Whether the layer contains text
+    has_text: bool,
+    /// The font generation the text of the layer was shaped with
+    font_generation: u64,
+}
+
+impl PreparedLayer {
+    /// Returns true if the world area in view can be drawn with the camera and fonts without preparing the layer again.
+    fn is_valid_for(&self, camera: &Camera, visible_area: &Bounds, font_generation: u64) -> bool {
+        fits_camera(&self.camera, camera)
+            && fits_fonts(self.font_generation, font_generation, self.has_text)
+            && self.area.contains(visible_area)
+    }
+
+    /// Returns the allocated bytes of the GPU buffers of the shapes.
+    fn buffer_size(&self) -> u64 {
+        self.buffers.vertices.size() + self.buffers.indices.size() + self.buffers.instances.size()
+    }
+}
+
+/// The tessellated shapes and shaped text of one object of a layer.
+struct PreparedObject {
+    /// Vertices and indices of the shapes, indices start at the first vertex of the object
+    buffers: VertexBuffers<ColoredVertex, u32>,
+    /// Rectangles and circles drawn as instances
+    instances: Vec<ShapeInstance>,
+    /// Shapes and text areas in drawing order
+    steps: Vec<ObjectStep>,
+    /// Shaped text of the text objects, shared with the text cache
+    glyph_buffers: Vec<Arc<GlyphonBuffer>>,
+    /// Inline icons of each glyph buffer
+    icon_glyphs: Vec<Vec<CustomGlyph>>,
+    /// Sample plots drawn from their samples on the GPU
+    plots: Vec<PreparedPlot>,
+    /// The camera the object was prepared with
+    camera: Camera,
+    /// Whether the object contains text
+    has_text: bool,
+    /// The font generation the text of the object was shaped with
+    font_generation: u64,
+    /// The world area the sample plots drawn as lines were limited to
+    plot_area: Option<Bounds>,
+}
+
+impl PreparedObject {
+    /// Returns true if the object can be drawn in the area with the camera and fonts without preparing it again.
+    fn is_valid_for(&self, camera: &Camera, font_generation: u64, area: &Bounds) -> bool {
+        fits_camera(&self.camera, camera)
+            && fits_fonts(self.font_generation, font_generation, self.has_text)
+            && self.plot_area.is_none_or(|plot_area| plot_area.contains(area))
+    }
+}
+
+/// A part of an object drawn with the same kind of draw calls.
+enum ObjectStep {
+    /// Triangles or instances of the object drawn with the clip rectangle and stencil step
+    Shapes(Primitives, Option<ClipBox>, StencilStep),
+    /// Text drawn by the text renderer
+    Text(ObjectTextArea),
+    /// The sample plot with the index drawn with the clip rectangle and the mask depth
+    Plot(usize, Option<ClipBox>, u32),
+}
+
+/// A sample plot drawn by the plot shader from the storage buffer of its samples.
+///
+/// Only its uniforms change with the view, so panning and zooming need no geometry.
+struct PreparedPlot {
+    plot: SamplePlot,
+    /// The transform to world coordinates, including the transforms of the groups
+    transform: Transform,
+    /// Linear color with the opacity of the groups
+    color: [f32; 4],
+    uniform_buffer: wgpu::Buffer,
+    /// The uniforms and the samples of the plot
+    bind_group: wgpu::BindGroup,
+    /// Number of instances drawing the samples in the prepared area
+    instances: u32,
+}
+
+impl PreparedPlot {
+    /// Uploads the samples of the plot if they are not on the GPU yet, or returns `None` if the plot has to be tessellated,
+    /// when the device has no storage buffers in vertex shaders or the samples don't fit in a storage buffer binding.
+    fn new(gfx: &mut Graphics, plot: &SamplePlot, transform: Transform, color: [f32; 4]) -> Option<Self> {
+        let pipeline = gfx.plot_pipeline.as_ref()?;
+        let samples = gfx.sample_buffers.get_or_upload(&gfx.device, &plot.samples)?;
+        let uniform_buffer = gfx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Plot Uniform Buffer"),
+            size: size_of::<PlotUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = gfx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Plot Bind Group"),
+            layout: &pipeline.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: samples.as_entire_binding() },
+            ],
+        });
+        Some(Self { plot: plot.clone(), transform, color, uniform_buffer, bind_group, instances: 0 })
+    }
+
+    /// Writes the uniforms drawing the samples in the world area with the current camera.
+    ///
+    /// When a pixel column holds several samples, each instance draws the range of the samples in about a column.
+    fn update(&mut self, gfx: &Graphics, area: &Bounds) {
+        let plot = &self.plot;
+        let to_pixels = self.transform.then(gfx.camera.transform());
+        let pixels_per_sample = plot.x_step.abs() * to_pixels.a.hypot(to_pixels.b);
+        let samples_per_instance = if pixels_per_sample > 0.0 && pixels_per_sample < 1.0 {
+            (1.0 / pixels_per_sample).floor().min(u32::MAX as f32) as u32
+        } else {
+            1
+        };
+        // The samples in the area, none if the transform collapses the plot
+        let range = match self.transform.inverse() {
+            Some(inverse) => {
+                let local = area.transformed(&inverse);
+                plot.sample_range(local.left, local.right)
+            }
+            None => 0..0,
+        };
+        let (first_sample, last_sample) = (range.start as u32, range.end.saturating_sub(1) as u32);
+        self.instances = last_sample.saturating_sub(first_sample).div_ceil(samples_per_instance);
+        let transform = self.transform;
+        let uniforms = PlotUniforms {
+            transform: [transform.a, transform.b, transform.c, transform.d],
+            translation: [transform.e, transform.f],
+            origin: [plot.x, plot.y],
+            x_step: plot.x_step,
+            y_scale: plot.y_scale,
+            half_width: plot.width / 2.0,
+            style: match plot.style {
+                PlotStyle::Polyline => 0,
+                PlotStyle::Step => 1,
+            },
+            color: self.color,
+            first_sample,
+            last_sample,
+            samples_per_instance,
+            _padding: 0,
+        };
+        gfx.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+    }
+}
+
+/// A glyphon text area of an object in world coordinates, mapped to screen pixels by the camera when the text is prepared.
+struct ObjectTextArea {
+    /// Index of the glyph buffer and icons of the text
+    buffer: usize,
+    /// The world position of the top left corner of the text
+    left: f32,
+    top: f32,
+    /// Screen pixels per text unit, which only changes with the zoom of the camera
+    scale: f32,
+    /// The text bounds clipped by the clip rectangles of its groups
+    bounds: ClipBox,
+    color: glyphon::Color,
+}
+
+/// Fraction of the view size beyond each edge whose objects are prepared with the objects in view.
+const CULL_MARGIN: f32 = 0.5;
+
+/// Returns true if objects prepared with the camera `prepared` can be drawn with `camera`.
+///
+/// Shapes are positioned by the camera in the vertex shader and text areas when the text is prepared,
+/// but both are prepared for its zoom.
+fn fits_camera(prepared: &Camera, camera: &Camera) -> bool {
+    camera.scale_x == prepared.scale_x && camera.scale_y == prepared.scale_y
+}
+
+/// Returns true if text shaped with the font generation `prepared` can be drawn with the current fonts.
+fn fits_fonts(prepared: u64, font_generation: u64, has_text: bool) -> bool {
+    !has_text || prepared == font_generation
+}
+
+// The main draw function for rendering the layers of 2D objects using wgpu
+pub(crate) fn draw(gfx: &mut Graphics, layers: &mut Layers<PreparedLayer>) {
     // Try to get the current frame's texture from the GPU surface
     let output = match gfx.surface.get_current_texture() {
         Ok(texture) => texture,
@@ -35,133 +308,167 @@ pub fn draw(gfx: &mut Graphics, objects: &[crate::Object2d]) {
     // Create views for the main output texture and the MSAA (anti-aliasing) texture
     let view = output.texture.create_view(&TextureViewDescriptor::default());
     let msaa_view = gfx.msaa_texture.create_view(&TextureViewDescriptor::default());
+    let stencil_view = gfx.stencil_texture.create_view(&TextureViewDescriptor::default());
 
-    // Lock the font system for text rendering
-    let mut font_system = FONT_SYSTEM.get()
-        .expect_throw("FontSystem not initialized")
-        .lock()
-        .expect_throw("Failed to lock FontSystem Mutex");
+    // Shapes not tessellated and text not shaped for a while are evicted from the caches first
+    gfx.tessellation_cache.next_frame();
+    gfx.sample_buffers.evict_dropped();
+    if gfx.text_cache.next_frame() {
+        // Registered or removed fonts change the size of text, so its bounds are computed again
+        layers.reindex();
+    }
+    let font_generation = gfx.text_cache.generation();
 
-    // Prepare glyph buffers for all text objects
-    let mut glyph_buffers: Vec<GlyphonBuffer> = Vec::new();
+    // Only the objects in view and within a margin around it are prepared and drawn,
+    // layers are prepared again when they change or the view leaves the prepared area
+    let visible_area = gfx.camera.view_bounds(gfx.surface_config.width as f32, gfx.surface_config.height as f32);
+    let prepared_area = visible_area.expanded(visible_area.width() * CULL_MARGIN, visible_area.height() * CULL_MARGIN);
+    // Objects are found before locking the font system, measuring text for their bounds locks it too
+    let visible: Vec<Option<Vec<usize>>> = layers.iter().map(|layer| {
+        let valid = layer.cache.as_ref().is_some_and(|prepared| prepared.is_valid_for(&gfx.camera, &visible_area, font_generation));
+        (layer.dirty || !valid).then(|| layer.visible(&prepared_area))
+    }).collect();
 
-    // Loop through all objects and collect text buffers
-    for obj in objects {
-        if let crate::Object2d::Text(text) = obj {
-            // Set up text metrics and buffer
-            let text_width_f32 = text.width;
-            let text_height_f32 = text.height;
-            let line_height_pixels = text.font_size * text.line_height_multiplier;
-            let mut buffer = GlyphonBuffer::new(&mut font_system, Metrics::new(text.font_size, line_height_pixels));
-            buffer.set_size(&mut font_system, Some(text_width_f32), Some(text_height_f32));
-
-            // Convert font family to glyphon format
-            let glyphon_family = match &text.family {
-                crate::object2d::Family::Name(name) => GlyphonFamily::Name(name.as_ref()),
-                crate::object2d::Family::SansSerif => GlyphonFamily::SansSerif,
-                crate::object2d::Family::Serif => GlyphonFamily::Serif,
-                crate::object2d::Family::Monospace => GlyphonFamily::Monospace,
-                crate::object2d::Family::Cursive => GlyphonFamily::Cursive,
-                crate::object2d::Family::Fantasy => GlyphonFamily::Fantasy,
-            };
+    // Create the text atlas again when glyphs no longer in use make it exceed the budget
+    let recreate_atlas = gfx.atlas_size.bytes() > gfx.memory_budget.glyph_atlas && gfx.atlas_size.can_shrink();
+    if recreate_atlas {
+        graphics::recreate_atlas(gfx);
+    }
 
-            let family_for_query = match &glyphon_family {
-                GlyphonFamily::Name(name) => glyphon::fontdb::Family::Name(name),
-                GlyphonFamily::SansSerif => glyphon::fontdb::Family::SansSerif,
-                GlyphonFamily::Serif => glyphon::fontdb::Family::Serif,
-                GlyphonFamily::Monospace => glyphon::fontdb::Family::Monospace,
-                GlyphonFamily::Cursive => glyphon::fontdb::Family::Cursive,
-                GlyphonFamily::Fantasy => glyphon::fontdb::Family::Fantasy,
-            };
+    // Prepare the layers changed since they were last drawn, the others are drawn from their cache
+    {
+        // Lock the font system for text rendering
+        let mut font_system = FONT_SYSTEM.get()
+            .expect_throw("FontSystem not initialized")
+            .lock()
+            .expect_throw("Failed to lock FontSystem Mutex");
+        gfx.atlas_size.begin();
+        // Text areas are mapped to screen pixels when the text is prepared, so panning prepares the text of cached layers again
+        let camera_moved = layers.iter()
+            .filter_map(|layer| layer.cache.as_ref())
+            .any(|prepared| prepared.has_text && prepared.text_camera != gfx.camera);
+        let mut text_prepared = recreate_atlas || camera_moved;
+        let mut cached: Vec<bool> = Vec::with_capacity(visible.len());
+        for (layer, visible) in layers.iter_mut().zip(visible) {
+            cached.push(visible.is_none());
+            if let Some(visible) = visible {
+                // Text renderers and the prepared objects unchanged since the previous frame are reused
+                let previous = layer.cache.take();
+                let prepared = prepare_layer(gfx, layer, &visible, prepared_area, previous, &mut font_system);
+                text_prepared |= prepared.has_text;
+                layer.cache = Some(prepared);
+                layer.mark_prepared();
+            }
+        }
+        // The atlas is trimmed after every frame, so new glyphs may have replaced the glyphs of the cached layers
+        if text_prepared {
+            for (layer, cached) in layers.iter_mut().zip(cached) {
+                if let Some(prepared) = &mut layer.cache && cached && prepared.has_text {
+                    prepare_text(gfx, prepared, &mut font_system);
+                }
+            }
+            // All text in view was prepared, so the glyphs counted are the glyphs in use
+            gfx.atlas_size.finish();
+        }
+    }
 
-            // Check if the font exists, warn if not
-            let font_query = glyphon::fontdb::Query {
-                families: &[family_for_query],
-                ..Default::default()
-            };
-            let font_exists = font_system.db().query(&font_query).is_some();
-            if !font_exists {
-                let warning_message = format!("Warning: Font family '{:?}' not found. Falling back to default.", text.family);
-                web_sys::console::warn_1(&JsValue::from_str(&warning_message));
+    // Create a command encoder for the GPU commands
+    let mut encoder = gfx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Render Encoder") });
+    {
+        // Begin a render pass (drawing session)
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &msaa_view,
+                resolve_target: Some(&view),
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 }), store: wgpu::StoreOp::Store },
+            })],
+            // The stencil holds the number of active masks covering each pixel
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &stencil_view,
+                depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Discard }),
+                stencil_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(0), store: wgpu::StoreOp::Discard }),
+            }),
+            timestamp_writes: None, occlusion_query_set: None,
+        });
+
+        // Draw the layers in the order of their z-index
+        for layer in layers.iter() {
+            if let Some(prepared) = &layer.cache {
+                render_layer(&mut render_pass, gfx, prepared);
             }
+        }
+    }
+    // Submit all drawing commands to the GPU
+    gfx.queue.submit(std::iter::once(encoder.finish()));
+    // Glyphs not used by the next prepared text can be replaced, so the atlas grows only with the glyphs in view
+    gfx.atlas.trim();
+    // Present the final image to the screen
+    output.present();
+}
 
-            // Set up text attributes (color, weight, style)
-            let glyphon_color = text.color.to_glyphon_color();
-            let attrs = Attrs::new()
-                .family(glyphon_family)
-                .color(glyphon_color)
-                .weight({
-                    use crate::object2d::FontWeight::*;
-                    match text.weight {
-                        Thin => glyphon::fontdb::Weight::THIN,
-                        ExtraLight => glyphon::fontdb::Weight::EXTRA_LIGHT,
-                        Light => glyphon::fontdb::Weight::LIGHT,
-                        Regular => glyphon::fontdb::Weight::NORMAL,
-                        Medium => glyphon::fontdb::Weight::MEDIUM,
-                        SemiBold => glyphon::fontdb::Weight::SEMIBOLD,
-                        Bold => glyphon::fontdb::Weight::BOLD,
-                        ExtraBold => glyphon::fontdb::Weight::EXTRA_BOLD,
-                        Black => glyphon::fontdb::Weight::BLACK,
-                    }
-                })
-                .style(if text.italic { glyphon::fontdb::Style::Italic } else { glyphon::fontdb::Style::Normal });
-            buffer.set_text(&mut font_system, &text.text, &attrs, Shaping::Advanced);
+/// Tessellates the shapes and shapes the text of the object for drawing with the current camera.
+fn prepare_object(gfx: &mut Graphics, object: &crate::Object2d, area: &Bounds, font_system: &mut FontSystem) -> PreparedObject {
+    // Flatten groups into objects with their accumulated transform, opacity, clip and masks
+    let objects = flatten(std::slice::from_ref(object));
+    // Shapes are positioned by the camera in the vertex shader, text areas and scissor rectangles here
+    let camera = gfx.camera.transform();
+
+    // Text is shaped with the registered fonts, so objects with text are prepared again when they change
+    let has_text = objects.iter().any(|flat| matches!(flat.step, FlatStep::Draw(crate::Object2d::Text(_))));
+
+    // Prepare glyph buffers for all text objects
+    let mut glyph_buffers: Vec<Arc<GlyphonBuffer>> = Vec::new();
+    // Inline icons of each text buffer
+    let mut icon_glyphs: Vec<Vec<CustomGlyph>> = Vec::new();
+
+    // Loop through all objects and collect text buffers
+    for flat in &objects {
+        if let FlatStep::Draw(crate::Object2d::Text(text)) = flat.step {
+            // Text shaped for another object or an earlier frame is reused
+            let buffer = gfx.text_cache.get_or_shape(text, || text_shaping::shape_text(font_system, text));
+            icon_glyphs.push(text_shaping::icon_glyphs(&buffer, text));
             glyph_buffers.push(buffer);
         }
     }
 
-    // Prepare text areas for rendering (position, bounds, etc.)
-    let mut text_areas: Vec<TextArea> = Vec::new();
-    let mut buffer_idx = 0;
-    for obj in objects {
-        if let crate::Object2d::Text(text) = obj {
-            let glyphon_color = text.color.to_glyphon_color();
-            let text_width_f32 = text.width;
-            let text_height_f32 = text.height;
-            let text_area = TextArea {
-                buffer: &glyph_buffers[buffer_idx],
-                left: text.left,
-                top: text.top,
-                bounds: TextBounds {
-                    left: text.left as i32,
-                    top: text.top as i32,
-                    right: (text.left + text_width_f32) as i32,
-                    bottom: (text.top + text_height_f32) as i32,
-                },
-                default_color: glyphon_color,
-                scale: 1.0,
-                custom_glyphs: &[],
-            };
-            text_areas.push(text_area);
-            buffer_idx += 1;
-        }
+    // Helper function to get the scale of text drawn as a glyphon text area, in screen pixels per text unit
+    // Glyphon only translates and scales text uniformly, and text areas are not masked by the stencil.
+    fn text_area_scale(transform: Transform, camera: Transform, mask_depth: u32) -> Option<f32> {
+        transform.then(camera).as_translate_scale().filter(|_| mask_depth == 0).map(|(_, _, scale)| scale)
     }
 
-    // Prepare the text renderer with all text areas
-    match gfx.text_renderer.prepare(
-        &gfx.device, &gfx.queue, &mut font_system, &mut gfx.atlas, &gfx.viewport,
-        text_areas.into_iter(), &mut gfx.swash_cache,
-    ) {
-        Ok(_) => {}
-        Err(e) => console::error_1(&JsValue::from_str(&format!("Error preparing text renderer: {:?}", e))),
+    // Helper function to get the glyphon text area of the text, or None if it is drawn from outlines
+    // Rotated, skewed, non-uniformly scaled or masked text is tessellated from glyph outlines with the shapes.
+    fn text_area(text: &Text, buffer: usize, flat: &FlatObject, camera: Transform) -> Option<ObjectTextArea> {
+        let scale = text_area_scale(flat.transform, camera, flat.mask_depth)?;
+        let glyphon_color = Color { a: text.color.a * flat.opacity, ..text.color }.to_glyphon_color();
+        // The text area is kept in world coordinates, so it can be drawn again after the camera pans
+        let (left, top) = flat.transform.transform_point(text.left, text.top);
+        // Text is clipped by its own bounds and the clip rectangles of its groups
+        let mut bounds = ClipBox::transformed(text.left, text.top, text.width, text.height, &flat.transform);
+        if let Some(clip) = &flat.clip {
+            bounds = bounds.intersect(clip);
+        }
+        Some(ObjectTextArea { buffer, left, top, scale, bounds, color: glyphon_color })
     }
 
-    // Create vertex and tessellator buffers for shape rendering
+    // Create vertex buffers for shape rendering, shapes are tessellated through the cache of recently drawn shapes
     let mut buffers: VertexBuffers<ColoredVertex, u32> = VertexBuffers::new();
-    let mut fill_tessellator = FillTessellator::new();
-    let mut stroke_tessellator = StrokeTessellator::new();
 
     // Helper function to draw rectangles, including fill and optional border
     // This function takes a Rectangle object and draws it to the screen.
     // It handles both the filled area and the border (if any).
     fn draw_rectangle(
         rect: &Rectangle,
+        transform: Transform,
+        tolerance: f32,
+        opacity: f32,
         buffers: &mut VertexBuffers<ColoredVertex, u32>,
-        fill_tessellator: &mut FillTessellator,
-        stroke_tessellator: &mut StrokeTessellator,
+        cache: &mut TessellationCache,
     ) {
         // Convert the rectangle's color to a linear color format for rendering
-        let linear_color = rect.color.to_linear();
+        let linear_color = with_opacity(rect.color.to_linear(), opacity);
         // Get the border width, or 0 if not set
         let border_width = rect.border_width.unwrap_or(0.0);
         // Check if the rectangle has a visible border
@@ -173,65 +480,52 @@ pub fn draw(gfx: &mut Graphics, objects: &[crate::Object2d]) {
             point(rect.position.x + fill_offset, rect.position.y + fill_offset),
             point(rect.position.x + rect.size.width - fill_offset, rect.position.y + rect.size.height - fill_offset),
         );
-        let mut builder = Path::builder();
-        // If any corner is rounded, add a rounded rectangle path
-        if rect.rounded_corners.top_left > 0.0 || rect.rounded_corners.top_right > 0.0 || rect.rounded_corners.bottom_left > 0.0 || rect.rounded_corners.bottom_right > 0.0 {
-            builder.add_rounded_rectangle(&fill_box, &LyonBorderRadii {
-                top_left: (rect.rounded_corners.top_left.max(0.0) - fill_offset).max(0.0),
-                top_right: (rect.rounded_corners.top_right.max(0.0) - fill_offset).max(0.0),
-                bottom_left: (rect.rounded_corners.bottom_left.max(0.0) - fill_offset).max(0.0),
-                bottom_right: (rect.rounded_corners.bottom_right.max(0.0) - fill_offset).max(0.0),
-            }, Winding::Positive);
-        } else {
-            // Otherwise, add a simple rectangle path
-            builder.add_rectangle(&fill_box, Winding::Positive);
+        // Corner radii shrunk by the inset of a box inside the rectangle
+        let corners = &rect.rounded_corners;
+        let is_rounded = corners.top_left > 0.0 || corners.top_right > 0.0 || corners.bottom_left > 0.0 || corners.bottom_right > 0.0;
+        let inset_radii = |inset: f32| LyonBorderRadii {
+            top_left: (corners.top_left.max(0.0) - inset).max(0.0),
+            top_right: (corners.top_right.max(0.0) - inset).max(0.0),
+            bottom_left: (corners.bottom_left.max(0.0) - inset).max(0.0),
+            bottom_right: (corners.bottom_right.max(0.0) - inset).max(0.0),
+        };
+        // Helper function to build the path of the box, with rounded corners if any corner is rounded
+        fn box_path(rect_box: &Box2D, radii: Option<LyonBorderRadii>) -> Path {
+            let mut builder = Path::builder();
+            match radii {
+                Some(radii) => builder.add_rounded_rectangle(rect_box, &radii, Winding::Positive),
+                None => builder.add_rectangle(rect_box, Winding::Positive),
+            }
+            builder.build()
+        }
+        // Helper function to get the cache key of the box with the radii and the tolerance
+        fn box_key(kind: &str, rect_box: &Box2D, radii: Option<LyonBorderRadii>, values: [f32; 2]) -> u64 {
+            let radii = radii.map_or([0.0; 4], |radii| [radii.top_left, radii.top_right, radii.bottom_left, radii.bottom_right]);
+            TessellationCache::key(kind, [rect_box.min.x, rect_box.min.y, rect_box.max.x, rect_box.max.y].into_iter().chain(radii).chain(values))
         }
-        let fill_path = builder.build();
         // Draw the filled part of the rectangle if it is visible
         if rect.color.a > 0.0 && fill_box.size().width > 0.0 && fill_box.size().height > 0.0 {
-            fill_tessellator.tessellate_path(
-                &fill_path,
-                &FillOptions::default(),
-                &mut BuffersBuilder::new(buffers, |vertex: FillVertex| ColoredVertex {
-                    position: [vertex.position().x, vertex.position().y],
-                    color: linear_color,
-                }),
-            ).unwrap_throw();
+            let radii = is_rounded.then(|| inset_radii(fill_offset));
+            let key = box_key("rectangle fill", &fill_box, radii, [tolerance, 0.0]);
+            let geometry = cache.fill(Some(key), || box_path(&fill_box, radii), &FillOptions::tolerance(tolerance)).unwrap_throw();
+            append_geometry(buffers, geometry, &transform, linear_color);
         }
         // Draw the border if needed
         if has_border && fill_box.size().width > 0.0 && fill_box.size().height > 0.0 {
             // Convert the border color to linear format
-            let linear_border_color = rect.border_color.unwrap_throw().to_linear();
+            let linear_border_color = with_opacity(rect.border_color.unwrap_throw().to_linear(), opacity);
             // Calculate the area for the border (centered on the rectangle's edge)
             let border_box = Box2D::new(
                 point(rect.position.x + border_width / 2.0, rect.position.y + border_width / 2.0),
                 point(rect.position.x + rect.size.width - border_width / 2.0, rect.position.y + rect.size.height - border_width / 2.0),
             );
-            let mut border_builder = Path::builder();
-            // Add a rounded rectangle path for the border if needed
-            if rect.rounded_corners.top_left > 0.0 || rect.rounded_corners.top_right > 0.0 || rect.rounded_corners.bottom_left > 0.0 || rect.rounded_corners.bottom_right > 0.0 {
-                border_builder.add_rounded_rectangle(&border_box, &LyonBorderRadii {
-                    top_left: (rect.rounded_corners.top_left.max(0.0) - border_width / 2.0).max(0.0),
-                    top_right: (rect.rounded_corners.top_right.max(0.0) - border_width / 2.0).max(0.0),
-                    bottom_left: (rect.rounded_corners.bottom_left.max(0.0) - border_width / 2.0).max(0.0),
-                    bottom_right: (rect.rounded_corners.bottom_right.max(0.0) - border_width / 2.0).max(0.0),
-                }, Winding::Positive);
-            } else {
-                // Otherwise, add a simple rectangle path for the border
-                border_builder.add_rectangle(&border_box, Winding::Positive);
-            }
-            let border_path = border_builder.build();
+            let radii = is_rounded.then(|| inset_radii(border_width / 2.0));
+            let key = box_key("rectangle border", &border_box, radii, [tolerance, border_width]);
             // Set border options (width, etc.)
-            let options = StrokeOptions::default().with_line_width(border_width);
+            let options = StrokeOptions::tolerance(tolerance).with_line_width(border_width);
             // Draw the border
-            stroke_tessellator.tessellate_path(
-                &border_path,
-                &options,
-                &mut BuffersBuilder::new(buffers, |vertex: StrokeVertex| ColoredVertex {
-                    position: [vertex.position().x, vertex.position().y],
-                    color: linear_border_color,
-                }),
-            ).unwrap_throw();
+            let geometry = cache.stroke(Some(key), || box_path(&border_box, radii), &options).unwrap_throw();
+            append_geometry(buffers, geometry, &transform, linear_border_color);
         }
     }
 
@@ -240,149 +534,626 @@ pub fn draw(gfx: &mut Graphics, objects: &[crate::Object2d]) {
     // It handles both the filled area and the border (if any).
     fn draw_circle(
         circle: &Circle,
+        transform: Transform,
+        tolerance: f32,
+        opacity: f32,
         buffers: &mut VertexBuffers<ColoredVertex, u32>,
-        fill_tessellator: &mut FillTessellator,
-        stroke_tessellator: &mut StrokeTessellator,
+        cache: &mut TessellationCache,
     ) {
         // Convert the circle's color to a linear color format for rendering
-        let linear_color = circle.color.to_linear();
+        let linear_color = with_opacity(circle.color.to_linear(), opacity);
         // Get the border width, or 0 if not set
         let border_width = circle.border_width.unwrap_or(0.0);
         // Check if the circle has a visible border
         let has_border = border_width > 0.0 && circle.border_color.map_or(false, |c| c.a > 0.0);
         // If there is a border, shrink the fill radius so the border fits inside the circle
         let fill_radius = if has_border { circle.radius - border_width } else { circle.radius };
-        let mut builder = Path::builder();
-        // Add a circle path for the filled area
-        builder.add_circle(point(circle.center.x, circle.center.y), fill_radius, Winding::Positive);
-        let fill_path = builder.build();
+        // Helper function to build the path of a circle
+        fn circle_path(center: LyonPoint, radius: f32) -> Path {
+            let mut builder = Path::builder();
+            builder.add_circle(center, radius, Winding::Positive);
+            builder.build()
+        }
+        let center = point(circle.center.x, circle.center.y);
         // Draw the filled part of the circle if it is visible
         if circle.color.a > 0.0 && fill_radius > 0.0 {
-            fill_tessellator.tessellate_path(
-                &fill_path,
-                &FillOptions::default(),
-                &mut BuffersBuilder::new(buffers, |vertex: FillVertex| ColoredVertex {
-                    position: [vertex.position().x, vertex.position().y],
-                    color: linear_color,
-                }),
-            ).unwrap_throw();
+            let key = TessellationCache::key("circle fill", [center.x, center.y, fill_radius, tolerance]);
+            let geometry = cache.fill(Some(key), || circle_path(center, fill_radius), &FillOptions::tolerance(tolerance)).unwrap_throw();
+            append_geometry(buffers, geometry, &transform, linear_color);
         }
         // Draw the border if needed
         if has_border && fill_radius > 0.0 {
             // Convert the border color to linear format
-            let linear_border_color = circle.border_color.unwrap_throw().to_linear();
-            let mut border_builder = Path::builder();
-            // Add a circle path for the border (centered on the edge)
-            border_builder.add_circle(
-                point(circle.center.x, circle.center.y),
-                fill_radius + border_width / 2.0,
-                Winding::Positive,
-            );
-            let border_path = border_builder.build();
+            let linear_border_color = with_opacity(circle.border_color.unwrap_throw().to_linear(), opacity);
+            // The border is centered on the edge of the fill
+            let border_radius = fill_radius + border_width / 2.0;
+            let key = TessellationCache::key("circle border", [center.x, center.y, border_radius, border_width, tolerance]);
             // Set border options (width, etc.)
-            let options = StrokeOptions::default().with_line_width(border_width);
+            let options = StrokeOptions::tolerance(tolerance).with_line_width(border_width);
             // Draw the border
-            stroke_tessellator.tessellate_path(
-                &border_path,
-                &options,
-                &mut BuffersBuilder::new(buffers, |vertex: StrokeVertex| ColoredVertex {
-                    position: [vertex.position().x, vertex.position().y],
-                    color: linear_border_color,
-                }),
-            ).unwrap_throw();
+            let geometry = cache.stroke(Some(key), || circle_path(center, border_radius), &options).unwrap_throw();
+            append_geometry(buffers, geometry, &transform, linear_border_color);
         }
     }
 
+    // Helper function to draw lines, with round caps and joins
+    // Lines with level of detail are reduced to a few points per pixel column at the camera's zoom.
+    fn draw_line(
+        line: &Line,
+        transform: Transform,
+        camera: Transform,
+        tolerance: f32,
+        opacity: f32,
+        buffers: &mut VertexBuffers<ColoredVertex, u32>,
+        cache: &mut TessellationCache,
+    ) {
+        // Convert the line's color to a linear color format for rendering
+        let linear_color = with_opacity(line.color.to_linear(), opacity);
+        // Helper function to build the path through the points of the line
+        fn line_path(points: &[crate::backend::Point]) -> Path {
+            let mut builder = Path::builder();
+            // Start the line at the first point
+            builder.begin(point(points[0].x, points[0].y));
+            // Add each subsequent point to the path
+            for next in &points[1..] {
+                builder.line_to(point(next.x, next.y));
+            }
+            // End the path (false = not closed)
+            builder.end(false);
+            builder.build()
+        }
+        // Draw the line if it is visible, a line needs at least two points
+        if line.points.len() >= 2 && line.color.a > 0.0 {
+            let points = line.lod_points(&transform.then(camera));
+            // Set line options: width, rounded ends and joins
+            let options = StrokeOptions::tolerance(tolerance)
+                .with_line_width(line.width)
+                .with_line_cap(LineCap::Round)
+                .with_line_join(LineJoin::Round);
+            let values = points.iter().flat_map(|point| [point.x, point.y]);
+            let key = TessellationCache::key("line", [line.width, tolerance].into_iter().chain(values));
+            // Draw the line
+            let geometry = cache.stroke(Some(key), || line_path(&points), &options).unwrap_throw();
+            append_geometry(buffers, geometry, &transform, linear_color);
+        }
+    }
+
+    // Helper function to get the fill color, border color and border width of a rectangle or circle instance
+    // Like when tessellated, the border is only drawn if it has a width and a visible color.
+    fn instance_colors(color: Color, border_color: Option<Color>, border_width: Option<f32>, opacity: f32) -> ([f32; 4], [f32; 4], f32) {
+        let fill = with_opacity(color.to_linear(), opacity);
+        let border_width = border_width.unwrap_or(0.0);
+        match border_color.filter(|border_color| border_width > 0.0 && border_color.a > 0.0) {
+            Some(border_color) => (fill, with_opacity(border_color.to_linear(), opacity), border_width),
+            None => (fill, [0.0; 4], 0.0),
+        }
+    }
+
+    // Helper function to get the instance drawing a rounded rectangle, or None if it has to be tessellated
+    // The signed distance is measured before the transform, so transforms collapsing the shape are tessellated.
+    fn shape_instance(
+        transform: &Transform,
+        (position, size): ([f32; 2], [f32; 2]),
+        radii: [f32; 4],
+        (color, border_color, border_width): ([f32; 4], [f32; 4], f32),
+    ) -> Option<ShapeInstance> {
+        transform.inverse()?;
+        // Radii are limited to half the width and height, like when tessellated
+        let max_radius = (size[0] / 2.0).min(size[1] / 2.0);
+        Some(ShapeInstance {
+            transform: [transform.a, transform.b, transform.c, transform.d],
+            translation: [transform.e, transform.f],
+            position,
+            size,
+            radii: radii.map(|radius| radius.clamp(0.0, max_radius)),
+            color,
+            border_color,
+            border_width,
+        })
+    }
+
+    // Helper function to add an instance to the steps, extending the last step if it draws the previous instance
+    fn push_instance(steps: &mut Vec<ObjectStep>, instances: &mut Vec<ShapeInstance>, instance: ShapeInstance, clip: Option<ClipBox>, stencil: StencilStep) {
+        let index = instances.len() as u32;
+        instances.push(instance);
+        let next = Primitives::Instances(index..index + 1);
+        if let Some(ObjectStep::Shapes(primitives, step_clip, step_stencil)) = steps.last_mut()
+            && *step_clip == clip && *step_stencil == stencil && primitives.extend(&next) {
+            return;
+        }
+        steps.push(ObjectStep::Shapes(next, clip, stencil));
+    }
+
+    // Helper function to add tessellated geometry to the buffers, mapped through the object's transform in a single color
+    fn append_geometry(buffers: &mut VertexBuffers<ColoredVertex, u32>, geometry: &Geometry, transform: &Transform, color: [f32; 4]) {
+        let first_vertex = buffers.vertices.len() as u32;
+        buffers.vertices.extend(geometry.vertices.iter().map(|&[x, y]| {
+            let (x, y) = transform.transform_point(x, y);
+            ColoredVertex { position: [x, y], color }
+        }));
+        buffers.indices.extend(geometry.indices.iter().map(|index| index + first_vertex));
+    }
+
+    // Helper function to get the tessellation tolerance keeping curves smooth after the transform
+    fn tolerance(transform: &Transform) -> f32 {
+        FillOptions::DEFAULT_TOLERANCE / transform.max_scale().max(1e-3)
+    }
+
+    // Helper function to get the clip rectangle of text drawn from outlines
+    // Like glyphon text areas, the text is clipped by its bounds, here their bounding box after the transform.
+    fn outline_text_clip(text: &Text, transform: &Transform, group_clip: Option<ClipBox>) -> ClipBox {
+        // Unbounded text (f32::MAX) is limited, so the transformed corners stay finite
+        const MAX_EXTENT: f32 = 1.0e6;
+        let bounds = ClipBox::transformed(text.left, text.top, text.width.min(MAX_EXTENT), text.height.min(MAX_EXTENT), transform);
+        group_clip.map_or(bounds, |clip| bounds.intersect(&clip))
+    }
+
+    // Helper function to build the path of a group mask
+    fn mask_path(mask: &Mask) -> Path {
+        match &mask.shape {
+            MaskShape::Rectangle { position, size, rounded_corners } => {
+                let mut builder = Path::builder();
+                let rect = Box2D::new(point(position.x, position.y), point(position.x + size.width, position.y + size.height));
+                builder.add_rounded_rectangle(&rect, &LyonBorderRadii {
+                    top_left: rounded_corners.top_left,
+                    top_right: rounded_corners.top_right,
+                    bottom_left: rounded_corners.bottom_left,
+                    bottom_right: rounded_corners.bottom_right,
+                }, Winding::Positive);
+                builder.build()
+            }
+            MaskShape::Circle { center, radius } => {
+                let mut builder = Path::builder();
+                builder.add_circle(point(center.x, center.y), *radius, Winding::Positive);
+                builder.build()
+            }
+            MaskShape::Path { commands } => {
+                let mut builder = Path::builder().with_svg();
+                icon_rasterizer::add_path_commands(&mut builder, commands);
+                builder.build()
+            }
+        }
+    }
+
+    // Helper function to scale the alpha of a linear color by the opacity of the object's groups
+    fn with_opacity(color: [f32; 4], opacity: f32) -> [f32; 4] {
+        [color[0], color[1], color[2], color[3] * opacity]
+    }
+
+    // Helper function to build the outlines of text with a transform glyphon can't render (rotation, skew, non-uniform scale)
+    // This function returns the outlines of the shaped glyphs, to be filled in the text color.
+    // Icons and color emoji layers are not included, emoji use their monochrome outlines if the font has them.
+    fn text_outline_path(
+        text: &Text,
+        buffer: &GlyphonBuffer,
+        font_system: &mut FontSystem,
+        swash_cache: &mut SwashCache,
+    ) -> Path {
+        let mut builder = Path::builder();
+        for run in buffer.layout_runs() {
+            for glyph in run.glyphs.iter() {
+                let physical_glyph = glyph.physical((0.0, 0.0), 1.0);
+                let Some(commands) = swash_cache.get_outline_commands(font_system, physical_glyph.cache_key) else {
+                    continue;
+                };
+                // Outlines have the origin on the baseline and the y axis pointing up
+                let origin_x = text.left + glyph.x + glyph.font_size * glyph.x_offset;
+                let origin_y = text.top + run.line_y + glyph.y - glyph.font_size * glyph.y_offset;
+                let to_point = |x: f32, y: f32| point(origin_x + x, origin_y - y);
+                let mut open = false;
+                for command in commands {
+                    match *command {
+                        Command::MoveTo(to) => {
+                            if open {
+                                builder.end(true);
+                            }
+                            builder.begin(to_point(to.x, to.y));
+                            open = true;
+                        }
+                        Command::LineTo(to) => { builder.line_to(to_point(to.x, to.y)); }
+                        Command::QuadTo(control, to) => {
+                            builder.quadratic_bezier_to(to_point(control.x, control.y), to_point(to.x, to.y));
+                        }
+                        Command::CurveTo(control_1, control_2, to) => {
+                            builder.cubic_bezier_to(
+                                to_point(control_1.x, control_1.y),
+                                to_point(control_2.x, control_2.y),
+                                to_point(to.x, to.y),
+                            );
+                        }
+                        Command::Close => {
+                            if open {
+                                builder.end(true);
+                                open = false;
+                            }
+                        }
+                    }
+                }
+                if open {
+                    builder.end(true);
+                }
+            }
+        }
+        builder.build()
+    }
+
+    // Shapes and text in drawing order, so later objects cover earlier ones like on the canvas backend
+    let mut steps: Vec<ObjectStep> = Vec::new();
+    // Rectangles and circles drawn as instances instead of tessellated triangles
+    let mut instances: Vec<ShapeInstance> = Vec::new();
+    // Indices of the pushed masks, drawn again to pop them
+    let mut mask_indices: Vec<Range<u32>> = Vec::new();
+    // Sample plots drawn on the GPU
+    let mut plots: Vec<PreparedPlot> = Vec::new();
+    // Set when sample plots are drawn as lines through the samples in the area
+    let mut plot_area = None;
+
     // Loop through all objects and draw them
-    for obj in objects {
-        match obj {
+    let mut buffer_idx = 0;
+    for flat in &objects {
+        let first_index = buffers.indices.len() as u32;
+        let transform = flat.transform;
+        let mut clip = flat.clip;
+        // Vertices are transformed after tessellation, curves are flattened finer when scaled up by the transform or camera
+        let tolerance = tolerance(&transform.then(camera));
+        let object = match flat.step {
+            FlatStep::Draw(object) => object,
+            FlatStep::PushMask(mask) => {
+                // Mask triangles only update the stencil, their color is never drawn
+                let options = FillOptions::tolerance(tolerance).with_fill_rule(FillRule::NonZero);
+                let geometry = gfx.tessellation_cache.fill(None, || mask_path(mask), &options).unwrap_throw();
+                append_geometry(&mut buffers, geometry, &transform, [0.0; 4]);
+                let indices = first_index..buffers.indices.len() as u32;
+                mask_indices.push(indices.clone());
+                steps.push(ObjectStep::Shapes(Primitives::Triangles(indices), clip, StencilStep::Push(flat.mask_depth)));
+                continue;
+            }
+            FlatStep::PopMask => {
+                if let Some(indices) = mask_indices.pop() {
+                    steps.push(ObjectStep::Shapes(Primitives::Triangles(indices), clip, StencilStep::Pop(flat.mask_depth)));
+                }
+                continue;
+            }
+        };
+        match object {
             crate::Object2d::Rectangle(rect) => {
-                // Draw a rectangle object
-                draw_rectangle(rect, &mut buffers, &mut fill_tessellator, &mut stroke_tessellator);
+                // Rectangles without an area are not drawn
+                if rect.size.width <= 0.0 || rect.size.height <= 0.0 {
+                    continue;
+                }
+                // Draw the rectangle as an instance if possible
+                let corners = &rect.rounded_corners;
+                let radii = [corners.top_left, corners.top_right, corners.bottom_right, corners.bottom_left];
+                let colors = instance_colors(rect.color, rect.border_color, rect.border_width, flat.opacity);
+                let shape = ([rect.position.x, rect.position.y], [rect.size.width, rect.size.height]);
+                if let Some(instance) = shape_instance(&transform, shape, radii, colors) {
+                    push_instance(&mut steps, &mut instances, instance, clip, StencilStep::Test(flat.mask_depth));
+                    continue;
+                }
+                // Otherwise tessellate the rectangle
+                draw_rectangle(rect, transform, tolerance, flat.opacity, &mut buffers, &mut gfx.tessellation_cache);
             }
             crate::Object2d::Circle(circle) => {
-                // Draw a circle object
-                draw_circle(circle, &mut buffers, &mut fill_tessellator, &mut stroke_tessellator);
+                // Circles without an area are not drawn
+                if circle.radius <= 0.0 {
+                    continue;
+                }
+                // Draw the circle as an instance of a rectangle rounded by its radius if possible
+                let colors = instance_colors(circle.color, circle.border_color, circle.border_width, flat.opacity);
+                let diameter = circle.radius * 2.0;
+                let shape = ([circle.center.x - circle.radius, circle.center.y - circle.radius], [diameter, diameter]);
+                if let Some(instance) = shape_instance(&transform, shape, [circle.radius; 4], colors) {
+                    push_instance(&mut steps, &mut instances, instance, clip, StencilStep::Test(flat.mask_depth));
+                    continue;
+                }
+                // Otherwise tessellate the circle
+                draw_circle(circle, transform, tolerance, flat.opacity, &mut buffers, &mut gfx.tessellation_cache);
             }
             crate::Object2d::Line(line) => {
-                // Draw a line object
-                // Convert the line's color to a linear color format for rendering
-                let linear_color = line.color.to_linear();
-                let mut builder = Path::builder();
-                // Only draw if there are at least two points (a line needs two points)
-                if line.points.len() >= 2 {
-                    // Start the line at the first point
-                    builder.begin(point(line.points[0].x, line.points[0].y));
-                    // Add each subsequent point to the path
-                    for i in 1..line.points.len() {
-                        builder.line_to(point(line.points[i].x, line.points[i].y));
-                    }
-                    // End the path (false = not closed)
-                    builder.end(false);
+                draw_line(line, transform, camera, tolerance, flat.opacity, &mut buffers, &mut gfx.tessellation_cache);
+            }
+            crate::Object2d::SamplePlot(plot) => {
+                // Plots without a segment or color are not drawn
+                if plot.samples.len() < 2 || plot.color.a <= 0.0 {
+                    continue;
+                }
+                // Draw the plot from its samples on the GPU if possible
+                let color = with_opacity(plot.color.to_linear(), flat.opacity);
+                if let Some(prepared) = PreparedPlot::new(gfx, plot, transform, color) {
+                    steps.push(ObjectStep::Plot(plots.len(), clip, flat.mask_depth));
+                    plots.push(prepared);
+                    continue;
+                }
+                // Otherwise tessellate the samples in the area as lines, none if the transform collapses the plot
+                let Some(inverse) = transform.inverse() else {
+                    continue;
+                };
+                let local = area.transformed(&inverse);
+                for line in plot.to_lines(plot.sample_range(local.left, local.right)) {
+                    draw_line(&line, transform, camera, tolerance, flat.opacity, &mut buffers, &mut gfx.tessellation_cache);
+                }
+                plot_area = Some(*area);
+            }
+            crate::Object2d::Text(text) => {
+                // Draw the text as a glyphon text area if glyphon can draw it
+                if let Some(text_area) = text_area(text, buffer_idx, flat, camera) {
+                    buffer_idx += 1;
+                    steps.push(ObjectStep::Text(text_area));
+                    continue;
                 }
-                let path = builder.build();
-                // Draw the line if it is visible
-                if line.points.len() >= 2 && line.color.a > 0.0 {
-                    // Set line options: width, rounded ends and joins
-                    let options = StrokeOptions::default()
-                        .with_line_width(line.width)
-                        .with_line_cap(LineCap::Round)
-                        .with_line_join(LineJoin::Round);
-                    // Draw the line
-                    stroke_tessellator.tessellate_path(
-                        &path,
-                        &options,
-                        &mut BuffersBuilder::new(&mut buffers, |vertex: StrokeVertex| ColoredVertex {
-                            position: [vertex.position().x, vertex.position().y],
-                            color: linear_color,
-                        }),
-                    ).unwrap_throw();
+                // Otherwise draw the text from glyph outlines
+                if text.color.a > 0.0 {
+                    clip = Some(outline_text_clip(text, &transform, clip));
+                    let linear_color = with_opacity(text.color.to_linear(), flat.opacity);
+                    let swash_cache = &mut gfx.swash_cache;
+                    let path = || text_outline_path(text, &glyph_buffers[buffer_idx], font_system, swash_cache);
+                    // Glyph outlines overlap within a glyph, fill them with the nonzero rule like the rasterizer does
+                    let options = FillOptions::tolerance(tolerance).with_fill_rule(FillRule::NonZero);
+                    let geometry = gfx.tessellation_cache.fill(None, path, &options).unwrap_throw();
+                    append_geometry(&mut buffers, geometry, &transform, linear_color);
                 }
+                buffer_idx += 1;
             }
-            crate::Object2d::Text(_) => {}
+            // Groups are flattened
+            crate::Object2d::Group(_) => {}
         }
+        let last_index = buffers.indices.len() as u32;
+        steps.push(ObjectStep::Shapes(Primitives::Triangles(first_index..last_index), clip, StencilStep::Test(flat.mask_depth)));
     }
 
-    // Create GPU buffers for vertices and indices
-    let vertex_buffer = gfx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Vertex Buffer"), contents: bytemuck::cast_slice(&buffers.vertices), usage: wgpu::BufferUsages::VERTEX,
-    });
-    let index_buffer = gfx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Index Buffer"), contents: bytemuck::cast_slice(&buffers.indices), usage: wgpu::BufferUsages::INDEX,
-    });
-    let num_indices = buffers.indices.len() as u32;
+    let font_generation = gfx.text_cache.generation();
+    PreparedObject { buffers, instances, steps, glyph_buffers, icon_glyphs, plots, camera: gfx.camera, has_text, font_generation, plot_area }
+}
 
-    // Create a command encoder for the GPU commands
-    let mut encoder = gfx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Render Encoder") });
-    {
-        // Begin a render pass (drawing session)
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &msaa_view,
-                resolve_target: Some(&view),
-                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 }), store: wgpu::StoreOp::Store },
-            })],
-            depth_stencil_attachment: None, timestamp_writes: None, occlusion_query_set: None,
-        });
+/// Prepares the objects of a layer at the visible indices for drawing with the current camera,
+/// reusing the objects of the previously prepared layer that didn't change and still fit the camera.
+///
+/// Objects culled from the layer are dropped with their GPU data and prepared again when they come into view.
+fn prepare_layer(
+    gfx: &mut Graphics,
+    layer: &Layer<PreparedLayer>,
+    visible: &[usize],
+    area: Bounds,
+    previous: Option<PreparedLayer>,
+    font_system: &mut FontSystem,
+) -> PreparedLayer {
+    // Text renderers and the shape buffers are reused with their GPU memory
+    let (mut text_renderers, mut previous_objects, mut layer_buffers) = previous
+        .map(|prepared| (prepared.text_renderers, prepared.objects, prepared.buffers))
+        .unwrap_or_else(|| (Vec::new(), HashMap::new(), LayerBuffers::new()));
 
-        // Draw all shapes if there are any indices
-        if num_indices > 0 {
-            render_pass.set_pipeline(&gfx.rect_pipeline);
-            render_pass.set_bind_group(0, &gfx.bind_group, &[]);
-            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.draw_indexed(0..num_indices, 0, 0..1);
+    // Prepare the changed objects, the others keep their tessellated shapes and shaped text
+    let mut prepared_objects: HashMap<ObjectId, PreparedObject> = HashMap::with_capacity(visible.len());
+    for &index in visible {
+        let id = layer.ids[index];
+        let mut prepared = previous_objects
+            .remove(&id)
+            .filter(|prepared| !layer.changed[index] && prepared.is_valid_for(&gfx.camera, gfx.text_cache.generation(), &area))
+            .unwrap_or_else(|| prepare_object(gfx, &layer.objects[index], &area, font_system));
+        // Plots draw the samples in the prepared area, so reused plots are updated for it too
+        for plot in &mut prepared.plots {
+            plot.update(gfx, &area);
         }
+        prepared_objects.insert(id, prepared);
+    }
+
+    // Helper function to add triangles or instances to the shapes drawn after the last text run
+    // The last batch is extended if the primitives follow it with the same clip rectangle and stencil step.
+    fn push_shapes(steps: &mut Vec<DrawStep>, primitives: Primitives, clip: Option<ClipBox>, stencil: StencilStep) {
+        if !matches!(steps.last(), Some(DrawStep::Shapes(_))) {
+            steps.push(DrawStep::Shapes(Vec::new()));
+        }
+        let Some(DrawStep::Shapes(batches)) = steps.last_mut() else { unreachable!() };
+        if let Some((batch, batch_clip, batch_stencil)) = batches.last_mut()
+            && *batch_clip == clip && *batch_stencil == stencil && batch.extend(&primitives) {
+            return;
+        }
+        batches.push((primitives, clip, stencil));
+    }
+
+    // Vertices and indices of all objects, the indices of each object offset by its first vertex
+    let mut buffers: VertexBuffers<ColoredVertex, u32> = VertexBuffers::new();
+    // Instances of all objects
+    let mut instances: Vec<ShapeInstance> = Vec::new();
+    // Shapes and text in drawing order, consecutive shapes of the objects are drawn together
+    let mut steps: Vec<DrawStep> = Vec::new();
+    // Number of text steps, each drawn by its own text renderer
+    let mut text_run_count = 0;
 
-        // Draw all text
-        match gfx.text_renderer.render(&gfx.atlas, &gfx.viewport, &mut render_pass) {
+    // Loop through the prepared objects in the order of the layer and merge them
+    for &index in visible {
+        let id = layer.ids[index];
+        let prepared = &prepared_objects[&id];
+        let first_vertex = buffers.vertices.len() as u32;
+        let first_index = buffers.indices.len() as u32;
+        let first_instance = instances.len() as u32;
+        buffers.vertices.extend_from_slice(&prepared.buffers.vertices);
+        buffers.indices.extend(prepared.buffers.indices.iter().map(|index| index + first_vertex));
+        instances.extend_from_slice(&prepared.instances);
+        for step in &prepared.steps {
+            match step {
+                ObjectStep::Shapes(primitives, clip, stencil) => {
+                    push_shapes(&mut steps, primitives.offset(first_index, first_instance), *clip, *stencil);
+                }
+                ObjectStep::Text(_) => {
+                    // Consecutive text areas are drawn by the same text renderer
+                    if !matches!(steps.last(), Some(DrawStep::Text(_))) {
+                        steps.push(DrawStep::Text(text_run_count));
+                        text_run_count += 1;
+                    }
+                }
+                ObjectStep::Plot(plot, clip, mask_depth) => steps.push(DrawStep::Plot(id, *plot, *clip, *mask_depth)),
+            }
+        }
+    }
+
+    // Create a text renderer for each text run, all of them sharing the glyph atlas
+    text_renderers.truncate(text_run_count);
+    while text_renderers.len() < text_run_count {
+        text_renderers.push(graphics::create_text_renderer(&gfx.device, &mut gfx.atlas));
+    }
+
+    // Write the shapes into the buffers of the layer, reallocated only when they don't fit
+    layer_buffers.vertices.write(&gfx.device, &gfx.queue, bytemuck::cast_slice(&buffers.vertices));
+    layer_buffers.indices.write(&gfx.device, &gfx.queue, bytemuck::cast_slice(&buffers.indices));
+    layer_buffers.instances.write(&gfx.device, &gfx.queue, bytemuck::cast_slice(&instances));
+
+    let has_text = prepared_objects.values().any(|prepared| prepared.has_text);
+    let font_generation = gfx.text_cache.generation();
+    let order = visible.iter().map(|&index| layer.ids[index]).collect();
+    let mut prepared = PreparedLayer {
+        buffers: layer_buffers, steps, text_renderers, objects: prepared_objects, order, camera: gfx.camera,
+        text_camera: gfx.camera, area, has_text, font_generation,
+    };
+    prepare_text(gfx, &mut prepared, font_system);
+    prepared
+}
+
+/// Prepares the text renderers of the layer with the text areas of its objects, adding missing glyphs to the atlas.
+///
+/// The text areas of consecutive objects form the same runs as the text steps of the layer.
+fn prepare_text(gfx: &mut Graphics, layer: &mut PreparedLayer, font_system: &mut FontSystem) {
+    // Text areas are positioned and clipped in screen pixels with the current camera
+    let camera = gfx.camera.transform();
+    layer.text_camera = gfx.camera;
+    // Text areas of each text step
+    let mut text_runs: Vec<Vec<TextArea>> = Vec::new();
+    let mut run_open = false;
+    for id in &layer.order {
+        let prepared = &layer.objects[id];
+        for step in &prepared.steps {
+            let ObjectStep::Text(area) = step else {
+                run_open = false;
+                continue;
+            };
+            let (left, top) = camera.transform_point(area.left, area.top);
+            let bounds = area.bounds.mapped(&camera);
+            let text_area = TextArea {
+                buffer: &prepared.glyph_buffers[area.buffer],
+                left,
+                top,
+                bounds: TextBounds {
+                    left: bounds.left as i32,
+                    top: bounds.top as i32,
+                    right: bounds.right as i32,
+                    bottom: bounds.bottom as i32,
+                },
+                default_color: area.color,
+                scale: area.scale,
+                custom_glyphs: &prepared.icon_glyphs[area.buffer],
+            };
+            // Count the glyphs for the estimated atlas size
+            for run in text_area.buffer.layout_runs() {
+                for glyph in run.glyphs {
+                    gfx.atlas_size.add_glyph(glyph.physical((left, top + run.line_y), area.scale).cache_key);
+                }
+            }
+            for icon in text_area.custom_glyphs {
+                gfx.atlas_size.add_icon(icon.id, icon.width * area.scale, icon.height * area.scale);
+            }
+            match text_runs.last_mut() {
+                Some(run) if run_open => run.push(text_area),
+                _ => text_runs.push(vec![text_area]),
+            }
+            run_open = true;
+        }
+    }
+    for (text_renderer, text_areas) in layer.text_renderers.iter_mut().zip(text_runs) {
+        match text_renderer.prepare_with_custom(
+            &gfx.device, &gfx.queue, font_system, &mut gfx.atlas, &gfx.viewport,
+            text_areas, &mut gfx.swash_cache, icon_rasterizer::rasterize_icon,
+        ) {
             Ok(_) => {}
-            Err(e) => console::error_1(&JsValue::from_str(&format!("Error rendering text: {:?}", e))),
+            Err(e) => console::error_1(&JsValue::from_str(&format!("Error preparing text renderer: {:?}", e))),
+        }
+    }
+}
+
+/// Returns the GPU memory used by the graphics and the prepared layers.
+pub(crate) fn gpu_memory_usage(gfx: &Graphics, layers: &Layers<PreparedLayer>) -> GpuMemoryUsage {
+    GpuMemoryUsage {
+        render_targets: graphics::render_target_size(gfx),
+        buffers: layers.iter().filter_map(|layer| layer.cache.as_ref()).map(PreparedLayer::buffer_size).sum::<u64>()
+            + gfx.sample_buffers.size(),
+        glyph_atlas_estimate: gfx.atlas_size.bytes(),
+    }
+}
+
+/// Records the draw calls of the prepared layer into the render pass.
+fn render_layer(render_pass: &mut wgpu::RenderPass, gfx: &Graphics, layer: &PreparedLayer) {
+    let camera = gfx.camera.transform();
+    let (surface_width, surface_height) = (gfx.surface_config.width, gfx.surface_config.height);
+    for step in &layer.steps {
+        match step {
+            DrawStep::Shapes(batches) => {
+                // The uniforms are bound again after text, the text renderer binds its own
+                render_pass.set_bind_group(0, &gfx.bind_group, &[]);
+                for (primitives, clip, stencil) in batches {
+                    // Shapes of clipped groups and outline text are drawn with a scissor rectangle
+                    let (x, y, width, height) = match clip {
+                        Some(clip) => clip.mapped(&camera).scissor_rect(surface_width, surface_height),
+                        None => (0, 0, surface_width, surface_height),
+                    };
+                    if primitives.range().is_empty() || width == 0 || height == 0 {
+                        continue;
+                    }
+                    render_pass.set_scissor_rect(x, y, width, height);
+                    // Masks are popped by decrementing the stencil where they incremented it
+                    let (pipeline, reference) = match (primitives, *stencil) {
+                        (Primitives::Instances(_), StencilStep::Test(depth)) => (&gfx.instance_pipeline, depth),
+                        (_, StencilStep::Test(depth)) => (&gfx.rect_pipeline, depth),
+                        (_, StencilStep::Push(depth)) => (&gfx.mask_push_pipeline, depth),
+                        (_, StencilStep::Pop(depth)) => (&gfx.mask_pop_pipeline, depth + 1),
+                    };
+                    render_pass.set_pipeline(pipeline);
+                    render_pass.set_stencil_reference(reference);
+                    match primitives {
+                        Primitives::Triangles(indices) => {
+                            let (Some(vertex_buffer), Some(index_buffer)) = (layer.buffers.vertices.buffer(), layer.buffers.indices.buffer()) else {
+                                continue;
+                            };
+                            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                            render_pass.draw_indexed(indices.clone(), 0, 0..1);
+                        }
+                        Primitives::Instances(instances) => {
+                            let Some(instance_buffer) = layer.buffers.instances.buffer() else {
+                                continue;
+                            };
+                            // The six corners of the quad are generated in the vertex shader
+                            render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+                            render_pass.draw(0..6, instances.clone());
+                        }
+                    }
+                }
+            }
+            DrawStep::Plot(id, index, clip, mask_depth) => {
+                let (Some(pipeline), Some(plot)) = (&gfx.plot_pipeline, layer.objects[id].plots.get(*index)) else {
+                    continue;
+                };
+                let (x, y, width, height) = match clip {
+                    Some(clip) => clip.mapped(&camera).scissor_rect(surface_width, surface_height),
+                    None => (0, 0, surface_width, surface_height),
+                };
+                if plot.instances == 0 || width == 0 || height == 0 {
+                    continue;
+                }
+                render_pass.set_scissor_rect(x, y, width, height);
+                render_pass.set_pipeline(&pipeline.pipeline);
+                render_pass.set_stencil_reference(*mask_depth);
+                render_pass.set_bind_group(0, &gfx.bind_group, &[]);
+                render_pass.set_bind_group(1, &plot.bind_group, &[]);
+                // Each instance draws two quads, the segment or the hold and the step to the next sample
+                render_pass.draw(0..12, 0..plot.instances);
+            }
+            DrawStep::Text(run) => {
+                // Text is clipped by its text area bounds
+                render_pass.set_scissor_rect(0, 0, surface_width, surface_height);
+                match layer.text_renderers[*run].render(&gfx.atlas, &gfx.viewport, render_pass) {
+                    Ok(_) => {}
+                    Err(e) => console::error_1(&JsValue::from_str(&format!("Error rendering text: {:?}", e))),
+                }
+            }
         }
     }
-    // Submit all drawing commands to the GPU
-    gfx.queue.submit(std::iter::once(encoder.finish()));
-    // Present the final image to the screen
-    output.present();
 }
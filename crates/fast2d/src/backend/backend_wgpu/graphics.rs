@@ -4,14 +4,23 @@
 //! It is designed to be beginner-friendly and well-documented for those new to graphics programming.
 
 use wgpu::{Device, Queue, Surface, SurfaceConfiguration, SurfaceTarget, Texture, BindGroup, Buffer as WgpuBuffer};
-use super::MSAA_SAMPLE_COUNT;
+use super::{MSAA_SAMPLE_COUNT, STENCIL_FORMAT};
 use glyphon::Viewport;
 use bytemuck;
 use web_sys::HtmlCanvasElement;
 use glyphon::{Cache, SwashCache, TextAtlas, TextRenderer, Resolution, ColorMode};
 use wgpu::util::DeviceExt;
+use crate::Camera;
+use std::collections::HashSet;
+use crate::backend::{MemoryBudget, SampleBuffers, TessellationCache, TextCache};
 use web_sys::wasm_bindgen::UnwrapThrowExt;
 
+/// Width of the glyph atlas textures when they are created.
+const INITIAL_ATLAS_WIDTH: u32 = 256;
+
+/// Smallest size of a growable buffer in bytes, so small layers don't reallocate while they grow.
+const MIN_BUFFER_SIZE: wgpu::BufferAddress = 4096;
+
 /// Uniforms for the canvas, passed to shaders.
 ///
 /// Uniforms are small pieces of data sent from the CPU to the GPU, often used to pass global parameters like screen size.
@@ -23,12 +32,36 @@ struct CanvasUniforms {
     width: f32,
     /// Height of the canvas in pixels
     height: f32,
+    /// World x coordinate shown at the left edge of the canvas
+    offset_x: f32,
+    /// World y coordinate shown at the top edge of the canvas
+    offset_y: f32,
+    /// Horizontal zoom of the camera
+    scale_x: f32,
+    /// Vertical zoom of the camera
+    scale_y: f32,
     /// Padding for alignment (unused, but required for GPU memory layout)
     _padding1: f32,
     /// Padding for alignment (unused, but required for GPU memory layout)
     _padding2: f32,
 }
 
+impl CanvasUniforms {
+    /// Creates the uniforms for a canvas of the given size viewed through the camera.
+    fn new(width: u32, height: u32, camera: &Camera) -> Self {
+        Self {
+            width: width as f32,
+            height: height as f32,
+            offset_x: camera.offset_x,
+            offset_y: camera.offset_y,
+            scale_x: camera.scale_x,
+            scale_y: camera.scale_y,
+            _padding1: 0.0,
+            _padding2: 0.0,
+        }
+    }
+}
+
 /// Holds all GPU resources and state needed for rendering.
 ///
 /// This struct is the main entry point for all graphics operations. It owns the device, queue, surface, and all other GPU objects.
@@ -43,26 +76,48 @@ pub struct Graphics {
     pub surface_config: SurfaceConfiguration,
     /// Texture used for multisample anti-aliasing (MSAA)
     pub msaa_texture: Texture,
+    /// Stencil texture used to draw group masks, multisampled like the MSAA texture
+    pub stencil_texture: Texture,
     /// Glyphon font cache for fast text rendering
     pub swash_cache: glyphon::SwashCache,
     /// Glyphon viewport for text layout
     pub viewport: glyphon::Viewport,
     /// Glyphon text atlas (texture storing rendered glyphs)
     pub atlas: glyphon::TextAtlas,
-    /// Glyphon text renderer
-    pub text_renderer: glyphon::TextRenderer,
     /// Buffer holding canvas uniforms (size, etc)
     pub uniform_buffer: WgpuBuffer,
     /// Bind group for uniforms (used by shaders)
     pub bind_group: BindGroup,
+    /// The camera the shapes are viewed through, applied in the vertex shader
+    pub camera: Camera,
     /// Pipeline for drawing rectangles (shapes)
     pub rect_pipeline: wgpu::RenderPipeline,
+    /// Pipeline adding a mask to the stencil, without drawing colors
+    pub mask_push_pipeline: wgpu::RenderPipeline,
+    /// Pipeline removing a mask from the stencil, without drawing colors
+    pub mask_pop_pipeline: wgpu::RenderPipeline,
+    /// Pipeline drawing rectangles and circles as instances, shaded by their signed distance
+    pub instance_pipeline: wgpu::RenderPipeline,
+    /// Pipeline drawing sample plots from storage buffers, None on WebGL, which has no storage buffers
+    pub(crate) plot_pipeline: Option<PlotPipeline>,
+    /// Storage buffers of the samples of the plots drawn, uploaded once
+    pub(crate) sample_buffers: SampleBuffers,
+    /// Recently tessellated shapes, reused when a layer is prepared again
+    pub(crate) tessellation_cache: TessellationCache,
+    /// Recently shaped text, reused when a layer is prepared again or by objects with the same text
+    pub(crate) text_cache: TextCache,
+    /// Glyphon cache of the text pipelines, used to create the text atlas again
+    pub(crate) glyphon_cache: Cache,
+    /// Estimated size of the text atlas, which glyphon doesn't report
+    pub(crate) atlas_size: GlyphAtlasSize,
+    /// Memory limits of the text atlas and the tessellation cache
+    pub(crate) memory_budget: MemoryBudget,
 }
 
 /// Resize the graphics surface and update all dependent resources.
 ///
 /// This should be called whenever the window or canvas size changes.
-/// It updates the surface, MSAA texture, text viewport, and uniform buffer.
+/// It updates the surface, MSAA and stencil textures, text viewport, and uniform buffer.
 ///
 /// # Arguments
 /// * `graphics` - The graphics state to update
@@ -79,10 +134,23 @@ pub fn resize_graphics(graphics: &mut Graphics, width: u32, height: u32) {
     graphics.surface.configure(&graphics.device, &graphics.surface_config);
     // Recreate the MSAA texture for the new size
     graphics.msaa_texture = create_msaa_texture(&graphics.device, new_width, new_height, graphics.surface_config.format);
+    // Recreate the stencil texture for the new size
+    graphics.stencil_texture = create_stencil_texture(&graphics.device, new_width, new_height);
     // Update the text viewport for the new size
     graphics.viewport.update(&graphics.queue, glyphon::Resolution { width: new_width, height: new_height });
     // Update the uniform buffer with the new size
-    let uniforms = CanvasUniforms { width: new_width as f32, height: new_height as f32, _padding1: 0.0, _padding2: 0.0 };
+    let uniforms = CanvasUniforms::new(new_width, new_height, &graphics.camera);
+    graphics.queue.write_buffer(&graphics.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+}
+
+/// Sets the camera the scene is viewed through and updates the uniform buffer.
+///
+/// # Arguments
+/// * `graphics` - The graphics state to update
+/// * `camera` - The new camera
+pub fn set_camera(graphics: &mut Graphics, camera: Camera) {
+    graphics.camera = camera;
+    let uniforms = CanvasUniforms::new(graphics.surface_config.width, graphics.surface_config.height, &camera);
     graphics.queue.write_buffer(&graphics.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
 }
 
@@ -182,12 +250,7 @@ pub async fn create_graphics(canvas: HtmlCanvasElement, width: u32, height: u32)
     surface.configure(&device, &surface_config);
 
     // Create the uniform buffer for passing canvas size to shaders
-    let uniforms = CanvasUniforms {
-        width: width as f32,
-        height: height as f32,
-        _padding1: 0.0,
-        _padding2: 0.0,
-    };
+    let uniforms = CanvasUniforms::new(width, height, &Camera::IDENTITY);
     let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("Canvas Uniform Buffer"),
         contents: bytemuck::cast_slice(&[uniforms]),
@@ -221,6 +284,7 @@ pub async fn create_graphics(canvas: HtmlCanvasElement, width: u32, height: u32)
 
     // Create a dummy 1x1 MSAA texture; will be replaced by resize_graphics
     let msaa_texture = create_msaa_texture(&device, 1, 1, surface_format);
+    let stencil_texture = create_stencil_texture(&device, 1, 1);
 
     // Set up Glyphon for fast, high-quality text rendering
     let swash_cache = SwashCache::new();
@@ -230,24 +294,15 @@ pub async fn create_graphics(canvas: HtmlCanvasElement, width: u32, height: u32)
 
     let color_mode = ColorMode::Web;
 
-    let mut atlas = TextAtlas::with_color_mode(
+    let atlas = TextAtlas::with_color_mode(
         &device,
         &queue,
         &cache,
         target_format,
         color_mode,
     );
+    let atlas_size = GlyphAtlasSize::new(device.limits().max_texture_dimension_2d);
 
-    let text_renderer = TextRenderer::new(
-        &mut atlas,
-        &device,
-        wgpu::MultisampleState {
-            count: MSAA_SAMPLE_COUNT,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        },
-        None,
-    );
 
     // Load the WGSL shader for drawing shapes
     let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -260,22 +315,137 @@ pub async fn create_graphics(canvas: HtmlCanvasElement, width: u32, height: u32)
         bind_group_layouts: &[&bind_group_layout],
         push_constant_ranges: &[],
     });
-    // Create the render pipeline for drawing rectangles
-    let rect_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("Shape Pipeline"),
-        layout: Some(&pipeline_layout),
+    // Create the render pipeline for drawing rectangles, where the stencil equals the mask depth
+    let rect_pipeline = create_shape_pipeline(
+        &device, &pipeline_layout, &shader, target_format, "Shape Pipeline", wgpu::ColorWrites::ALL,
+        mask_stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep),
+    );
+    // Create the pipelines for pushing and popping masks, they only update the stencil
+    let mask_push_pipeline = create_shape_pipeline(
+        &device, &pipeline_layout, &shader, target_format, "Mask Push Pipeline", wgpu::ColorWrites::empty(),
+        mask_stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::IncrementClamp),
+    );
+    let mask_pop_pipeline = create_shape_pipeline(
+        &device, &pipeline_layout, &shader, target_format, "Mask Pop Pipeline", wgpu::ColorWrites::empty(),
+        mask_stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::DecrementClamp),
+    );
+
+    // Create the pipeline drawing rectangles and circles from instance data instead of tessellated triangles
+    let instance_pipeline = create_instance_pipeline(&device, &pipeline_layout, &shader, target_format);
+
+    // Create the pipeline drawing sample plots if the samples can be read in the vertex shader
+    let vertex_storage = adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE);
+    let plot_pipeline = (vertex_storage && device.limits().max_storage_buffers_per_shader_stage > 0)
+        .then(|| create_plot_pipeline(&device, &bind_group_layout, target_format));
+
+    // Bundle everything into the Graphics struct
+    let mut graphics = Graphics {
+        device,
+        queue,
+        surface,
+        surface_config,
+        msaa_texture,
+        stencil_texture,
+        swash_cache,
+        viewport,
+        atlas,
+        uniform_buffer,
+        bind_group,
+        camera: Camera::IDENTITY,
+        rect_pipeline,
+        mask_push_pipeline,
+        mask_pop_pipeline,
+        instance_pipeline,
+        plot_pipeline,
+        sample_buffers: SampleBuffers::new(),
+        tessellation_cache: TessellationCache::new(MemoryBudget::default().tessellation_cache as usize),
+        text_cache: TextCache::new(),
+        glyphon_cache: cache,
+        atlas_size,
+        memory_budget: MemoryBudget::default(),
+    };
+    // Ensure all resources are sized correctly
+    resize_graphics(&mut graphics, width, height);
+    graphics
+}
+
+/// Creates a pipeline drawing colored triangles, tested against and optionally updating the mask stencil.
+fn create_shape_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    label: &str,
+    write_mask: wgpu::ColorWrites,
+    stencil: wgpu::StencilState,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
         vertex: wgpu::VertexState {
-            module: &shader,
+            module: shader,
             entry_point: Some("vs_main"),
             compilation_options: Default::default(),
             buffers: &[super::ColoredVertex::desc()],
         },
         fragment: Some(wgpu::FragmentState {
-            module: &shader,
+            module: shader,
             entry_point: Some("fs_main"),
             compilation_options: Default::default(),
             targets: &[Some(wgpu::ColorTargetState {
-                format: target_format,
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil,
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: MSAA_SAMPLE_COUNT,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+/// Creates a pipeline drawing rectangles and circles as instances of a quad, where the stencil equals the mask depth.
+fn create_instance_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Instance Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_shape"),
+            compilation_options: Default::default(),
+            buffers: &[super::ShapeInstance::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_shape"),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
                 blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
@@ -289,7 +459,13 @@ pub async fn create_graphics(canvas: HtmlCanvasElement, width: u32, height: u32)
             unclipped_depth: false,
             conservative: false,
         },
-        depth_stencil: None,
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: mask_stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep),
+            bias: wgpu::DepthBiasState::default(),
+        }),
         multisample: wgpu::MultisampleState {
             count: MSAA_SAMPLE_COUNT,
             mask: !0,
@@ -297,24 +473,287 @@ pub async fn create_graphics(canvas: HtmlCanvasElement, width: u32, height: u32)
         },
         multiview: None,
         cache: None,
+    })
+}
+
+/// The pipeline drawing sample plots, with the layout of the bind group of each plot.
+pub(crate) struct PlotPipeline {
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    /// Layout of the uniforms and the storage buffer of the samples of a plot
+    pub(crate) bind_group_layout: wgpu::BindGroupLayout,
+}
+
+/// Creates a pipeline drawing sample plots from their samples without vertex buffers, where the stencil equals the mask depth.
+///
+/// The plot shader is a separate module, as it doesn't compile without storage buffers.
+fn create_plot_pipeline(device: &Device, camera_layout: &wgpu::BindGroupLayout, format: wgpu::TextureFormat) -> PlotPipeline {
+    let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Buffer { ty, has_dynamic_offset: false, min_binding_size: None },
+        count: None,
+    };
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Plot Bind Group Layout"),
+        entries: &[
+            entry(0, wgpu::BufferBindingType::Uniform),
+            entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+        ],
     });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Plot Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("plot.wgsl").into()),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Plot Pipeline Layout"),
+        bind_group_layouts: &[camera_layout, &bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Plot Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_plot"),
+            compilation_options: Default::default(),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_plot"),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: mask_stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: MSAA_SAMPLE_COUNT,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    });
+    PlotPipeline { pipeline, bind_group_layout }
+}
 
-    // Bundle everything into the Graphics struct
-    let mut graphics = Graphics {
-        device,
-        queue,
-        surface,
-        surface_config,
-        msaa_texture,
-        swash_cache,
-        viewport,
-        atlas,
-        text_renderer,
-        uniform_buffer,
-        bind_group,
-        rect_pipeline,
+/// Creates a stencil state comparing the stencil value with the reference and applying the operation where it passes.
+///
+/// Shapes are drawn where the stencil equals the mask depth, masks increment it when pushed and decrement it when popped.
+fn mask_stencil_state(compare: wgpu::CompareFunction, pass_op: wgpu::StencilOperation) -> wgpu::StencilState {
+    let face = wgpu::StencilFaceState { compare, fail_op: wgpu::StencilOperation::Keep, depth_fail_op: wgpu::StencilOperation::Keep, pass_op };
+    let write_mask = if pass_op == wgpu::StencilOperation::Keep { 0 } else { 0xff };
+    wgpu::StencilState { front: face, back: face, read_mask: 0xff, write_mask }
+}
+
+/// Creates the multisampled stencil texture holding the active masks of every pixel.
+fn create_stencil_texture(device: &Device, width: u32, height: u32) -> Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Stencil Texture"),
+        size: wgpu::Extent3d { width: width.max(1), height: height.max(1), depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: MSAA_SAMPLE_COUNT,
+        dimension: wgpu::TextureDimension::D2,
+        format: STENCIL_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    })
+}
+
+/// Creates the text atlas again with no glyphs, shrinking its textures to their initial size.
+///
+/// All text must be prepared again before it's drawn, the text renderers are kept.
+pub(crate) fn recreate_atlas(graphics: &mut Graphics) {
+    graphics.atlas = TextAtlas::with_color_mode(&graphics.device, &graphics.queue, &graphics.glyphon_cache, graphics.surface_config.format, ColorMode::Web);
+    graphics.atlas_size.reset();
+}
+
+/// Returns the bytes of the multisampled color and stencil textures.
+pub(crate) fn render_target_size(graphics: &Graphics) -> u64 {
+    // The stencil format has no single block size, depth and stencil take four bytes together
+    let texture_size = |texture: &Texture| {
+        let bytes = texture.format().block_copy_size(None).unwrap_or(4);
+        texture.width() as u64 * texture.height() as u64 * texture.sample_count() as u64 * bytes as u64
     };
-    // Ensure all resources are sized correctly
-    resize_graphics(&mut graphics, width, height);
-    graphics
+    texture_size(&graphics.msaa_texture) + texture_size(&graphics.stencil_texture)
+}
+
+/// Estimated size of the glyph atlas textures, which glyphon doesn't report.
+///
+/// Glyphon keeps mask glyphs in a single channel texture and color glyphs in an RGBA texture.
+/// Both are square and double their width when the glyphs in use don't fit, they never shrink.
+/// Glyphs are counted as em squares, and only inline icons as color glyphs.
+pub(crate) struct GlyphAtlasSize {
+    /// Width of the mask texture
+    mask_width: u32,
+    /// Width of the color texture
+    color_width: u32,
+    /// Largest texture width of the device
+    max_width: u32,
+    /// Glyphs counted since `begin`, with the pixels of the mask and color glyphs
+    glyphs: HashSet<glyphon::CacheKey>,
+    icons: HashSet<(u16, u32, u32)>,
+    mask_pixels: u64,
+    color_pixels: u64,
+    /// Widths the glyphs in use counted by the last `finish` fit in
+    needed_widths: (u32, u32),
+}
+
+impl GlyphAtlasSize {
+    /// Creates the size of a new atlas.
+    pub(crate) fn new(max_width: u32) -> Self {
+        Self {
+            mask_width: INITIAL_ATLAS_WIDTH,
+            color_width: INITIAL_ATLAS_WIDTH,
+            max_width,
+            glyphs: HashSet::new(),
+            icons: HashSet::new(),
+            mask_pixels: 0,
+            color_pixels: 0,
+            needed_widths: (INITIAL_ATLAS_WIDTH, INITIAL_ATLAS_WIDTH),
+        }
+    }
+
+    /// Starts counting the glyphs in use, when all text is prepared again.
+    pub(crate) fn begin(&mut self) {
+        self.glyphs.clear();
+        self.icons.clear();
+        self.mask_pixels = 0;
+        self.color_pixels = 0;
+    }
+
+    /// Counts a glyph prepared for drawing.
+    pub(crate) fn add_glyph(&mut self, key: glyphon::CacheKey) {
+        if self.glyphs.insert(key) {
+            let size = f32::from_bits(key.font_size_bits).ceil() as u64;
+            self.mask_pixels += size * size;
+        }
+    }
+
+    /// Counts an inline icon prepared for drawing with the size in pixels.
+    pub(crate) fn add_icon(&mut self, id: u16, width: f32, height: f32) {
+        if self.icons.insert((id, width.to_bits(), height.to_bits())) {
+            self.color_pixels += width.ceil() as u64 * height.ceil() as u64;
+        }
+    }
+
+    /// Grows the textures to fit the glyphs counted since `begin`.
+    pub(crate) fn finish(&mut self) {
+        self.needed_widths = (self.fitting_width(self.mask_pixels), self.fitting_width(self.color_pixels));
+        self.mask_width = self.mask_width.max(self.needed_widths.0);
+        self.color_width = self.color_width.max(self.needed_widths.1);
+    }
+
+    /// Returns true if an atlas created again would be smaller, as glyphs not in use take space.
+    pub(crate) fn can_shrink(&self) -> bool {
+        self.needed_widths.0 < self.mask_width || self.needed_widths.1 < self.color_width
+    }
+
+    /// Shrinks the textures to their initial width, after the atlas was created again.
+    fn reset(&mut self) {
+        self.mask_width = INITIAL_ATLAS_WIDTH;
+        self.color_width = INITIAL_ATLAS_WIDTH;
+    }
+
+    /// Returns the estimated bytes of both textures.
+    pub(crate) fn bytes(&self) -> u64 {
+        let mask_width = self.mask_width as u64;
+        let color_width = self.color_width as u64;
+        mask_width * mask_width + color_width * color_width * 4
+    }
+
+    /// Returns the smallest width, doubling from the initial width, of a square texture holding the pixels.
+    fn fitting_width(&self, pixels: u64) -> u32 {
+        let mut width = INITIAL_ATLAS_WIDTH;
+        while (width as u64 * width as u64) < pixels && width < self.max_width {
+            width *= 2;
+        }
+        width.min(self.max_width)
+    }
+}
+
+/// A GPU buffer kept between frames and written with `queue.write_buffer`, reallocated only when the data outgrows it.
+pub(crate) struct GrowableBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    /// The buffer, None until data is first written, as empty buffers can't be bound
+    buffer: Option<WgpuBuffer>,
+}
+
+impl GrowableBuffer {
+    /// Creates a buffer with the usage, allocated when data is first written.
+    pub(crate) fn new(label: &'static str, usage: wgpu::BufferUsages) -> Self {
+        Self { label, usage: usage | wgpu::BufferUsages::COPY_DST, buffer: None }
+    }
+
+    /// Writes the data to the start of the buffer, allocating a larger buffer if it doesn't fit.
+    ///
+    /// The capacity grows to the next power of two, so a growing scene reallocates rarely.
+    /// The data must be a multiple of 4 bytes long, empty data is not written.
+    pub(crate) fn write(&mut self, device: &Device, queue: &Queue, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        let size = data.len() as wgpu::BufferAddress;
+        if self.buffer.as_ref().is_none_or(|buffer| buffer.size() < size) {
+            self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size: size.next_power_of_two().max(MIN_BUFFER_SIZE),
+                usage: self.usage,
+                mapped_at_creation: false,
+            }));
+        }
+        queue.write_buffer(self.buffer.as_ref().unwrap(), 0, data);
+    }
+
+    /// Returns the allocated bytes of the buffer.
+    pub(crate) fn size(&self) -> u64 {
+        self.buffer.as_ref().map_or(0, |buffer| buffer.size())
+    }
+
+    /// Returns the buffer, or None if no data was written yet.
+    pub(crate) fn buffer(&self) -> Option<&WgpuBuffer> {
+        self.buffer.as_ref()
+    }
+}
+
+/// Creates a glyphon text renderer drawing into the MSAA texture, sharing the glyphs of the atlas.
+///
+/// Each layer has a text renderer for each run of text drawn between its shapes.
+pub(crate) fn create_text_renderer(device: &Device, atlas: &mut TextAtlas) -> TextRenderer {
+    TextRenderer::new(
+        atlas,
+        device,
+        wgpu::MultisampleState {
+            count: MSAA_SAMPLE_COUNT,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        // Text ignores masks, masked text is drawn from outlines with the shapes
+        Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+    )
 }
@@ -0,0 +1,166 @@
+// Sample plots: evenly spaced samples read from a storage buffer, drawn without vertex buffers
+//
+// Kept apart from shaders.wgsl, as WebGL has no storage buffers and can't compile this module.
+
+// The canvas uniforms, like in shaders.wgsl
+struct CanvasUniforms {
+    width: f32,
+    height: f32,
+    // Camera: the world point at the top-left corner and the zoom along each axis
+    offset_x: f32,
+    offset_y: f32,
+    scale_x: f32,
+    scale_y: f32,
+    _padding1: f32,
+    _padding2: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> canvas: CanvasUniforms;
+
+// Uniforms of a plot matching Rust's PlotUniforms, the size is a multiple of 16 bytes
+struct PlotUniforms {
+    // Transform to world coordinates: the columns (a, b) and (c, d), and the translation
+    transform: vec4<f32>,
+    translation: vec2<f32>,
+    // Position of the first sample with the value 0, before the transform
+    origin: vec2<f32>,
+    x_step: f32,
+    y_scale: f32,
+    half_width: f32,
+    // 0 for a polyline, 1 for steps
+    style: u32,
+    // Linear color with the opacity of the groups
+    color: vec4<f32>,
+    // The samples drawn, from the first to the last sample in view
+    first_sample: u32,
+    last_sample: u32,
+    // Samples reduced to their range by each instance, 1 to draw each segment
+    samples_per_instance: u32,
+    _padding: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> plot: PlotUniforms;
+
+@group(1) @binding(1)
+var<storage, read> samples: array<f32>;
+
+struct PlotOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// Returns the position of the sample before the transform
+fn sample_point(index: u32) -> vec2<f32> {
+    return plot.origin + vec2<f32>(f32(index) * plot.x_step, samples[index] * plot.y_scale);
+}
+
+// Returns a corner of the two triangles of a quad, from -1 to 1
+fn quad_corner(corner: u32) -> vec2<f32> {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(-1.0, 1.0),
+        vec2<f32>(-1.0, 1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+    );
+    return corners[corner];
+}
+
+// Returns a corner of the quad covering the segment from a to b, extended by half the width at both ends
+// so consecutive segments overlap at their joins
+fn segment_corner(a: vec2<f32>, b: vec2<f32>, corner: u32) -> vec2<f32> {
+    let c = quad_corner(corner);
+    let delta = b - a;
+    let length = length(delta);
+    let direction = select(vec2<f32>(1.0, 0.0), delta / length, length > 0.0);
+    let normal = vec2<f32>(-direction.y, direction.x);
+    let end = select(a, b, c.x > 0.0);
+    return end + (direction * c.x + normal * c.y) * plot.half_width;
+}
+
+// Returns true if the value is a number, NaN and infinite samples leave a gap
+fn is_finite(value: f32) -> bool {
+    return value == value && abs(value) <= 3.4e38;
+}
+
+@vertex
+fn vs_plot(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> PlotOutput {
+    var out: PlotOutput;
+    // Degenerate triangles outside the view for parts without anything to draw
+    out.clip_position = vec4<f32>(2.0, 2.0, 0.0, 1.0);
+    let quad = vertex_index / 6u;
+    let corner = vertex_index % 6u;
+    let start = plot.first_sample + instance_index * plot.samples_per_instance;
+    let end = min(start + plot.samples_per_instance, plot.last_sample);
+    if start >= end {
+        return out;
+    }
+    var local: vec2<f32>;
+    if plot.samples_per_instance == 1u {
+        // Each instance draws the segment to the next sample, or the hold and the step to it
+        let a = sample_point(start);
+        let b = sample_point(end);
+        if !is_finite(a.y) || !is_finite(b.y) {
+            return out;
+        }
+        if plot.style == 0u {
+            if quad == 1u {
+                return out;
+            }
+            local = segment_corner(a, b, corner);
+        } else {
+            let step = vec2<f32>(b.x, a.y);
+            local = select(segment_corner(step, b, corner), segment_corner(a, step, corner), quad == 0u);
+        }
+    } else {
+        // Zoomed out, each instance covers the range of the samples in about a pixel column
+        if quad == 1u {
+            return out;
+        }
+        var low = 3.4e38;
+        var high = -3.4e38;
+        for (var index = start; index <= end; index++) {
+            let value = samples[index];
+            if is_finite(value) {
+                low = min(low, value);
+                high = max(high, value);
+            }
+        }
+        if low > high {
+            return out;
+        }
+        // The box covers the samples and half the width around them
+        let first = plot.origin + vec2<f32>(f32(start) * plot.x_step, low * plot.y_scale);
+        let last = plot.origin + vec2<f32>(f32(end) * plot.x_step, high * plot.y_scale);
+        let c = quad_corner(corner);
+        let low_corner = min(first, last) - plot.half_width;
+        let high_corner = max(first, last) + plot.half_width;
+        local = select(low_corner, high_corner, c > vec2<f32>(0.0));
+    }
+    let world = vec2<f32>(
+        plot.transform.x * local.x + plot.transform.z * local.y,
+        plot.transform.y * local.x + plot.transform.w * local.y,
+    ) + plot.translation;
+    // Apply the camera and map to NDC like vs_main
+    let pixel_x = (world.x - canvas.offset_x) * canvas.scale_x;
+    let pixel_y = (world.y - canvas.offset_y) * canvas.scale_y;
+    let ndc_x = (pixel_x / canvas.width) * 2.0 - 1.0;
+    let ndc_y = (pixel_y / canvas.height) * -2.0 + 1.0;
+    out.clip_position = vec4<f32>(ndc_x, ndc_y, 0.0, 1.0);
+    return out;
+}
+
+// sRGB conversion, like in shaders.wgsl
+fn linear_to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let clamped = clamp(linear, vec3<f32>(0.0), vec3<f32>(1.0));
+    let cutoff = clamped < vec3<f32>(0.0031308);
+    let higher = 1.055 * pow(clamped, vec3<f32>(1.0 / 2.4)) - 0.055;
+    let lower = clamped * 12.92;
+    return select(higher, lower, cutoff);
+}
+
+@fragment
+fn fs_plot(in: PlotOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(linear_to_srgb(plot.color.rgb), plot.color.a);
+}
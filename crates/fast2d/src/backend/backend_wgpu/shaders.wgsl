@@ -7,10 +7,15 @@
 // Uniform buffer objects (UBOs) in WGSL and wgpu must follow strict alignment rules:
 // - Each field must be aligned to a 4-byte boundary (f32), but the struct as a whole must be aligned to 16 bytes.
 // - If the struct is not a multiple of 16 bytes, the GPU may read invalid data or cause validation errors.
-// - Adding two f32 padding fields ensures the struct size is 16 bytes, matching what Rust expects and what the GPU requires.
+// - Adding two f32 padding fields ensures the struct size is 32 bytes, matching what Rust expects and what the GPU requires.
 struct CanvasUniforms {
     width: f32,
     height: f32,
+    // Camera: the world point at the top-left corner and the zoom along each axis
+    offset_x: f32,
+    offset_y: f32,
+    scale_x: f32,
+    scale_y: f32,
     _padding1: f32,
     _padding2: f32,
 };
@@ -36,11 +41,14 @@ fn vs_main(
     in: VertexInput
 ) -> VertexOutput {
     var out: VertexOutput;
+    // Apply the camera to get pixel coordinates from world coordinates
+    let pixel_x = (in.position.x - canvas.offset_x) * canvas.scale_x;
+    let pixel_y = (in.position.y - canvas.offset_y) * canvas.scale_y;
     // Transform pixel coordinates to Normalized Device Coordinates (NDC)
     // NDC X: (pixel_x / width) * 2.0 - 1.0
     // NDC Y: (pixel_y / height) * -2.0 + 1.0  (Invert Y)
-    let ndc_x = (in.position.x / canvas.width) * 2.0 - 1.0;
-    let ndc_y = (in.position.y / canvas.height) * -2.0 + 1.0; // Invert Y axis
+    let ndc_x = (pixel_x / canvas.width) * 2.0 - 1.0;
+    let ndc_y = (pixel_y / canvas.height) * -2.0 + 1.0; // Invert Y axis
     out.clip_position = vec4<f32>(ndc_x, ndc_y, 0.0, 1.0);
     out.color = in.color; // Pass linear color through
     return out;
@@ -64,3 +72,97 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     // Always apply manual sRGB conversion
     return vec4<f32>(linear_to_srgb(linear_color.rgb), linear_color.a);
 }
+
+// Instanced shapes: rectangles and circles drawn as quads, shaded by their signed distance
+
+// Input instance structure matching Rust's ShapeInstance
+struct ShapeInstance {
+    // Transform to world coordinates: the columns (a, b) and (c, d), and the translation
+    @location(0) transform: vec4<f32>,
+    @location(1) translation: vec2<f32>,
+    @location(2) position: vec2<f32>,
+    @location(3) size: vec2<f32>,
+    // Corner radii: top-left, top-right, bottom-right, bottom-left
+    @location(4) radii: vec4<f32>,
+    @location(5) color: vec4<f32>,
+    @location(6) border_color: vec4<f32>,
+    @location(7) border_width: f32,
+};
+
+// Output structure to pass the shape to the fragment shader
+struct ShapeOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Position relative to the center of the shape, before its transform
+    @location(0) local: vec2<f32>,
+    @location(1) @interpolate(flat) half_size: vec2<f32>,
+    @location(2) @interpolate(flat) radii: vec4<f32>,
+    @location(3) @interpolate(flat) color: vec4<f32>,
+    @location(4) @interpolate(flat) border_color: vec4<f32>,
+    @location(5) @interpolate(flat) border_width: f32,
+};
+
+@vertex
+fn vs_shape(
+    @builtin(vertex_index) vertex_index: u32,
+    instance: ShapeInstance,
+) -> ShapeOutput {
+    // Two triangles covering the shape, with corners from -1 to 1
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(-1.0, 1.0),
+        vec2<f32>(-1.0, 1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let half_size = instance.size * 0.5;
+    let center = instance.position + half_size;
+    // The quad is grown by a pixel for the anti-aliased edge, measured along each axis after the transform and camera
+    let axis_x = vec2<f32>(instance.transform.x * canvas.scale_x, instance.transform.y * canvas.scale_y);
+    let axis_y = vec2<f32>(instance.transform.z * canvas.scale_x, instance.transform.w * canvas.scale_y);
+    let margin = vec2<f32>(1.0 / max(length(axis_x), 1e-6), 1.0 / max(length(axis_y), 1e-6));
+    let local = corner * (half_size + margin);
+    let point = center + local;
+    let world = vec2<f32>(
+        instance.transform.x * point.x + instance.transform.z * point.y,
+        instance.transform.y * point.x + instance.transform.w * point.y,
+    ) + instance.translation;
+    // Apply the camera and map to NDC like vs_main
+    let pixel_x = (world.x - canvas.offset_x) * canvas.scale_x;
+    let pixel_y = (world.y - canvas.offset_y) * canvas.scale_y;
+    let ndc_x = (pixel_x / canvas.width) * 2.0 - 1.0;
+    let ndc_y = (pixel_y / canvas.height) * -2.0 + 1.0;
+    var out: ShapeOutput;
+    out.clip_position = vec4<f32>(ndc_x, ndc_y, 0.0, 1.0);
+    out.local = local;
+    out.half_size = half_size;
+    out.radii = instance.radii;
+    out.color = instance.color;
+    out.border_color = instance.border_color;
+    out.border_width = instance.border_width;
+    return out;
+}
+
+// Signed distance from the outline of a rounded box centered at the origin, negative inside
+fn rounded_box_distance(p: vec2<f32>, half_size: vec2<f32>, radii: vec4<f32>) -> f32 {
+    // Radius of the corner in the quadrant of the point, the y axis points down
+    let top = select(radii.y, radii.x, p.x < 0.0);
+    let bottom = select(radii.z, radii.w, p.x < 0.0);
+    let radius = min(select(bottom, top, p.y < 0.0), min(half_size.x, half_size.y));
+    let q = abs(p) - half_size + radius;
+    return min(max(q.x, q.y), 0.0) + length(max(q, vec2<f32>(0.0))) - radius;
+}
+
+@fragment
+fn fs_shape(in: ShapeOutput) -> @location(0) vec4<f32> {
+    let distance = rounded_box_distance(in.local, in.half_size, in.radii);
+    // Coverage of the pixel by the shape and by the fill inside the border, a pixel wide ramp across the edges
+    let pixel = max(fwidth(distance), 1e-4);
+    let shape_coverage = clamp(0.5 - distance / pixel, 0.0, 1.0);
+    let fill_coverage = clamp(0.5 - (distance + in.border_width) / pixel, 0.0, 1.0);
+    // Colors are mixed premultiplied, so a transparent fill doesn't darken the border edge
+    let fill = vec4<f32>(in.color.rgb * in.color.a, in.color.a);
+    let border = vec4<f32>(in.border_color.rgb * in.border_color.a, in.border_color.a);
+    let color = mix(border, fill, fill_coverage) * shape_coverage;
+    if color.a <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(linear_to_srgb(color.rgb / color.a), color.a);
+}
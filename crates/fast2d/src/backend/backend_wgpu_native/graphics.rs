@@ -4,11 +4,20 @@
 //! It provides the same functionality as the web backend but uses native WGPU surfaces instead of HTML Canvas elements.
 
 use wgpu::{Device, Queue, Surface, SurfaceConfiguration, Texture, BindGroup, Buffer as WgpuBuffer};
-use super::MSAA_SAMPLE_COUNT;
+use super::{MSAA_SAMPLE_COUNT, STENCIL_FORMAT};
 use glyphon::Viewport;
 use bytemuck;
 use glyphon::{Cache, SwashCache, TextAtlas, TextRenderer};
 use wgpu::util::DeviceExt;
+use crate::Camera;
+use std::collections::HashSet;
+use crate::backend::{MemoryBudget, SampleBuffers, TessellationCache, TextCache};
+
+/// Width of the glyph atlas textures when they are created.
+const INITIAL_ATLAS_WIDTH: u32 = 256;
+
+/// Smallest size of a growable buffer in bytes, so small layers don't reallocate while they grow.
+const MIN_BUFFER_SIZE: wgpu::BufferAddress = 4096;
 
 /// Uniforms for the canvas, passed to shaders.
 ///
@@ -21,12 +30,36 @@ struct CanvasUniforms {
     width: f32,
     /// Height of the canvas in pixels
     height: f32,
+    /// World x coordinate shown at the left edge of the canvas
+    offset_x: f32,
+    /// World y coordinate shown at the top edge of the canvas
+    offset_y: f32,
+    /// Horizontal zoom of the camera
+    scale_x: f32,
+    /// Vertical zoom of the camera
+    scale_y: f32,
     /// Padding for alignment (unused, but required for GPU memory layout)
     _padding1: f32,
     /// Padding for alignment (unused, but required for GPU memory layout)
     _padding2: f32,
 }
 
+impl CanvasUniforms {
+    /// Creates the uniforms for a canvas of the given size viewed through the camera.
+    fn new(width: u32, height: u32, camera: &Camera) -> Self {
+        Self {
+            width: width as f32,
+            height: height as f32,
+            offset_x: camera.offset_x,
+            offset_y: camera.offset_y,
+            scale_x: camera.scale_x,
+            scale_y: camera.scale_y,
+            _padding1: 0.0,
+            _padding2: 0.0,
+        }
+    }
+}
+
 /// Holds all GPU resources and state needed for rendering on native platforms.
 ///
 /// This struct is the main entry point for all graphics operations. It owns the device, queue, surface, and all other GPU objects.
@@ -41,22 +74,44 @@ pub struct Graphics {
     pub surface_config: SurfaceConfiguration,
     /// Texture used for multisample anti-aliasing (MSAA)
     pub msaa_texture: Texture,
+    /// Stencil texture used to draw group masks, multisampled like the MSAA texture
+    pub stencil_texture: Texture,
     /// Glyphon font cache for fast text rendering
     pub swash_cache: glyphon::SwashCache,
     /// Glyphon viewport for text layout
     pub viewport: glyphon::Viewport,
     /// Glyphon text atlas for glyph storage
     pub text_atlas: glyphon::TextAtlas,
-    /// Glyphon text renderer for drawing text
-    pub text_renderer: glyphon::TextRenderer,
     /// Main render pipeline for colored geometry
     pub render_pipeline: wgpu::RenderPipeline,
+    /// Pipeline adding a mask to the stencil, without drawing colors
+    pub mask_push_pipeline: wgpu::RenderPipeline,
+    /// Pipeline removing a mask from the stencil, without drawing colors
+    pub mask_pop_pipeline: wgpu::RenderPipeline,
+    /// Pipeline drawing rectangles and circles as instances, shaded by their signed distance
+    pub instance_pipeline: wgpu::RenderPipeline,
+    /// Pipeline drawing sample plots from storage buffers, None if the device has no storage buffers in vertex shaders
+    pub(crate) plot_pipeline: Option<PlotPipeline>,
+    /// Storage buffers of the samples of the plots drawn, uploaded once
+    pub(crate) sample_buffers: SampleBuffers,
+    /// Recently tessellated shapes, reused when a layer is prepared again
+    pub(crate) tessellation_cache: TessellationCache,
+    /// Recently shaped text, reused when a layer is prepared again or by objects with the same text
+    pub(crate) text_cache: TextCache,
+    /// Glyphon cache of the text pipelines, used to create the text atlas again
+    pub(crate) glyphon_cache: Cache,
+    /// Estimated size of the text atlas, which glyphon doesn't report
+    pub(crate) atlas_size: GlyphAtlasSize,
+    /// Memory limits of the text atlas and the tessellation cache
+    pub(crate) memory_budget: MemoryBudget,
     /// Bind group layout for uniforms
     pub bind_group_layout: wgpu::BindGroupLayout,
     /// Uniform buffer for canvas parameters
     pub uniform_buffer: WgpuBuffer,
     /// Bind group for uniforms
     pub uniform_bind_group: BindGroup,
+    /// The camera the shapes are viewed through, applied in the vertex shader
+    pub camera: Camera,
 }
 
 /// Creates a new Graphics context with a native WGPU surface.
@@ -144,22 +199,14 @@ pub async fn create_graphics_with_adapter(
 
     // Create MSAA texture for anti-aliasing
     let msaa_texture = create_msaa_texture(&device, &surface_config);
+    let stencil_texture = create_stencil_texture(&device, width, height);
 
     // Initialize Glyphon for text rendering
     let mut swash_cache = SwashCache::new();
     let cache = Cache::new(&device);
     let viewport = Viewport::new(&device, &cache);
-    let mut text_atlas = TextAtlas::new(&device, &queue, &cache, surface_format);
-    let text_renderer = TextRenderer::new(
-        &mut text_atlas,
-        &device,
-        wgpu::MultisampleState {
-            count: MSAA_SAMPLE_COUNT,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        },
-        None,
-    );
+    let text_atlas = TextAtlas::new(&device, &queue, &cache, surface_format);
+    let atlas_size = GlyphAtlasSize::new(device.limits().max_texture_dimension_2d);
 
     // Create shader module
     let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -189,54 +236,34 @@ pub async fn create_graphics_with_adapter(
         push_constant_ranges: &[],
     });
 
-    // Create render pipeline
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("Render Pipeline"),
-        layout: Some(&pipeline_layout),
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: Some("vs_main"),
-            buffers: &[super::ColoredVertex::desc()],
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: Some("fs_main"),
-            targets: &[Some(wgpu::ColorTargetState {
-                format: surface_config.format,
-                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-        }),
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: None,
-            polygon_mode: wgpu::PolygonMode::Fill,
-            unclipped_depth: false,
-            conservative: false,
-        },
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState {
-            count: MSAA_SAMPLE_COUNT,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        },
-        multiview: None,
-        cache: None,
-    });
+    // Create render pipeline, drawing where the stencil equals the mask depth
+    let render_pipeline = create_shape_pipeline(
+        &device, &pipeline_layout, &shader, surface_config.format, "Render Pipeline", wgpu::ColorWrites::ALL,
+        mask_stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep),
+    );
+
+    // Create pipelines for pushing and popping masks, they only update the stencil
+    let mask_push_pipeline = create_shape_pipeline(
+        &device, &pipeline_layout, &shader, surface_config.format, "Mask Push Pipeline", wgpu::ColorWrites::empty(),
+        mask_stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::IncrementClamp),
+    );
+    let mask_pop_pipeline = create_shape_pipeline(
+        &device, &pipeline_layout, &shader, surface_config.format, "Mask Pop Pipeline", wgpu::ColorWrites::empty(),
+        mask_stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::DecrementClamp),
+    );
+
+    // Create the pipeline drawing rectangles and circles from instance data instead of tessellated triangles
+    let instance_pipeline = create_instance_pipeline(&device, &pipeline_layout, &shader, surface_config.format);
+
+    // Create the pipeline drawing sample plots if the samples can be read in the vertex shader
+    let vertex_storage = adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE);
+    let plot_pipeline = (vertex_storage && device.limits().max_storage_buffers_per_shader_stage > 0)
+        .then(|| create_plot_pipeline(&device, &bind_group_layout, surface_config.format));
 
     // Create uniform buffer
     let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("Uniform Buffer"),
-        contents: bytemuck::cast_slice(&[CanvasUniforms {
-            width: width as f32,
-            height: height as f32,
-            _padding1: 0.0,
-            _padding2: 0.0,
-        }]),
+        contents: bytemuck::cast_slice(&[CanvasUniforms::new(width, height, &Camera::IDENTITY)]),
         usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
     });
 
@@ -256,14 +283,25 @@ pub async fn create_graphics_with_adapter(
         surface,
         surface_config,
         msaa_texture,
+        stencil_texture,
         swash_cache,
         viewport,
         text_atlas,
-        text_renderer,
         render_pipeline,
+        mask_push_pipeline,
+        mask_pop_pipeline,
+        instance_pipeline,
+        plot_pipeline,
+        sample_buffers: SampleBuffers::new(),
+        tessellation_cache: TessellationCache::new(MemoryBudget::default().tessellation_cache as usize),
+        text_cache: TextCache::new(),
+        glyphon_cache: cache,
+        atlas_size,
+        memory_budget: MemoryBudget::default(),
         bind_group_layout,
         uniform_buffer,
         uniform_bind_group,
+        camera: Camera::IDENTITY,
     }
 }
 
@@ -296,14 +334,10 @@ pub fn resize_graphics(graphics: &mut Graphics, width: u32, height: u32) {
     println!("Creating MSAA texture: {}x{}", width, height);
     graphics.msaa_texture = create_msaa_texture(&graphics.device, &graphics.surface_config);
     println!("MSAA texture created successfully");
+    graphics.stencil_texture = create_stencil_texture(&graphics.device, width, height);
     
     // Update uniform buffer with new dimensions
-    let uniforms = CanvasUniforms {
-        width: width as f32,
-        height: height as f32,
-        _padding1: 0.0,
-        _padding2: 0.0,
-    };
+    let uniforms = CanvasUniforms::new(width, height, &graphics.camera);
     graphics.queue.write_buffer(
         &graphics.uniform_buffer,
         0,
@@ -313,6 +347,17 @@ pub fn resize_graphics(graphics: &mut Graphics, width: u32, height: u32) {
     println!("Surface resize completed: {}x{}", width, height);
 }
 
+/// Sets the camera the scene is viewed through and updates the uniform buffer.
+///
+/// # Arguments
+/// * `graphics` - The graphics state to update
+/// * `camera` - The new camera
+pub fn set_camera(graphics: &mut Graphics, camera: Camera) {
+    graphics.camera = camera;
+    let uniforms = CanvasUniforms::new(graphics.surface_config.width, graphics.surface_config.height, &camera);
+    graphics.queue.write_buffer(&graphics.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+}
+
 /// Creates an MSAA texture for anti-aliasing.
 fn create_msaa_texture(device: &Device, surface_config: &SurfaceConfiguration) -> Texture {
     device.create_texture(&wgpu::TextureDescriptor {
@@ -329,4 +374,393 @@ fn create_msaa_texture(device: &Device, surface_config: &SurfaceConfiguration) -
         label: Some("MSAA Texture"),
         view_formats: &[],
     })
-}
\ No newline at end of file
+}
+
+/// Creates a pipeline drawing colored triangles, tested against and optionally updating the mask stencil.
+fn create_shape_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    label: &str,
+    write_mask: wgpu::ColorWrites,
+    stencil: wgpu::StencilState,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            compilation_options: Default::default(),
+            buffers: &[super::ColoredVertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil,
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: MSAA_SAMPLE_COUNT,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+/// Creates a pipeline drawing rectangles and circles as instances of a quad, where the stencil equals the mask depth.
+fn create_instance_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Instance Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_shape"),
+            compilation_options: Default::default(),
+            buffers: &[super::ShapeInstance::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_shape"),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: mask_stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: MSAA_SAMPLE_COUNT,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+/// The pipeline drawing sample plots, with the layout of the bind group of each plot.
+pub(crate) struct PlotPipeline {
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    /// Layout of the uniforms and the storage buffer of the samples of a plot
+    pub(crate) bind_group_layout: wgpu::BindGroupLayout,
+}
+
+/// Creates a pipeline drawing sample plots from their samples without vertex buffers, where the stencil equals the mask depth.
+///
+/// The plot shader is a separate module, as it doesn't compile without storage buffers.
+fn create_plot_pipeline(device: &Device, camera_layout: &wgpu::BindGroupLayout, format: wgpu::TextureFormat) -> PlotPipeline {
+    let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Buffer { ty, has_dynamic_offset: false, min_binding_size: None },
+        count: None,
+    };
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Plot Bind Group Layout"),
+        entries: &[
+            entry(0, wgpu::BufferBindingType::Uniform),
+            entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+        ],
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Fast2D Plot Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../backend_wgpu/plot.wgsl").into()),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Plot Pipeline Layout"),
+        bind_group_layouts: &[camera_layout, &bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Plot Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_plot"),
+            compilation_options: Default::default(),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_plot"),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: mask_stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: MSAA_SAMPLE_COUNT,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    });
+    PlotPipeline { pipeline, bind_group_layout }
+}
+
+/// Creates a stencil state comparing the stencil value with the reference and applying the operation where it passes.
+///
+/// Shapes are drawn where the stencil equals the mask depth, masks increment it when pushed and decrement it when popped.
+fn mask_stencil_state(compare: wgpu::CompareFunction, pass_op: wgpu::StencilOperation) -> wgpu::StencilState {
+    let face = wgpu::StencilFaceState { compare, fail_op: wgpu::StencilOperation::Keep, depth_fail_op: wgpu::StencilOperation::Keep, pass_op };
+    let write_mask = if pass_op == wgpu::StencilOperation::Keep { 0 } else { 0xff };
+    wgpu::StencilState { front: face, back: face, read_mask: 0xff, write_mask }
+}
+
+/// Creates the multisampled stencil texture holding the active masks of every pixel.
+fn create_stencil_texture(device: &Device, width: u32, height: u32) -> Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Stencil Texture"),
+        size: wgpu::Extent3d { width: width.max(1), height: height.max(1), depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: MSAA_SAMPLE_COUNT,
+        dimension: wgpu::TextureDimension::D2,
+        format: STENCIL_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    })
+}
+
+/// Creates the text atlas again with no glyphs, shrinking its textures to their initial size.
+///
+/// All text must be prepared again before it's drawn, the text renderers are kept.
+pub(crate) fn recreate_atlas(graphics: &mut Graphics) {
+    graphics.text_atlas = TextAtlas::new(&graphics.device, &graphics.queue, &graphics.glyphon_cache, graphics.surface_config.format);
+    graphics.atlas_size.reset();
+}
+
+/// Returns the bytes of the multisampled color and stencil textures.
+pub(crate) fn render_target_size(graphics: &Graphics) -> u64 {
+    // The stencil format has no single block size, depth and stencil take four bytes together
+    let texture_size = |texture: &Texture| {
+        let bytes = texture.format().block_copy_size(None).unwrap_or(4);
+        texture.width() as u64 * texture.height() as u64 * texture.sample_count() as u64 * bytes as u64
+    };
+    texture_size(&graphics.msaa_texture) + texture_size(&graphics.stencil_texture)
+}
+
+/// Estimated size of the glyph atlas textures, which glyphon doesn't report.
+///
+/// Glyphon keeps mask glyphs in a single channel texture and color glyphs in an RGBA texture.
+/// Both are square and double their width when the glyphs in use don't fit, they never shrink.
+/// Glyphs are counted as em squares, and only inline icons as color glyphs.
+pub(crate) struct GlyphAtlasSize {
+    /// Width of the mask texture
+    mask_width: u32,
+    /// Width of the color texture
+    color_width: u32,
+    /// Largest texture width of the device
+    max_width: u32,
+    /// Glyphs counted since `begin`, with the pixels of the mask and color glyphs
+    glyphs: HashSet<glyphon::CacheKey>,
+    icons: HashSet<(u16, u32, u32)>,
+    mask_pixels: u64,
+    color_pixels: u64,
+    /// Widths the glyphs in use counted by the last `finish` fit in
+    needed_widths: (u32, u32),
+}
+
+impl GlyphAtlasSize {
+    /// Creates the size of a new atlas.
+    pub(crate) fn new(max_width: u32) -> Self {
+        Self {
+            mask_width: INITIAL_ATLAS_WIDTH,
+            color_width: INITIAL_ATLAS_WIDTH,
+            max_width,
+            glyphs: HashSet::new(),
+            icons: HashSet::new(),
+            mask_pixels: 0,
+            color_pixels: 0,
+            needed_widths: (INITIAL_ATLAS_WIDTH, INITIAL_ATLAS_WIDTH),
+        }
+    }
+
+    /// Starts counting the glyphs in use, when all text is prepared again.
+    pub(crate) fn begin(&mut self) {
+        self.glyphs.clear();
+        self.icons.clear();
+        self.mask_pixels = 0;
+        self.color_pixels = 0;
+    }
+
+    /// Counts a glyph prepared for drawing.
+    pub(crate) fn add_glyph(&mut self, key: glyphon::CacheKey) {
+        if self.glyphs.insert(key) {
+            let size = f32::from_bits(key.font_size_bits).ceil() as u64;
+            self.mask_pixels += size * size;
+        }
+    }
+
+    /// Counts an inline icon prepared for drawing with the size in pixels.
+    pub(crate) fn add_icon(&mut self, id: u16, width: f32, height: f32) {
+        if self.icons.insert((id, width.to_bits(), height.to_bits())) {
+            self.color_pixels += width.ceil() as u64 * height.ceil() as u64;
+        }
+    }
+
+    /// Grows the textures to fit the glyphs counted since `begin`.
+    pub(crate) fn finish(&mut self) {
+        self.needed_widths = (self.fitting_width(self.mask_pixels), self.fitting_width(self.color_pixels));
+        self.mask_width = self.mask_width.max(self.needed_widths.0);
+        self.color_width = self.color_width.max(self.needed_widths.1);
+    }
+
+    /// Returns true if an atlas created again would be smaller, as glyphs not in use take space.
+    pub(crate) fn can_shrink(&self) -> bool {
+        self.needed_widths.0 < self.mask_width || self.needed_widths.1 < self.color_width
+    }
+
+    /// Shrinks the textures to their initial width, after the atlas was created again.
+    fn reset(&mut self) {
+        self.mask_width = INITIAL_ATLAS_WIDTH;
+        self.color_width = INITIAL_ATLAS_WIDTH;
+    }
+
+    /// Returns the estimated bytes of both textures.
+    pub(crate) fn bytes(&self) -> u64 {
+        let mask_width = self.mask_width as u64;
+        let color_width = self.color_width as u64;
+        mask_width * mask_width + color_width * color_width * 4
+    }
+
+    /// Returns the smallest width, doubling from the initial width, of a square texture holding the pixels.
+    fn fitting_width(&self, pixels: u64) -> u32 {
+        let mut width = INITIAL_ATLAS_WIDTH;
+        while (width as u64 * width as u64) < pixels && width < self.max_width {
+            width *= 2;
+        }
+        width.min(self.max_width)
+    }
+}
+
+/// A GPU buffer kept between frames and written with `queue.write_buffer`, reallocated only when the data outgrows it.
+pub(crate) struct GrowableBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    /// The buffer, None until data is first written, as empty buffers can't be bound
+    buffer: Option<WgpuBuffer>,
+}
+
+impl GrowableBuffer {
+    /// Creates a buffer with the usage, allocated when data is first written.
+    pub(crate) fn new(label: &'static str, usage: wgpu::BufferUsages) -> Self {
+        Self { label, usage: usage | wgpu::BufferUsages::COPY_DST, buffer: None }
+    }
+
+    /// Writes the data to the start of the buffer, allocating a larger buffer if it doesn't fit.
+    ///
+    /// The capacity grows to the next power of two, so a growing scene reallocates rarely.
+    /// The data must be a multiple of 4 bytes long, empty data is not written.
+    pub(crate) fn write(&mut self, device: &Device, queue: &Queue, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        let size = data.len() as wgpu::BufferAddress;
+        if self.buffer.as_ref().is_none_or(|buffer| buffer.size() < size) {
+            self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size: size.next_power_of_two().max(MIN_BUFFER_SIZE),
+                usage: self.usage,
+                mapped_at_creation: false,
+            }));
+        }
+        queue.write_buffer(self.buffer.as_ref().unwrap(), 0, data);
+    }
+
+    /// Returns the allocated bytes of the buffer.
+    pub(crate) fn size(&self) -> u64 {
+        self.buffer.as_ref().map_or(0, |buffer| buffer.size())
+    }
+
+    /// Returns the buffer, or None if no data was written yet.
+    pub(crate) fn buffer(&self) -> Option<&WgpuBuffer> {
+        self.buffer.as_ref()
+    }
+}
+
+/// Creates a glyphon text renderer drawing into the MSAA texture, sharing the glyphs of the atlas.
+///
+/// Each layer has a text renderer for each run of text drawn between its shapes.
+pub(crate) fn create_text_renderer(device: &Device, atlas: &mut TextAtlas) -> TextRenderer {
+    TextRenderer::new(
+        atlas,
+        device,
+        wgpu::MultisampleState {
+            count: MSAA_SAMPLE_COUNT,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        // Text ignores masks, masked text is drawn from outlines with the shapes
+        Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+    )
+}
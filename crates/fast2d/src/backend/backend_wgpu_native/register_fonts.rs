@@ -1,45 +1,92 @@
-use std::sync::Mutex;
+use std::path::Path;
 use glyphon::FontSystem;
-use crate::backend::{FONT_SYSTEM, RegisterFontsError};
+use glyphon::fontdb::Database;
+use crate::backend::{with_font_database, RegisterFontsError, FontDescriptor};
+
+/// Locale used for script-specific font fallback when the system locale is not available.
+const FALLBACK_LOCALE: &str = "en-US";
 
 /// Registers fonts for the native WGPU backend.
 ///
 /// You can call this function multiple times to add more fonts.
 /// This is the synchronous version for native applications.
 ///
+/// Only the provided fonts are available for rendering,
+/// call [`load_system_fonts`] to also use the fonts installed on the system.
+///
 /// # Arguments
 /// * `fonts` - Font data as a Vec of Vec<u8> (e.g., TTF or OTF).
 ///
 /// # Returns
-/// * `Ok(())` if at least one valid font is loaded or added.
+/// * `Ok(Vec<FontDescriptor>)` describing the newly loaded faces if at least one valid font is loaded or added.
 /// * `Err(RegisterFontsError)` if no valid font is loaded, or if no fonts are provided.
-pub fn register_fonts(fonts: Vec<Vec<u8>>) -> Result<(), RegisterFontsError> {
+pub fn register_fonts(fonts: Vec<Vec<u8>>) -> Result<Vec<FontDescriptor>, RegisterFontsError> {
     if fonts.is_empty() {
         return Err(RegisterFontsError::NoFontsProvided);
     }
-
-    // If already initialized, just add new fonts
-    if let Some(font_system_mutex) = FONT_SYSTEM.get() {
-        let mut font_system = font_system_mutex.lock().expect("Failed to lock font system");
-        let db = font_system.db_mut();
+    with_font_database(new_font_system, |db| {
         for font_data in fonts {
             db.load_font_data(font_data);
         }
-        if db.faces().next().is_none() {
-            return Err(RegisterFontsError::NoValidFontLoaded);
-        }
-        return Ok(());
-    }
+        Ok(())
+    })
+}
 
-    // Not initialized yet: create and load
-    let mut font_system = FontSystem::new();
-    let db = font_system.db_mut();
-    for font_data in fonts {
-        db.load_font_data(font_data);
-    }
-    if db.faces().next().is_none() {
-        return Err(RegisterFontsError::NoValidFontLoaded);
+/// Registers fonts from font files or directories for the native WGPU backend.
+///
+/// Directories are scanned recursively for TTF, OTF, TTC and OTC files.
+/// Files in a directory that cannot be read or parsed are skipped, only a path given directly
+/// that cannot be read is an error.
+/// You can call this function multiple times to add more fonts.
+///
+/// # Arguments
+/// * `paths` - Paths to font files or directories containing font files.
+///
+/// # Returns
+/// * `Ok(Vec<FontDescriptor>)` describing the newly loaded faces if at least one valid font is loaded or added.
+/// * `Err(RegisterFontsError)` if a path cannot be read, no valid font is loaded, or no paths are provided.
+pub fn register_font_paths<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Vec<FontDescriptor>, RegisterFontsError> {
+    let paths: Vec<P> = paths.into_iter().collect();
+    if paths.is_empty() {
+        return Err(RegisterFontsError::NoFontsProvided);
     }
-    FONT_SYSTEM.set(Mutex::new(font_system)).expect("Failed to set font system");
-    Ok(())
-}
\ No newline at end of file
+    with_font_database(new_font_system, |db| {
+        for path in paths {
+            let path = path.as_ref();
+            if path.is_dir() {
+                db.load_fonts_dir(path);
+            } else {
+                db.load_font_file(path)
+                    .map_err(|error| RegisterFontsError::FontFileError(format!("{}: {error}", path.display())))?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Loads the fonts installed on the system alongside the registered fonts.
+///
+/// Uses the fontconfig directories on Linux and the standard font folders on Windows and macOS.
+/// Scanning the system fonts may take a while, call it once during startup.
+///
+/// # Returns
+/// * `Ok(Vec<FontDescriptor>)` describing the newly loaded faces if at least one valid font is available.
+/// * `Err(RegisterFontsError)` if no valid font is loaded.
+pub fn load_system_fonts() -> Result<Vec<FontDescriptor>, RegisterFontsError> {
+    with_font_database(new_font_system, |db| {
+        db.load_system_fonts();
+        Ok(())
+    })
+}
+
+/// Creates the font system with an empty database, system fonts are loaded only on request.
+///
+/// The generic families resolve to the same fonts as with `FontSystem::new`.
+fn new_font_system() -> FontSystem {
+    let mut db = Database::new();
+    db.set_sans_serif_family("Open Sans");
+    db.set_serif_family("DejaVu Serif");
+    db.set_monospace_family("Noto Sans Mono");
+    let locale = sys_locale::get_locale().unwrap_or_else(|| FALLBACK_LOCALE.to_owned());
+    FontSystem::new_with_locale_and_db(locale, db)
+}
@@ -0,0 +1,93 @@
+//! The font database of the glyphon backends, shared by their font registration functions.
+//!
+//! Each backend creates the font system its own way on the first registration,
+//! introspection and removal of faces work the same for all of them.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use glyphon::FontSystem;
+use glyphon::fontdb::{Database, FaceInfo, Query, Style, Weight, ID as FontId};
+use crate::backend::{glyphon_family, FONT_SYSTEM, FONT_GENERATION, RegisterFontsError, FontDescriptor};
+use crate::object2d::{Family, FontWeight};
+
+/// Returns descriptors of all registered font faces.
+pub fn registered_fonts() -> Vec<FontDescriptor> {
+    let Some(font_system_mutex) = FONT_SYSTEM.get() else {
+        return Vec::new();
+    };
+    let font_system = font_system_mutex.lock().expect("Failed to lock font system");
+    font_system.db().faces().map(font_descriptor).collect()
+}
+
+/// Returns the registered face that would be used to render text with the given family, weight and style.
+///
+/// Returns `None` if no face of the family is registered, e.g. because of a typo in the family name.
+/// The returned face may differ in weight or style when no exact match exists.
+pub fn resolve_font(family: &Family, weight: FontWeight, italic: bool) -> Option<FontDescriptor> {
+    let font_system = FONT_SYSTEM.get()?.lock().expect("Failed to lock font system");
+    let db = font_system.db();
+    let face_id = db.query(&Query {
+        families: &[glyphon_family(family)],
+        weight: Weight(weight.value()),
+        style: if italic { Style::Italic } else { Style::Normal },
+        ..Default::default()
+    })?;
+    db.face(face_id).map(font_descriptor)
+}
+
+/// Removes all registered faces matching the descriptor (family, weight and style).
+///
+/// # Returns
+/// `true` if at least one face was removed.
+pub fn unregister_font(descriptor: &FontDescriptor) -> bool {
+    let Some(font_system_mutex) = FONT_SYSTEM.get() else {
+        return false;
+    };
+    let mut font_system = font_system_mutex.lock().expect("Failed to lock font system");
+    let db = font_system.db_mut();
+    let face_ids: Vec<FontId> = db.faces()
+        .filter(|face| font_descriptor(face) == *descriptor)
+        .map(|face| face.id)
+        .collect();
+    for face_id in &face_ids {
+        db.remove_face(*face_id);
+    }
+    if !face_ids.is_empty() {
+        FONT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+    !face_ids.is_empty()
+}
+
+/// Runs `load` with the font database, creating the font system with `new_font_system` on first use.
+///
+/// Returns descriptors of the faces added by `load`.
+pub(crate) fn with_font_database(
+    new_font_system: impl FnOnce() -> FontSystem,
+    load: impl FnOnce(&mut Database) -> Result<(), RegisterFontsError>,
+) -> Result<Vec<FontDescriptor>, RegisterFontsError> {
+    let font_system_mutex = FONT_SYSTEM.get_or_init(|| Mutex::new(new_font_system()));
+    let mut font_system = font_system_mutex.lock().expect("Failed to lock font system");
+    let db = font_system.db_mut();
+    let previous_ids: HashSet<FontId> = db.faces().map(|face| face.id).collect();
+    let loaded = load(db);
+    // Faces may be added before an error, text is shaped again either way
+    FONT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    loaded?;
+    if db.faces().next().is_none() {
+        return Err(RegisterFontsError::NoValidFontLoaded);
+    }
+    Ok(db.faces()
+        .filter(|face| !previous_ids.contains(&face.id))
+        .map(font_descriptor)
+        .collect())
+}
+
+/// Creates a descriptor from the face info stored in the font database.
+fn font_descriptor(face: &FaceInfo) -> FontDescriptor {
+    FontDescriptor {
+        family: face.families.first().map(|(name, _)| name.clone()).unwrap_or_default(),
+        weight: face.weight.0,
+        italic: face.style != Style::Normal,
+    }
+}
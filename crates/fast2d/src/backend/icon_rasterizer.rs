@@ -0,0 +1,144 @@
+//! Rasterization of inline text icons for glyphon custom glyphs, shared by the wgpu backends.
+//!
+//! Path icons are rasterized into alpha masks tinted with the text color,
+//! bitmap icons are resampled into color images.
+
+use glyphon::{ContentType, RasterizeCustomGlyphRequest, RasterizedCustomGlyph};
+use lyon::math::{point, vector, Angle, Point, Transform};
+use lyon::path::{ArcFlags, Path, PathEvent};
+use lyon::path::builder::SvgPathBuilder;
+use crate::backend::with_icon_glyph;
+use crate::object2d::{Icon, PathCommand};
+
+/// Maximum distance in pixels between curves and their flattened line segments.
+const FLATTENING_TOLERANCE: f32 = 0.05;
+/// Number of coverage samples per pixel in each direction.
+const SAMPLES_PER_PIXEL: usize = 4;
+
+/// Rasterizes the icon with the requested glyph id at the requested size.
+pub fn rasterize_icon(request: RasterizeCustomGlyphRequest) -> Option<RasterizedCustomGlyph> {
+    let (width, height) = (request.width as usize, request.height as usize);
+    if width == 0 || height == 0 {
+        return None;
+    }
+    with_icon_glyph(request.id, |registered| match &registered.icon {
+        Icon::Path { width: view_box_width, height: view_box_height, .. } => {
+            let transform = Transform::scale(width as f32 / view_box_width, height as f32 / view_box_height);
+            RasterizedCustomGlyph {
+                data: rasterize_path(&registered.path, transform, width, height),
+                content_type: ContentType::Mask,
+            }
+        }
+        Icon::Bitmap { width: source_width, height: source_height, rgba } => RasterizedCustomGlyph {
+            data: resample_rgba(rgba, *source_width as usize, *source_height as usize, width, height),
+            content_type: ContentType::Color,
+        },
+    })
+}
+
+/// Fills the path with the nonzero rule into an alpha mask of the given size.
+fn rasterize_path(commands: &[PathCommand], transform: Transform, width: usize, height: usize) -> Vec<u8> {
+    let mut builder = Path::builder()
+        .flattened(FLATTENING_TOLERANCE)
+        .transformed(transform)
+        .with_svg();
+    add_path_commands(&mut builder, commands);
+    let path = builder.build();
+
+    // Collect the edges of the flattened path, open sub-paths are closed for filling
+    let mut edges: Vec<(Point, Point)> = Vec::new();
+    for event in path.iter() {
+        match event {
+            PathEvent::Line { from, to } => edges.push((from, to)),
+            PathEvent::End { last, first, .. } => edges.push((last, first)),
+            _ => {}
+        }
+    }
+
+    // Accumulate coverage of sample points, row by row
+    let mut coverage = vec![0u16; width * height];
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    let samples = SAMPLES_PER_PIXEL as f32;
+    for sample_row in 0..height * SAMPLES_PER_PIXEL {
+        let y = (sample_row as f32 + 0.5) / samples;
+        crossings.clear();
+        for (from, to) in &edges {
+            if (from.y <= y) != (to.y <= y) {
+                let x = from.x + (y - from.y) / (to.y - from.y) * (to.x - from.x);
+                crossings.push((x, if to.y > from.y { 1 } else { -1 }));
+            }
+        }
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let row_start = sample_row / SAMPLES_PER_PIXEL * width;
+        let mut winding = 0;
+        for pair in crossings.windows(2) {
+            winding += pair[0].1;
+            if winding == 0 {
+                continue;
+            }
+            // Sample columns whose centers lie between the two crossings
+            let first_column = (pair[0].0 * samples - 0.5).ceil().max(0.0) as usize;
+            let end_column = ((pair[1].0 * samples - 0.5).ceil().max(0.0) as usize).min(width * SAMPLES_PER_PIXEL);
+            for sample_column in first_column..end_column {
+                coverage[row_start + sample_column / SAMPLES_PER_PIXEL] += 1;
+            }
+        }
+    }
+
+    let full_coverage = (SAMPLES_PER_PIXEL * SAMPLES_PER_PIXEL) as u32;
+    coverage.into_iter()
+        .map(|samples| (samples as u32 * 255 / full_coverage).min(255) as u8)
+        .collect()
+}
+
+/// Adds the parsed SVG path commands to the path builder.
+pub fn add_path_commands(builder: &mut impl SvgPathBuilder, commands: &[PathCommand]) {
+    for command in commands {
+        match *command {
+            PathCommand::MoveTo { x, y } => { builder.move_to(point(x, y)); }
+            PathCommand::LineTo { x, y } => { builder.line_to(point(x, y)); }
+            PathCommand::QuadraticTo { x1, y1, x, y } => { builder.quadratic_bezier_to(point(x1, y1), point(x, y)); }
+            PathCommand::CubicTo { x1, y1, x2, y2, x, y } => { builder.cubic_bezier_to(point(x1, y1), point(x2, y2), point(x, y)); }
+            PathCommand::ArcTo { rx, ry, x_axis_rotation, large_arc, sweep, x, y } => {
+                builder.arc_to(vector(rx, ry), Angle::degrees(x_axis_rotation), ArcFlags { large_arc, sweep }, point(x, y));
+            }
+            PathCommand::Close => builder.close(),
+        }
+    }
+}
+
+/// Resamples RGBA pixels to the target size, averaging the covered source pixels when downscaling.
+fn resample_rgba(rgba: &[u8], source_width: usize, source_height: usize, width: usize, height: usize) -> Vec<u8> {
+    let mut resampled = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        let source_rows = source_range(y, height, source_height);
+        for x in 0..width {
+            let source_columns = source_range(x, width, source_width);
+            // Average with alpha weighting, so transparent pixels don't darken the edges
+            let mut sum = [0u32; 4];
+            for source_y in source_rows.clone() {
+                for source_x in source_columns.clone() {
+                    let pixel = &rgba[(source_y * source_width + source_x) * 4..][..4];
+                    let alpha = pixel[3] as u32;
+                    sum[0] += pixel[0] as u32 * alpha;
+                    sum[1] += pixel[1] as u32 * alpha;
+                    sum[2] += pixel[2] as u32 * alpha;
+                    sum[3] += alpha;
+                }
+            }
+            let pixel_count = (source_rows.len() * source_columns.len()) as u32;
+            // Fully transparent pixels have no color
+            let color = |channel: u32| channel.checked_div(sum[3]).unwrap_or(0) as u8;
+            resampled.extend_from_slice(&[color(sum[0]), color(sum[1]), color(sum[2]), (sum[3] / pixel_count) as u8]);
+        }
+    }
+    resampled
+}
+
+/// Returns the range of source pixels covered by the target pixel, at least one pixel.
+fn source_range(target: usize, target_size: usize, source_size: usize) -> std::ops::Range<usize> {
+    let start = target * source_size / target_size;
+    let end = ((target + 1) * source_size / target_size).max(start + 1).min(source_size);
+    start..end
+}
@@ -0,0 +1,162 @@
+//! Registry of icons rendered inline with text, shared by all backends.
+
+use std::collections::BTreeMap;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use crate::object2d::{Icon, PathCommand, parse_svg_path};
+use super::RegisterIconError;
+
+/// An icon with its parsed path data.
+pub(crate) struct RegisteredIcon {
+    pub icon: Icon,
+    /// Parsed path commands for the GPU rasterizer (empty for bitmap icons),
+    /// the canvas backend fills the path data directly
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    pub path: Vec<PathCommand>,
+    /// Unique per registration, used as the canvas image cache key and in the text cache keys,
+    /// so re-registering an id never shows a stale icon
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "canvas"))]
+    pub revision: u64,
+    /// The glyphon custom glyph id, unique among the registered icons
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    pub glyph_id: u16,
+}
+
+struct IconRegistry {
+    icons: BTreeMap<u16, RegisteredIcon>,
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "canvas"))]
+    next_revision: u64,
+    /// The icon id of each glyph id in use
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    glyph_icons: BTreeMap<u16, u16>,
+    /// Glyph ids never handed out yet, from 0 up to `u16::MAX`
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    next_glyph_id: u32,
+    /// Glyph ids of removed and replaced icons, reused oldest first only when no fresh id is left,
+    /// so the glyph atlas has long evicted the old icon
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    free_glyph_ids: VecDeque<u16>,
+}
+
+static ICONS: Mutex<IconRegistry> = Mutex::new(IconRegistry {
+    icons: BTreeMap::new(),
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "canvas"))]
+    next_revision: 0,
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    glyph_icons: BTreeMap::new(),
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    next_glyph_id: 0,
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    free_glyph_ids: VecDeque::new(),
+});
+
+impl IconRegistry {
+    /// Removes the icon with the id, its glyph id becomes free.
+    fn remove(&mut self, id: u16) -> Option<RegisteredIcon> {
+        let icon = self.icons.remove(&id)?;
+        #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+        {
+            self.glyph_icons.remove(&icon.glyph_id);
+            self.free_glyph_ids.push_back(icon.glyph_id);
+        }
+        Some(icon)
+    }
+
+    /// Returns a glyph id not used by any registered icon, or `None` if all of them are used.
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    fn allocate_glyph_id(&mut self) -> Option<u16> {
+        if let Ok(glyph_id) = u16::try_from(self.next_glyph_id) {
+            self.next_glyph_id += 1;
+            return Some(glyph_id);
+        }
+        self.free_glyph_ids.pop_front()
+    }
+}
+
+/// Registers an icon that can be rendered inline with text.
+///
+/// Reference the icon in text content with [`icon_char`](crate::icon_char) or [`Text::icon`](crate::Text::icon).
+/// Registering an icon with an existing id replaces it.
+///
+/// # Arguments
+/// * `id` - The id used to reference the icon.
+/// * `icon` - SVG path data or an RGBA bitmap, see [`Icon`].
+///
+/// # Returns
+/// * `Ok(())` if the icon is registered.
+/// * `Err(RegisterIconError)` if the path data cannot be parsed, the bitmap size doesn't match its data,
+///   or the wgpu backends have no glyph id left for the icon (65 536 icons registered at once).
+pub fn register_icon(id: u16, icon: Icon) -> Result<(), RegisterIconError> {
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    let path = validate_icon(&icon)?;
+    #[cfg(not(any(feature = "webgl", feature = "webgpu", feature = "native")))]
+    validate_icon(&icon)?;
+    let mut registry = ICONS.lock().expect("Failed to lock icon registry");
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    let glyph_id = registry.allocate_glyph_id().ok_or(RegisterIconError::TooManyIcons)?;
+    registry.remove(id);
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    registry.glyph_icons.insert(glyph_id, id);
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "canvas"))]
+    let revision = {
+        let revision = registry.next_revision;
+        registry.next_revision += 1;
+        revision
+    };
+    registry.icons.insert(id, RegisteredIcon {
+        icon,
+        #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+        path,
+        #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "canvas"))]
+        revision,
+        #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+        glyph_id,
+    });
+    Ok(())
+}
+
+/// Checks the icon size and data, returns the parsed path commands of path icons.
+fn validate_icon(icon: &Icon) -> Result<Vec<PathCommand>, RegisterIconError> {
+    match icon {
+        Icon::Path { data, width, height } => {
+            if *width <= 0.0 || *height <= 0.0 {
+                return Err(RegisterIconError::EmptyIcon);
+            }
+            parse_svg_path(data).map_err(RegisterIconError::InvalidPath)
+        }
+        Icon::Bitmap { width, height, rgba } => {
+            if *width == 0 || *height == 0 {
+                return Err(RegisterIconError::EmptyIcon);
+            }
+            let expected_length = *width as usize * *height as usize * 4;
+            if rgba.len() != expected_length {
+                return Err(RegisterIconError::InvalidBitmap(format!(
+                    "expected {expected_length} bytes for {width}x{height} pixels, got {}", rgba.len()
+                )));
+            }
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Removes the icon registered with the given id.
+///
+/// # Returns
+/// `true` if an icon was removed.
+pub fn unregister_icon(id: u16) -> bool {
+    ICONS.lock().expect("Failed to lock icon registry").remove(id).is_some()
+}
+
+/// Calls `f` with the icon registered with the given id.
+pub(crate) fn with_icon<R>(id: u16, f: impl FnOnce(&RegisteredIcon) -> R) -> Option<R> {
+    ICONS.lock().expect("Failed to lock icon registry").icons.get(&id).map(f)
+}
+
+/// Calls `f` with the icon registered with the given glyph id.
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+pub(crate) fn with_icon_glyph<R>(glyph_id: u16, f: impl FnOnce(&RegisteredIcon) -> R) -> Option<R> {
+    let registry = ICONS.lock().expect("Failed to lock icon registry");
+    let id = registry.glyph_icons.get(&glyph_id)?;
+    registry.icons.get(id).map(f)
+}
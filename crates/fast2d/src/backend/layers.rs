@@ -0,0 +1,382 @@
+//! Named layers of objects drawn in the order of their z-index, shared by all backends.
+//!
+//! Each layer keeps what its backend prepared for drawing it (GPU buffers or an offscreen canvas),
+//! so a frame only prepares the layers changed since the previous one.
+//! Objects have ids, so a backend can also reuse what it prepared for the unchanged objects of a changed layer.
+//! A layer is indexed by the bounds of its objects on the first hit test or region query, the index is then
+//! updated with the objects, also by a layer update that keeps the number of objects.
+
+use std::cell::OnceCell;
+#[cfg(feature = "webgpu-blade")]
+use std::marker::PhantomData;
+use crate::Object2d;
+use crate::object2d::Bounds;
+use super::spatial_index::SpatialIndex;
+
+/// The name of the layer updated by `CanvasWrapper::update_objects`, it has z-index 0.
+pub const DEFAULT_LAYER: &str = "default";
+
+/// A handle to an object added with `CanvasWrapper::insert_object`, for updating or removing it later.
+///
+/// Ids are never reused. The handles of a layer's objects become invalid when the whole layer is
+/// updated with `CanvasWrapper::update_layer`, as the objects can't be told apart after that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(pub(super) u64);
+
+/// Which objects a region query returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionMode {
+    /// Objects whose bounds intersect or touch the region
+    Intersecting,
+    /// Objects whose bounds lie completely inside the region, e.g. for rubber-band selection
+    Inside,
+}
+
+/// A named list of objects with the backend's cached drawing of them.
+pub(crate) struct Layer<C> {
+    pub(crate) name: String,
+    pub(crate) z_index: i32,
+    pub(crate) objects: Vec<Object2d>,
+    /// The id of each object, in ascending order as objects are only appended
+    pub(crate) ids: Vec<ObjectId>,
+    /// Whether each object changed since the cache was prepared
+    pub(crate) changed: Vec<bool>,
+    /// Set when the objects changed since the cache was prepared
+    pub(crate) dirty: bool,
+    /// Resources the backend keeps between frames, reused when the layer is prepared again
+    #[cfg(not(feature = "webgpu-blade"))]
+    pub(crate) cache: Option<C>,
+    /// The `webgpu-blade` backend draws all layers every frame without a cache
+    #[cfg(feature = "webgpu-blade")]
+    cache: PhantomData<C>,
+    /// Spatial index of the object bounds, built on the first query
+    index: OnceCell<SpatialIndex>,
+}
+
+impl<C> Layer<C> {
+    /// Marks the layer and all its objects as prepared, after the backend updated the cache.
+    #[cfg(not(feature = "webgpu-blade"))]
+    pub(crate) fn mark_prepared(&mut self) {
+        self.dirty = false;
+        self.changed.fill(false);
+    }
+
+    /// Returns the spatial index of the objects, building it if the layer was not queried since it was updated.
+    fn index(&self) -> &SpatialIndex {
+        self.index.get_or_init(|| SpatialIndex::new(self.ids.iter().copied().zip(self.objects.iter().map(Object2d::bounds))))
+    }
+
+    /// Updates the bounds of the object at the index in the spatial index, if there is one.
+    fn reindex(&mut self, index: usize) {
+        if let Some(spatial_index) = self.index.get_mut() {
+            spatial_index.update(self.ids[index], self.objects[index].bounds());
+        }
+    }
+
+    /// Returns the indices of the objects whose bounds intersect or touch the area, in drawing order.
+    ///
+    /// Objects without bounds draw nothing and are left out, so backends can skip everything else when drawing.
+    pub(crate) fn visible(&self, area: &Bounds) -> Vec<usize> {
+        let mut visible = Vec::new();
+        self.index().query(area, |id, _| {
+            if let Ok(index) = self.ids.binary_search(&id) {
+                visible.push(index);
+            }
+        });
+        visible.sort_unstable();
+        visible
+    }
+
+    /// Returns the object with the id, or `None` if it is not in the layer.
+    fn object(&self, id: ObjectId) -> Option<&Object2d> {
+        self.ids.binary_search(&id).ok().map(|index| &self.objects[index])
+    }
+}
+
+/// Layers sorted by z-index, layers with the same z-index in the order they were added.
+pub(crate) struct Layers<C> {
+    layers: Vec<Layer<C>>,
+    next_id: u64,
+}
+
+impl<C> Layers<C> {
+    /// Creates the layers with an empty default layer.
+    pub(crate) fn new() -> Self {
+        let mut layers = Self { layers: Vec::new(), next_id: 0 };
+        layers.get_or_insert(DEFAULT_LAYER);
+        layers
+    }
+
+    /// Returns the index of the layer with the name, adding an empty layer with z-index 0 if there is none.
+    fn index_or_insert(&mut self, name: &str) -> usize {
+        match self.layers.iter().position(|layer| layer.name == name) {
+            Some(index) => index,
+            None => {
+                self.layers.push(Layer {
+                    name: name.to_owned(),
+                    z_index: 0,
+                    objects: Vec::new(),
+                    ids: Vec::new(),
+                    changed: Vec::new(),
+                    dirty: true,
+                    cache: Default::default(),
+                    index: OnceCell::new(),
+                });
+                self.sort();
+                self.layers.iter().position(|layer| layer.name == name).unwrap()
+            }
+        }
+    }
+
+    /// Returns the layer with the name, adding an empty layer with z-index 0 if there is none.
+    fn get_or_insert(&mut self, name: &str) -> &mut Layer<C> {
+        let index = self.index_or_insert(name);
+        &mut self.layers[index]
+    }
+
+    /// Lets the updater change the objects of the layer and marks it for preparing again.
+    ///
+    /// All objects of the layer get new ids, as the updater may have reordered, replaced or removed any of them.
+    /// When the number of objects is unchanged, the spatial index is updated with the objects whose bounds changed.
+    pub(crate) fn update(&mut self, name: &str, updater: impl FnOnce(&mut Vec<Object2d>)) {
+        let index = self.index_or_insert(name);
+        let layer = &mut self.layers[index];
+        updater(&mut layer.objects);
+        let count = layer.objects.len();
+        let previous_ids = std::mem::replace(&mut layer.ids, (self.next_id..self.next_id + count as u64).map(ObjectId).collect());
+        layer.changed = vec![true; count];
+        layer.dirty = true;
+        self.next_id += count as u64;
+        match layer.index.get_mut() {
+            // Objects keep their place in the index, only those whose bounds changed are updated,
+            // so the index is packed again only after enough changes
+            Some(spatial_index) if previous_ids.len() == count => {
+                let position = |id: ObjectId| previous_ids.binary_search(&id).expect("Indexed object not in layer");
+                let mut previous_bounds = vec![None; count];
+                for (id, bounds) in spatial_index.bounds() {
+                    previous_bounds[position(id)] = Some(*bounds);
+                }
+                spatial_index.renumber(|id| layer.ids[position(id)]);
+                for (index, object) in layer.objects.iter().enumerate() {
+                    let bounds = object.bounds();
+                    if bounds != previous_bounds[index] {
+                        spatial_index.update(layer.ids[index], bounds);
+                    }
+                }
+            }
+            // Indexing all objects again is left to the next query
+            _ => layer.index = OnceCell::new(),
+        }
+    }
+
+    /// Adds the object on top of the other objects of the layer, adding the layer if there is none.
+    pub(crate) fn insert(&mut self, name: &str, object: Object2d) -> ObjectId {
+        let id = ObjectId(self.next_id);
+        self.next_id += 1;
+        let layer = self.get_or_insert(name);
+        layer.objects.push(object);
+        layer.ids.push(id);
+        layer.changed.push(true);
+        layer.dirty = true;
+        layer.reindex(layer.objects.len() - 1);
+        id
+    }
+
+    /// Returns the indices of the layer and of the object with the id within it.
+    fn find(&self, id: ObjectId) -> Option<(usize, usize)> {
+        self.layers.iter().enumerate().find_map(|(layer_index, layer)| {
+            layer.ids.binary_search(&id).ok().map(|index| (layer_index, index))
+        })
+    }
+
+    /// Returns the object with the id, or `None` if it was removed.
+    pub(crate) fn object(&self, id: ObjectId) -> Option<&Object2d> {
+        let (layer_index, index) = self.find(id)?;
+        Some(&self.layers[layer_index].objects[index])
+    }
+
+    /// Lets the updater change the object with the id and marks it for preparing again,
+    /// returns false if there is no such object.
+    pub(crate) fn update_object(&mut self, id: ObjectId, updater: impl FnOnce(&mut Object2d)) -> bool {
+        let Some((layer_index, index)) = self.find(id) else {
+            return false;
+        };
+        let layer = &mut self.layers[layer_index];
+        updater(&mut layer.objects[index]);
+        layer.changed[index] = true;
+        layer.dirty = true;
+        layer.reindex(index);
+        true
+    }
+
+    /// Removes the object with the id, or returns `None` if there is no such object.
+    pub(crate) fn remove_object(&mut self, id: ObjectId) -> Option<Object2d> {
+        let (layer_index, index) = self.find(id)?;
+        let layer = &mut self.layers[layer_index];
+        if let Some(spatial_index) = layer.index.get_mut() {
+            spatial_index.update(id, None);
+        }
+        layer.ids.remove(index);
+        layer.changed.remove(index);
+        layer.dirty = true;
+        Some(layer.objects.remove(index))
+    }
+
+    /// Returns the objects of the layer, or `None` if there is no layer with the name.
+    pub(crate) fn objects(&self, name: &str) -> Option<&Vec<Object2d>> {
+        self.layers.iter().find(|layer| layer.name == name).map(|layer| &layer.objects)
+    }
+
+    /// Sets the z-index of the layer, adding an empty layer if there is none.
+    pub(crate) fn set_z_index(&mut self, name: &str, z_index: i32) {
+        self.get_or_insert(name).z_index = z_index;
+        self.sort();
+    }
+
+    /// Removes the layer, returns false if there is no layer with the name.
+    ///
+    /// The default layer is cleared instead, so it always exists.
+    pub(crate) fn remove(&mut self, name: &str) -> bool {
+        if name == DEFAULT_LAYER {
+            self.update(name, Vec::clear);
+            return true;
+        }
+        let count = self.layers.len();
+        self.layers.retain(|layer| layer.name != name);
+        self.layers.len() != count
+    }
+
+    /// Returns the ids of the objects containing the point in world coordinates, topmost first.
+    pub(crate) fn hit_test(&self, x: f32, y: f32) -> Vec<ObjectId> {
+        let point = Bounds::from_corners((x, y), (x, y));
+        let mut hits = Vec::new();
+        for layer in self.layers.iter().rev() {
+            // Candidates are found by their bounds, then tested against their exact shape
+            let mut layer_hits = Vec::new();
+            layer.index().query(&point, |id, _| {
+                if layer.object(id).is_some_and(|object| object.contains_point(x, y)) {
+                    layer_hits.push(id);
+                }
+            });
+            // Ids of a layer ascend in drawing order
+            layer_hits.sort_unstable_by(|a, b| b.cmp(a));
+            hits.append(&mut layer_hits);
+        }
+        hits
+    }
+
+    /// Returns the ids of the objects whose bounds intersect or lie inside the area in world coordinates, topmost first.
+    pub(crate) fn query_region(&self, area: Bounds, mode: RegionMode) -> Vec<ObjectId> {
+        let mut found = Vec::new();
+        for layer in self.layers.iter().rev() {
+            let mut layer_found = Vec::new();
+            layer.index().query(&area, |id, bounds| {
+                if mode == RegionMode::Intersecting || area.contains(bounds) {
+                    layer_found.push(id);
+                }
+            });
+            layer_found.sort_unstable_by(|a, b| b.cmp(a));
+            found.append(&mut layer_found);
+        }
+        found
+    }
+
+    /// Returns the bounds covering the objects of all layers in world coordinates, or `None` if none covers an area.
+    pub(crate) fn bounds(&self) -> Option<Bounds> {
+        Bounds::of_objects(self.layers.iter().flat_map(|layer| &layer.objects))
+    }
+
+    /// Marks all layers for preparing again, e.g. after the canvas was resized.
+    #[cfg(feature = "canvas")]
+    pub(crate) fn invalidate(&mut self) {
+        for layer in &mut self.layers {
+            layer.dirty = true;
+        }
+    }
+
+    /// Rebuilds the spatial indices of all layers when they are next queried, e.g. after fonts changed the size of text.
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    pub(crate) fn reindex(&mut self) {
+        for layer in &mut self.layers {
+            layer.index = OnceCell::new();
+        }
+    }
+
+    /// Returns the layers in drawing order.
+    #[cfg(not(feature = "canvas"))]
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Layer<C>> {
+        self.layers.iter()
+    }
+
+    /// Returns the layers in drawing order, for preparing them.
+    #[cfg(not(feature = "webgpu-blade"))]
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut Layer<C>> {
+        self.layers.iter_mut()
+    }
+
+    /// Sorts the layers by z-index, keeping the order of layers with the same z-index.
+    fn sort(&mut self) {
+        self.layers.sort_by_key(|layer| layer.z_index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object2d::Rectangle;
+
+    fn square(x: f32, y: f32) -> Object2d {
+        Rectangle::new().position(x, y).size(10.0, 10.0).into()
+    }
+
+    #[test]
+    fn region_queries_return_the_topmost_objects_first() {
+        let mut layers: Layers<()> = Layers::new();
+        let below = layers.insert(DEFAULT_LAYER, square(0.0, 0.0));
+        let above = layers.insert("top", square(5.0, 5.0));
+        layers.set_z_index("top", 1);
+        let beside = layers.insert(DEFAULT_LAYER, square(20.0, 0.0));
+        let area = Bounds::new(0.0, 0.0, 30.0, 30.0);
+        assert_eq!(layers.query_region(area, RegionMode::Intersecting), vec![above, beside, below]);
+        assert_eq!(layers.hit_test(7.0, 7.0), vec![above, below]);
+        // Negative sizes span the region to the left and up
+        assert_eq!(layers.query_region(Bounds::new(12.0, 12.0, -12.0, -12.0), RegionMode::Inside), vec![below]);
+        assert_eq!(layers.query_region(Bounds::new(16.0, 16.0, -2.0, -2.0), RegionMode::Intersecting), vec![above]);
+    }
+
+    #[test]
+    fn queries_follow_object_updates_and_removals() {
+        let mut layers: Layers<()> = Layers::new();
+        let id = layers.insert(DEFAULT_LAYER, square(0.0, 0.0));
+        assert_eq!(layers.hit_test(5.0, 5.0), vec![id]);
+        layers.update_object(id, |object| *object = square(100.0, 0.0));
+        assert_eq!(layers.hit_test(5.0, 5.0), Vec::new());
+        assert_eq!(layers.hit_test(105.0, 5.0), vec![id]);
+        assert!(layers.remove_object(id).is_some());
+        assert_eq!(layers.hit_test(105.0, 5.0), Vec::new());
+        assert!(!layers.update_object(id, |_| {}));
+    }
+
+    #[test]
+    fn layer_updates_keep_the_index_with_new_ids() {
+        let mut layers: Layers<()> = Layers::new();
+        layers.update("grid", |objects| objects.extend((0..300).map(|i| square(i as f32 * 20.0, 0.0))));
+        let first = layers.hit_test(5.0, 5.0);
+        assert_eq!(first.len(), 1);
+        // The same number of objects updates the existing index
+        layers.update("grid", |objects| objects[0] = square(0.0, 100.0));
+        let layer = layers.layers.iter().find(|layer| layer.name == "grid").unwrap();
+        assert!(layer.index.get().is_some());
+        assert_eq!(layers.hit_test(5.0, 5.0), Vec::new());
+        let moved = layers.hit_test(5.0, 105.0);
+        assert_eq!(moved.len(), 1);
+        assert_ne!(moved, first);
+        assert_eq!(layers.hit_test(25.0, 5.0).len(), 1);
+        assert_eq!(layers.query_region(Bounds::new(-1.0, -1.0, 10000.0, 200.0), RegionMode::Inside).len(), 300);
+        // Another number of objects indexes the layer again on the next query
+        layers.update("grid", |objects| objects.truncate(10));
+        let layer = layers.layers.iter().find(|layer| layer.name == "grid").unwrap();
+        assert!(layer.index.get().is_none());
+        assert_eq!(layers.query_region(Bounds::new(-1.0, -1.0, 10000.0, 200.0), RegionMode::Inside).len(), 10);
+    }
+}
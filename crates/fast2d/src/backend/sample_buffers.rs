@@ -0,0 +1,59 @@
+//! The samples of sample plots uploaded to GPU storage buffers for the wgpu backends,
+//! so a plot is uploaded once however often its layer is prepared again.
+//!
+//! Buffers are keyed by the address of the shared samples and dropped with them,
+//! plots sharing the same samples share the buffer.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use wgpu::util::DeviceExt;
+
+/// A storage buffer with the samples it holds, the samples are only referenced weakly.
+struct SampleBuffer {
+    samples: Weak<[f32]>,
+    buffer: wgpu::Buffer,
+}
+
+/// The storage buffers of the samples of recently drawn plots.
+pub(crate) struct SampleBuffers {
+    entries: HashMap<usize, SampleBuffer>,
+}
+
+impl SampleBuffers {
+    /// Creates an empty cache.
+    pub(crate) fn new() -> Self {
+        Self { entries: HashMap::new() }
+    }
+
+    /// Returns the storage buffer of the samples, uploading them if they have none,
+    /// or `None` if they are larger than a storage buffer binding of the device.
+    pub(crate) fn get_or_upload(&mut self, device: &wgpu::Device, samples: &Arc<[f32]>) -> Option<wgpu::Buffer> {
+        let size = size_of_val(samples.as_ref()) as u64;
+        if size == 0 || size > device.limits().max_storage_buffer_binding_size as u64 {
+            return None;
+        }
+        let key = Arc::as_ptr(samples) as *const f32 as usize;
+        // Samples dropped since their upload may have left their address to new samples
+        if let Some(entry) = self.entries.get(&key)
+            && entry.samples.upgrade().is_some_and(|cached| Arc::ptr_eq(&cached, samples)) {
+            return Some(entry.buffer.clone());
+        }
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sample Buffer"),
+            contents: bytemuck::cast_slice(samples),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        self.entries.insert(key, SampleBuffer { samples: Arc::downgrade(samples), buffer: buffer.clone() });
+        Some(buffer)
+    }
+
+    /// Drops the buffers of samples no longer referenced by any plot.
+    pub(crate) fn evict_dropped(&mut self) {
+        self.entries.retain(|_, entry| entry.samples.strong_count() > 0);
+    }
+
+    /// Returns the bytes of all buffers.
+    pub(crate) fn size(&self) -> u64 {
+        self.entries.values().map(|entry| entry.buffer.size()).sum()
+    }
+}
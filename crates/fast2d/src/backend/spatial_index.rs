@@ -0,0 +1,214 @@
+//! A spatial index of the bounding boxes of a layer's objects, for hit testing and region queries.
+//!
+//! The boxes are packed into a static R-tree with the sort-tile-recursive algorithm. Changed objects
+//! are kept in a short list next to the tree until there are enough of them to pack the tree again,
+//! so updating single objects of a large layer stays cheap.
+
+use std::collections::HashSet;
+use crate::object2d::Bounds;
+use super::ObjectId;
+
+/// Number of children of each node of the tree.
+const NODE_SIZE: usize = 16;
+/// Number of changes always kept next to the tree before it is packed again,
+/// larger trees are packed again after changes of a quarter of their entries.
+const MIN_CHANGES: usize = 256;
+
+/// The bounding boxes of objects, found by the area they overlap.
+pub(crate) struct SpatialIndex {
+    /// Bounds of the objects in the order of the tree leaves
+    entries: Vec<(Bounds, ObjectId)>,
+    /// Bounds of the tree nodes by level, each node covers `NODE_SIZE` items of the level below
+    levels: Vec<Vec<Bounds>>,
+    /// Bounds of the objects changed since the tree was packed
+    changed: Vec<(Bounds, ObjectId)>,
+    /// Objects whose entry in the tree is outdated
+    stale: HashSet<ObjectId>,
+}
+
+impl SpatialIndex {
+    /// Packs the bounds of the objects into a new index, objects without bounds are left out.
+    pub(crate) fn new(objects: impl IntoIterator<Item = (ObjectId, Option<Bounds>)>) -> Self {
+        let entries = objects.into_iter().filter_map(|(id, bounds)| Some((bounds?, id))).collect();
+        let mut index = Self { entries: Vec::new(), levels: Vec::new(), changed: Vec::new(), stale: HashSet::new() };
+        index.pack(entries);
+        index
+    }
+
+    /// Sets the bounds of the object, `None` removes it from the index.
+    pub(crate) fn update(&mut self, id: ObjectId, bounds: Option<Bounds>) {
+        self.stale.insert(id);
+        self.changed.retain(|(_, changed_id)| *changed_id != id);
+        if let Some(bounds) = bounds {
+            self.changed.push((bounds, id));
+        }
+        if self.changed.len() + self.stale.len() > MIN_CHANGES.max(self.entries.len() / 4) {
+            let mut entries = std::mem::take(&mut self.entries);
+            entries.retain(|(_, id)| !self.stale.contains(id));
+            entries.append(&mut self.changed);
+            self.stale.clear();
+            self.pack(entries);
+        }
+    }
+
+    /// Gives the objects new ids, keeping their bounds.
+    pub(crate) fn renumber(&mut self, new_id: impl Fn(ObjectId) -> ObjectId) {
+        for (_, id) in self.entries.iter_mut().chain(self.changed.iter_mut()) {
+            *id = new_id(*id);
+        }
+        self.stale = self.stale.drain().map(&new_id).collect();
+    }
+
+    /// Returns the current bounds of each object, objects without bounds are left out.
+    pub(crate) fn bounds(&self) -> impl Iterator<Item = (ObjectId, &Bounds)> {
+        self.entries.iter()
+            .filter(|(_, id)| !self.stale.contains(id))
+            .chain(&self.changed)
+            .map(|(bounds, id)| (*id, bounds))
+    }
+
+    /// Calls `visit` with the objects whose bounds intersect or touch the area.
+    pub(crate) fn query(&self, area: &Bounds, mut visit: impl FnMut(ObjectId, &Bounds)) {
+        if let Some(top) = self.levels.last() {
+            for node in 0..top.len() {
+                self.query_node(self.levels.len() - 1, node, area, &mut visit);
+            }
+        }
+        for (bounds, id) in &self.changed {
+            if bounds.intersects(area) {
+                visit(*id, bounds);
+            }
+        }
+    }
+
+    /// Visits the entries below the node that intersect the area.
+    fn query_node(&self, level: usize, node: usize, area: &Bounds, visit: &mut impl FnMut(ObjectId, &Bounds)) {
+        if !self.levels[level][node].intersects(area) {
+            return;
+        }
+        let children = node * NODE_SIZE..((node + 1) * NODE_SIZE);
+        if level == 0 {
+            for (bounds, id) in &self.entries[children.start..children.end.min(self.entries.len())] {
+                if bounds.intersects(area) && !self.stale.contains(id) {
+                    visit(*id, bounds);
+                }
+            }
+        } else {
+            for child in children.start..children.end.min(self.levels[level - 1].len()) {
+                self.query_node(level - 1, child, area, visit);
+            }
+        }
+    }
+
+    /// Builds the tree from the entries.
+    fn pack(&mut self, mut entries: Vec<(Bounds, ObjectId)>) {
+        // Sort-tile-recursive: vertical slices of the leaves sorted by x, each sorted by y,
+        // so every leaf covers a compact tile of the scene
+        let leaf_count = entries.len().div_ceil(NODE_SIZE);
+        let slice_count = (leaf_count as f64).sqrt().ceil().max(1.0) as usize;
+        let center_x = |bounds: &Bounds| bounds.left + bounds.right;
+        let center_y = |bounds: &Bounds| bounds.top + bounds.bottom;
+        entries.sort_by(|(a, _), (b, _)| center_x(a).total_cmp(&center_x(b)));
+        for slice in entries.chunks_mut(slice_count * NODE_SIZE) {
+            slice.sort_by(|(a, _), (b, _)| center_y(a).total_cmp(&center_y(b)));
+        }
+
+        // Helper function to get the bounds covering all the bounds of a node
+        fn union(bounds: impl Iterator<Item = Bounds>) -> Bounds {
+            bounds.reduce(|union, bounds| union.union(&bounds)).unwrap()
+        }
+        self.levels.clear();
+        if !entries.is_empty() {
+            let mut level: Vec<Bounds> = entries.chunks(NODE_SIZE).map(|leaf| union(leaf.iter().map(|(bounds, _)| *bounds))).collect();
+            while level.len() > 1 {
+                let parents = level.chunks(NODE_SIZE).map(|node| union(node.iter().copied())).collect();
+                self.levels.push(level);
+                level = parents;
+            }
+            self.levels.push(level);
+        }
+        self.entries = entries;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A grid of unit squares, ten per row with gaps of one unit, with ids in row order.
+    fn grid(count: u64) -> Vec<(ObjectId, Option<Bounds>)> {
+        (0..count).map(|id| {
+            let (column, row) = ((id % 10) as f32, (id / 10) as f32);
+            (ObjectId(id), Some(Bounds::new(column * 2.0, row * 2.0, 1.0, 1.0)))
+        }).collect()
+    }
+
+    fn found(index: &SpatialIndex, area: Bounds) -> Vec<u64> {
+        let mut ids = Vec::new();
+        index.query(&area, |id, _| ids.push(id.0));
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn query_finds_intersecting_and_touching_bounds() {
+        let index = SpatialIndex::new(grid(1000));
+        assert_eq!(found(&index, Bounds::new(0.5, 0.5, 2.0, 0.0)), vec![0, 1]);
+        assert_eq!(found(&index, Bounds::new(1.0, 0.0, 1.0, 1.0)), vec![0, 1]);
+        assert_eq!(found(&index, Bounds::new(1.2, 1.2, 0.5, 0.5)), Vec::<u64>::new());
+        assert_eq!(found(&index, Bounds::new(-100.0, -100.0, 1000.0, 1000.0)).len(), 1000);
+    }
+
+    #[test]
+    fn query_areas_with_negative_sizes_are_normalized() {
+        let index = SpatialIndex::new(grid(100));
+        assert_eq!(found(&index, Bounds::new(3.5, 2.5, -3.0, -2.0)), vec![0, 1, 10, 11]);
+    }
+
+    #[test]
+    fn objects_without_bounds_are_left_out() {
+        let index = SpatialIndex::new([(ObjectId(0), None), (ObjectId(1), Some(Bounds::new(0.0, 0.0, 1.0, 1.0)))]);
+        assert_eq!(found(&index, Bounds::new(-1.0, -1.0, 3.0, 3.0)), vec![1]);
+        assert_eq!(found(&SpatialIndex::new([]), Bounds::new(-1.0, -1.0, 3.0, 3.0)), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn updates_move_and_remove_objects() {
+        let mut index = SpatialIndex::new(grid(1000));
+        index.update(ObjectId(0), Some(Bounds::new(100.0, 100.0, 1.0, 1.0)));
+        index.update(ObjectId(1), None);
+        index.update(ObjectId(1000), Some(Bounds::new(0.0, 0.0, 1.0, 1.0)));
+        assert_eq!(found(&index, Bounds::new(0.0, 0.0, 3.0, 0.5)), vec![1000]);
+        assert_eq!(found(&index, Bounds::new(100.5, 100.5, 0.0, 0.0)), vec![0]);
+        // Updating a changed object again replaces its changed bounds
+        index.update(ObjectId(0), Some(Bounds::new(200.0, 200.0, 1.0, 1.0)));
+        assert_eq!(found(&index, Bounds::new(100.5, 100.5, 0.0, 0.0)), Vec::<u64>::new());
+        assert_eq!(found(&index, Bounds::new(200.5, 200.5, 0.0, 0.0)), vec![0]);
+    }
+
+    #[test]
+    fn many_updates_pack_the_tree_again() {
+        let mut index = SpatialIndex::new(grid(100));
+        let corner = Bounds::new(-10.0, -10.0, 1.0, 1.0);
+        // Each update of another object adds a changed and a stale entry
+        for id in 0..(MIN_CHANGES / 2) as u64 {
+            index.update(ObjectId(id), Some(corner));
+        }
+        assert_eq!(index.changed.len(), MIN_CHANGES / 2);
+        index.update(ObjectId((MIN_CHANGES / 2) as u64), Some(corner));
+        assert!(index.changed.is_empty() && index.stale.is_empty());
+        assert_eq!(found(&index, Bounds::new(-9.5, -9.5, 0.0, 0.0)).len(), MIN_CHANGES / 2 + 1);
+        assert_eq!(index.bounds().count(), MIN_CHANGES / 2 + 1);
+    }
+
+    #[test]
+    fn renumber_keeps_the_bounds() {
+        let mut index = SpatialIndex::new(grid(20));
+        index.update(ObjectId(3), Some(Bounds::new(-10.0, -10.0, 1.0, 1.0)));
+        index.update(ObjectId(4), None);
+        index.renumber(|id| ObjectId(id.0 + 100));
+        assert_eq!(found(&index, Bounds::new(-9.5, -9.5, 0.0, 0.0)), vec![103]);
+        assert_eq!(found(&index, Bounds::new(0.0, 0.0, 10.0, 0.5)), vec![100, 101, 102, 105]);
+        assert_eq!(index.bounds().count(), 19);
+    }
+}
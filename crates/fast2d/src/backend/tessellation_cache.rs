@@ -0,0 +1,153 @@
+//! A cache of tessellated shapes for the wgpu backends, so static shapes are not tessellated again every time
+//! their layer is prepared, e.g. a grid or backgrounds in a layer that is rebuilt for every frame.
+//!
+//! Shapes are cached before their transform and without colors, keyed by a hash of their geometry and
+//! the tessellation tolerance, so moved and recolored shapes reuse their triangles too.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use lyon::path::Path;
+use lyon::tessellation::{
+    BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator, StrokeVertex,
+    TessellationError, VertexBuffers,
+};
+
+/// Fraction of the budget the largest cached shape may use, larger shapes are always tessellated.
+const MAX_ENTRY_FRACTION: usize = 8;
+
+/// Untransformed vertex positions and indices of a tessellated shape.
+pub(crate) type Geometry = VertexBuffers<[f32; 2], u32>;
+
+/// A cached shape with the frame it was last used in.
+struct CacheEntry {
+    geometry: Geometry,
+    last_used: u64,
+}
+
+/// The tessellators with the shapes they tessellated recently.
+pub(crate) struct TessellationCache {
+    fill_tessellator: FillTessellator,
+    stroke_tessellator: StrokeTessellator,
+    entries: HashMap<u64, CacheEntry>,
+    /// Bytes of the vertices and indices of all entries
+    size: usize,
+    /// Bytes kept in the cache, least recently used shapes are evicted beyond it
+    budget: usize,
+    /// The current frame, counted by `next_frame`
+    frame: u64,
+    /// Geometry of the last shape that was not cached
+    scratch: Geometry,
+}
+
+impl TessellationCache {
+    /// Creates an empty cache keeping up to `budget` bytes of shapes.
+    pub(crate) fn new(budget: usize) -> Self {
+        Self {
+            fill_tessellator: FillTessellator::new(),
+            stroke_tessellator: StrokeTessellator::new(),
+            entries: HashMap::new(),
+            size: 0,
+            budget,
+            frame: 0,
+            scratch: Geometry::new(),
+        }
+    }
+
+    /// Returns the key of a shape from its kind and the values defining its geometry, including the tolerance.
+    pub(crate) fn key(kind: &str, values: impl IntoIterator<Item = f32>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        kind.hash(&mut hasher);
+        for value in values {
+            value.to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Sets the bytes kept in the cache, evicting the least recently used shapes beyond it.
+    pub(crate) fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        if self.size > budget {
+            Self::evict(&mut self.entries, &mut self.size, budget);
+        }
+    }
+
+    /// Starts a new frame, shapes not used in the recent frames are evicted first.
+    pub(crate) fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Returns the fill of the path built by `path`, cached with the key if there is one.
+    ///
+    /// The path is only built if the shape is not cached.
+    pub(crate) fn fill(&mut self, key: Option<u64>, path: impl FnOnce() -> Path, options: &FillOptions) -> Result<&Geometry, TessellationError> {
+        let tessellator = &mut self.fill_tessellator;
+        Self::get_or_insert(&mut self.entries, &mut self.size, self.budget, &mut self.scratch, self.frame, key, |geometry| {
+            tessellator.tessellate_path(&path(), options, &mut BuffersBuilder::new(geometry, |vertex: FillVertex| {
+                vertex.position().to_array()
+            })).map(|_| ())
+        })
+    }
+
+    /// Returns the stroke of the path built by `path`, cached with the key if there is one.
+    ///
+    /// The path is only built if the shape is not cached.
+    pub(crate) fn stroke(&mut self, key: Option<u64>, path: impl FnOnce() -> Path, options: &StrokeOptions) -> Result<&Geometry, TessellationError> {
+        let tessellator = &mut self.stroke_tessellator;
+        Self::get_or_insert(&mut self.entries, &mut self.size, self.budget, &mut self.scratch, self.frame, key, |geometry| {
+            tessellator.tessellate_path(&path(), options, &mut BuffersBuilder::new(geometry, |vertex: StrokeVertex| {
+                vertex.position().to_array()
+            })).map(|_| ())
+        })
+    }
+
+    /// Returns the cached geometry with the key, or tessellates it and caches it if it's small enough.
+    fn get_or_insert<'a>(
+        entries: &'a mut HashMap<u64, CacheEntry>,
+        size: &mut usize,
+        budget: usize,
+        scratch: &'a mut Geometry,
+        frame: u64,
+        key: Option<u64>,
+        tessellate: impl FnOnce(&mut Geometry) -> Result<(), TessellationError>,
+    ) -> Result<&'a Geometry, TessellationError> {
+        if let Some(key) = key && entries.contains_key(&key) {
+            let entry = entries.get_mut(&key).unwrap();
+            entry.last_used = frame;
+            return Ok(&entry.geometry);
+        }
+        scratch.vertices.clear();
+        scratch.indices.clear();
+        tessellate(scratch)?;
+        let entry_size = geometry_size(scratch);
+        let Some(key) = key.filter(|_| entry_size <= budget / MAX_ENTRY_FRACTION) else {
+            return Ok(scratch);
+        };
+        if *size + entry_size > budget {
+            // Evict down to three quarters of the budget, so eviction is rare
+            Self::evict(entries, size, (budget * 3 / 4).saturating_sub(entry_size));
+        }
+        *size += entry_size;
+        let geometry = std::mem::replace(scratch, Geometry::new());
+        let entry = entries.entry(key).or_insert(CacheEntry { geometry, last_used: frame });
+        Ok(&entry.geometry)
+    }
+
+    /// Evicts the least recently used shapes until the entries fit in `limit` bytes.
+    fn evict(entries: &mut HashMap<u64, CacheEntry>, size: &mut usize, limit: usize) {
+        let mut by_age: Vec<(u64, u64)> = entries.iter().map(|(key, entry)| (entry.last_used, *key)).collect();
+        by_age.sort_unstable();
+        for (_, old_key) in by_age {
+            if *size <= limit {
+                break;
+            }
+            if let Some(old) = entries.remove(&old_key) {
+                *size -= geometry_size(&old.geometry);
+            }
+        }
+    }
+}
+
+/// Returns the bytes of the vertices and indices of the geometry.
+fn geometry_size(geometry: &Geometry) -> usize {
+    size_of_val(geometry.vertices.as_slice()) + size_of_val(geometry.indices.as_slice())
+}
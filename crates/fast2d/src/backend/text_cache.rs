@@ -0,0 +1,101 @@
+//! A cache of shaped text for the wgpu backends, so text that doesn't change is not shaped again
+//! every time its layer is prepared, e.g. labels in a layer that is rebuilt for every frame.
+//!
+//! Buffers are keyed by the text content, font attributes, size and bounds. All of them are dropped
+//! when fonts are registered or removed, because shaping depends on the available faces.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use glyphon::Buffer as GlyphonBuffer;
+use crate::backend::{with_icon, FONT_GENERATION};
+use crate::object2d::{Text, icon_id};
+
+/// Number of shaped texts kept in the cache, least recently used texts are evicted beyond it.
+const MAX_CACHED: usize = 4096;
+
+/// A shaped text with the frame it was last used in.
+struct CacheEntry {
+    buffer: Arc<GlyphonBuffer>,
+    last_used: u64,
+}
+
+/// Text shaped recently, with the font generation it was shaped with.
+pub(crate) struct TextCache {
+    entries: HashMap<u64, CacheEntry>,
+    /// The value of `FONT_GENERATION` when the entries were shaped
+    generation: u64,
+    /// The current frame, counted by `next_frame`
+    frame: u64,
+}
+
+impl TextCache {
+    /// Creates an empty cache.
+    pub(crate) fn new() -> Self {
+        Self { entries: HashMap::new(), generation: FONT_GENERATION.load(Ordering::Relaxed), frame: 0 }
+    }
+
+    /// Starts a new frame, texts not used in the recent frames are evicted first.
+    ///
+    /// Drops all shaped text if fonts were registered or removed since the previous frame.
+    ///
+    /// # Returns
+    /// `true` if the fonts changed.
+    pub(crate) fn next_frame(&mut self) -> bool {
+        self.frame += 1;
+        let generation = FONT_GENERATION.load(Ordering::Relaxed);
+        if generation == self.generation {
+            return false;
+        }
+        self.generation = generation;
+        self.entries.clear();
+        true
+    }
+
+    /// Returns the font generation of the cached text, text prepared with another generation is outdated.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the cached buffer of the text, or shapes it with `shape` and caches it.
+    pub(crate) fn get_or_shape(&mut self, text: &Text, shape: impl FnOnce() -> GlyphonBuffer) -> Arc<GlyphonBuffer> {
+        let key = Self::key(text);
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_used = self.frame;
+            return entry.buffer.clone();
+        }
+        if self.entries.len() >= MAX_CACHED {
+            // Evict the least recently used texts down to three quarters of the limit, so eviction is rare
+            let mut by_age: Vec<(u64, u64)> = self.entries.iter().map(|(key, entry)| (entry.last_used, *key)).collect();
+            by_age.sort_unstable();
+            let excess = self.entries.len() - MAX_CACHED * 3 / 4;
+            for (_, old_key) in by_age.into_iter().take(excess) {
+                self.entries.remove(&old_key);
+            }
+        }
+        let buffer = Arc::new(shape());
+        self.entries.insert(key, CacheEntry { buffer: buffer.clone(), last_used: self.frame });
+        buffer
+    }
+
+    /// Returns the key of the text from everything its shaping depends on.
+    ///
+    /// The position and transform are left out, they are applied when the text is drawn.
+    fn key(text: &Text) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        text.text.hash(&mut hasher);
+        text.families.hash(&mut hasher);
+        text.weight.value().hash(&mut hasher);
+        text.italic.hash(&mut hasher);
+        text.color.to_glyphon_color().0.hash(&mut hasher);
+        for value in [text.font_size, text.line_height_multiplier, text.width, text.height] {
+            value.to_bits().hash(&mut hasher);
+        }
+        // Icons are as wide as their aspect ratio, so text is shaped again when they are registered
+        for id in text.text.chars().filter_map(icon_id) {
+            with_icon(id, |icon| icon.revision).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
@@ -0,0 +1,329 @@
+//! Text shaping shared by the glyphon backends.
+//!
+//! Converts Fast2D [`Text`] objects into shaped glyphon buffers, including
+//! per-character resolution of the font family fallback chain.
+//!
+//! Inline icons reserve their width with an em space widened by letter spacing to the icon width,
+//! measured in the face of the run, and are drawn as glyphon custom glyphs at the reserved positions.
+//!
+//! Emoji are shaped with a registered color font (COLR, CBDT or sbix tables), which glyphon
+//! rasterizes into its color atlas. SVG glyphs are not supported by the rasterizer,
+//! fonts with only SVG color glyphs render their monochrome outlines.
+
+use std::ops::Range;
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use glyphon::{Shaping, Buffer as GlyphonBuffer, Attrs, Metrics, Family as GlyphonFamily, FontSystem};
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+use glyphon::CustomGlyph;
+use glyphon::fontdb::{ID as FontId, Query};
+use unicode_properties::{EmojiStatus, UnicodeEmoji};
+use crate::backend::{with_icon, FONT_GENERATION, FONT_SYSTEM};
+use crate::object2d::{Family, FontWeight, Text, icon_id};
+
+/// Marks glyphs reserving space for an icon, the icon id is stored in the lower 16 bits of the glyph metadata.
+const ICON_METADATA: usize = 1 << 16;
+/// Placeholder shaped in place of icons, an em space, which is one em wide in most fonts.
+const ICON_PLACEHOLDER: &str = "\u{2003}";
+
+/// Creates a glyphon buffer with the text laid out and shaped, ready to be rendered.
+pub(crate) fn shape_text(font_system: &mut FontSystem, text: &Text) -> GlyphonBuffer {
+    // Set up text metrics and buffer
+    let line_height_pixels = text.font_size * text.line_height_multiplier;
+    let mut buffer = GlyphonBuffer::new(font_system, Metrics::new(text.font_size, line_height_pixels));
+    buffer.set_size(font_system, Some(text.width), Some(text.height));
+
+    let attrs = text_attrs(text, &text.families[0]);
+
+    // Find the best matching face for every family in the chain, warn about missing ones
+    let face_ids: Vec<Option<FontId>> = text.families.iter().map(|family| {
+        let face_id = font_system.db().query(&Query {
+            families: &[glyphon_family(family)],
+            weight: attrs.weight,
+            style: attrs.style,
+            ..Default::default()
+        });
+        if face_id.is_none() {
+            warn(&format!("Warning: Font family '{:?}' not found. Falling back to default.", family));
+        }
+        face_id
+    }).collect();
+
+    // Emoji prefer a color font, even when a text font has monochrome glyphs for them
+    let color_face_id = if text.text.chars().any(|character| is_emoji_presentation(character) || character == EMOJI_VARIATION_SELECTOR) {
+        color_face(font_system, &face_ids)
+    } else {
+        None
+    };
+
+    let has_icons = text.text.chars().any(|character| icon_id(character).is_some());
+    if text.families.len() == 1 && color_face_id.is_none() && !has_icons {
+        buffer.set_text(font_system, &text.text, &attrs, Shaping::Advanced);
+        return buffer;
+    }
+
+    // Append the color font to the chain when it was found outside of it
+    let mut families = text.families.clone();
+    let mut face_ids = face_ids;
+    let emoji_index = color_face_id.map(|color_face_id| {
+        face_ids.iter().position(|face_id| *face_id == Some(color_face_id)).unwrap_or_else(|| {
+            let family_name = font_system.db().face(color_face_id)
+                .and_then(|face| face.families.first())
+                .map(|(name, _)| name.clone())
+                .unwrap_or_default();
+            families.push(Family::name(family_name));
+            face_ids.push(Some(color_face_id));
+            face_ids.len() - 1
+        })
+    });
+
+    // Split the text into runs by the first family that contains each character
+    let runs = fallback_runs(font_system, &text.text, &face_ids, emoji_index);
+    let mut spans: Vec<(&str, Attrs)> = Vec::new();
+    for (range, family_index) in runs {
+        let placeholder_width = placeholder_width(font_system, face_ids[family_index]);
+        push_spans(&mut spans, &text.text, range, text_attrs(text, &families[family_index]), placeholder_width);
+    }
+    buffer.set_rich_text(font_system, spans, &attrs, Shaping::Advanced, None);
+    buffer
+}
+
+/// Returns the width and height of the text laid out into lines, limited by its size.
+pub(crate) fn text_size(text: &Text) -> (f32, f32) {
+    // Without registered fonts no text is drawn
+    let Some(font_system_mutex) = FONT_SYSTEM.get() else {
+        return (0.0, 0.0);
+    };
+    let mut font_system = font_system_mutex.lock().expect("Failed to lock FontSystem Mutex");
+    let buffer = shape_text(&mut font_system, text);
+    let (width, height) = buffer.layout_runs().fold((0.0f32, 0.0f32), |(width, height), run| {
+        (width.max(run.line_w), height.max(run.line_top + run.line_height))
+    });
+    (width.min(text.width), height.min(text.height))
+}
+
+/// Logs a warning to the standard error output or the browser console.
+fn warn(message: &str) {
+    #[cfg(feature = "native")]
+    eprintln!("{message}");
+    #[cfg(not(feature = "native"))]
+    web_sys::console::warn_1(&message.into());
+}
+
+/// Returns custom glyphs drawing the icons at the positions reserved in the shaped buffer.
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+pub(crate) fn icon_glyphs(buffer: &GlyphonBuffer, text: &Text) -> Vec<CustomGlyph> {
+    let mut glyphs = Vec::new();
+    for run in buffer.layout_runs() {
+        for glyph in run.glyphs.iter().filter(|glyph| glyph.metadata & ICON_METADATA != 0) {
+            let Some(glyph_id) = with_icon(glyph.metadata as u16, |icon| icon.glyph_id) else {
+                continue;
+            };
+            glyphs.push(CustomGlyph {
+                id: glyph_id,
+                left: glyph.x,
+                top: run.line_top + (run.line_height - glyph.font_size) / 2.0,
+                width: glyph.w,
+                height: glyph.font_size,
+                // Tints path icons, bitmap icons keep their colors
+                color: Some(text.color.to_glyphon_color()),
+                snap_to_physical_pixel: true,
+                metadata: 0,
+            });
+        }
+    }
+    glyphs
+}
+
+/// Pushes spans for the text range, replacing icon characters with placeholders as wide as the icons.
+///
+/// `placeholder_width` is the width of the placeholder in the face of the range in ems.
+fn push_spans<'a>(
+    spans: &mut Vec<(&'a str, Attrs<'a>)>,
+    text: &'a str,
+    range: Range<usize>,
+    attrs: Attrs<'a>,
+    placeholder_width: f32,
+) {
+    let mut start = range.start;
+    for (offset, character) in text[range.clone()].char_indices() {
+        let Some(id) = icon_id(character) else {
+            continue;
+        };
+        let icon_start = range.start + offset;
+        if start < icon_start {
+            spans.push((&text[start..icon_start], attrs.clone()));
+        }
+        // Unregistered icons keep a square space, so registering them later doesn't move the text
+        let aspect_ratio = with_icon(id, |icon| icon.icon.aspect_ratio()).unwrap_or(1.0);
+        let icon_attrs = attrs.clone().metadata(ICON_METADATA | id as usize).letter_spacing(aspect_ratio - placeholder_width);
+        spans.push((ICON_PLACEHOLDER, icon_attrs));
+        start = icon_start + character.len_utf8();
+    }
+    if start < range.end {
+        spans.push((&text[start..range.end], attrs));
+    }
+}
+
+/// Returns the advance of the icon placeholder in the face in ems,
+/// or 1 em if the face is missing or has no glyph for it.
+fn placeholder_width(font_system: &mut FontSystem, face_id: Option<FontId>) -> f32 {
+    face_id.and_then(|face_id| font_system.get_font(face_id)).and_then(|font| {
+        let font = font.as_swash();
+        let placeholder = ICON_PLACEHOLDER.chars().next()?;
+        let glyph_id = font.charmap().map(placeholder);
+        let advance = font.glyph_metrics(&[]).scale(1.0).advance_width(glyph_id);
+        (glyph_id != 0 && advance > 0.0).then_some(advance)
+    }).unwrap_or(1.0)
+}
+
+/// Creates glyphon attributes (family, color, weight, style) for the text.
+fn text_attrs<'a>(text: &Text, family: &'a Family) -> Attrs<'a> {
+    Attrs::new()
+        .family(glyphon_family(family))
+        .color(text.color.to_glyphon_color())
+        .weight(glyphon_weight(text.weight))
+        .style(if text.italic { glyphon::fontdb::Style::Italic } else { glyphon::fontdb::Style::Normal })
+}
+
+/// Converts a font family to the glyphon format.
+pub(crate) fn glyphon_family(family: &Family) -> GlyphonFamily<'_> {
+    match family {
+        Family::Name(name) => GlyphonFamily::Name(name.as_ref()),
+        Family::SansSerif => GlyphonFamily::SansSerif,
+        Family::Serif => GlyphonFamily::Serif,
+        Family::Monospace => GlyphonFamily::Monospace,
+        Family::Cursive => GlyphonFamily::Cursive,
+        Family::Fantasy => GlyphonFamily::Fantasy,
+    }
+}
+
+/// Converts a font weight to the glyphon format.
+fn glyphon_weight(weight: FontWeight) -> glyphon::fontdb::Weight {
+    use FontWeight::*;
+    match weight {
+        Thin => glyphon::fontdb::Weight::THIN,
+        ExtraLight => glyphon::fontdb::Weight::EXTRA_LIGHT,
+        Light => glyphon::fontdb::Weight::LIGHT,
+        Regular => glyphon::fontdb::Weight::NORMAL,
+        Medium => glyphon::fontdb::Weight::MEDIUM,
+        SemiBold => glyphon::fontdb::Weight::SEMIBOLD,
+        Bold => glyphon::fontdb::Weight::BOLD,
+        ExtraBold => glyphon::fontdb::Weight::EXTRA_BOLD,
+        Black => glyphon::fontdb::Weight::BLACK,
+    }
+}
+
+/// Splits the text into byte ranges, each paired with the index of the first family
+/// in the fallback chain whose face contains the range's characters.
+///
+/// Emoji go to the family at `emoji_index` when its face contains them.
+/// Whitespace, joiners and characters not found in any family stay in the current run,
+/// so clusters are not broken apart and glyphon's own fallback handles unknown glyphs.
+fn fallback_runs(
+    font_system: &mut FontSystem,
+    text: &str,
+    face_ids: &[Option<FontId>],
+    emoji_index: Option<usize>,
+) -> Vec<(Range<usize>, usize)> {
+    let mut runs: Vec<(Range<usize>, usize)> = Vec::new();
+    let mut utf8_buffer = [0u8; 4];
+    let mut characters = text.char_indices().peekable();
+    while let Some((start, character)) = characters.next() {
+        let end = start + character.len_utf8();
+        let family_index = if character.is_whitespace() || continues_cluster(character) {
+            None
+        } else {
+            let character_str = character.encode_utf8(&mut utf8_buffer);
+            // A variation selector 16 requests the emoji presentation of the previous character
+            let is_emoji = is_emoji_presentation(character)
+                || characters.peek().is_some_and(|(_, next)| *next == EMOJI_VARIATION_SELECTOR);
+            emoji_index
+                .filter(|index| is_emoji && face_contains(font_system, face_ids[*index], character_str))
+                .or_else(|| face_ids.iter().position(|face_id| face_contains(font_system, *face_id, character_str)))
+        };
+        match (runs.last_mut(), family_index) {
+            (Some((range, current_index)), Some(index)) if *current_index == index => range.end = end,
+            (Some((range, _)), None) => range.end = end,
+            (_, index) => runs.push((start..end, index.unwrap_or(0))),
+        }
+    }
+    runs
+}
+
+/// Returns true if the face exists and contains a glyph for the single character.
+fn face_contains(font_system: &mut FontSystem, face_id: Option<FontId>, character: &str) -> bool {
+    face_id.is_some_and(|id| font_system.get_font_supported_codepoints_in_word(id, character) == Some(1))
+}
+
+/// Finds a color font for emoji, preferring faces of the family chain.
+///
+/// Outside of the chain only families named like emoji fonts are considered
+/// (e.g. "Noto Color Emoji", "Twemoji Mozilla"), so not every registered font has to be loaded.
+fn color_face(font_system: &mut FontSystem, face_ids: &[Option<FontId>]) -> Option<FontId> {
+    face_ids.iter()
+        .flatten()
+        .copied()
+        .find(|face_id| is_color_face(font_system, *face_id))
+        .or_else(|| emoji_face(font_system))
+}
+
+/// The color face of a family named like an emoji font, with the font generation it was found in.
+static EMOJI_FACE: Mutex<Option<(u64, Option<FontId>)>> = Mutex::new(None);
+
+/// Returns the first color face of a family named like an emoji font,
+/// looked up once after fonts are registered or removed.
+fn emoji_face(font_system: &mut FontSystem) -> Option<FontId> {
+    let generation = FONT_GENERATION.load(Ordering::Relaxed);
+    let mut emoji_face = EMOJI_FACE.lock().expect("Failed to lock the emoji face");
+    if let Some((face_generation, face_id)) = *emoji_face && face_generation == generation {
+        return face_id;
+    }
+    let emoji_face_ids: Vec<FontId> = font_system.db().faces()
+        .filter(|face| face.families.iter().any(|(name, _)| name.to_lowercase().contains("emoji")))
+        .map(|face| face.id)
+        .collect();
+    let face_id = emoji_face_ids.into_iter().find(|face_id| is_color_face(font_system, *face_id));
+    *emoji_face = Some((generation, face_id));
+    face_id
+}
+
+/// Returns true if the face has color glyph tables the rasterizer can render.
+fn is_color_face(font_system: &mut FontSystem, face_id: FontId) -> bool {
+    font_system.get_font(face_id).is_some_and(|font| {
+        let font = font.as_swash();
+        COLOR_GLYPH_TABLES.iter().any(|tag| font.table(u32::from_be_bytes(*tag)).is_some())
+    })
+}
+
+/// OpenType tables with color glyphs: COLR layers, CBDT color bitmaps and Apple sbix bitmaps.
+const COLOR_GLYPH_TABLES: [[u8; 4]; 3] = [*b"COLR", *b"CBDT", *b"sbix"];
+
+/// Variation selector requesting the emoji presentation of the previous character.
+const EMOJI_VARIATION_SELECTOR: char = '\u{FE0F}';
+
+/// Returns true for characters displayed as emoji by default (Unicode `Emoji_Presentation`),
+/// including regional indicators used for flags and skin tone modifiers.
+fn is_emoji_presentation(character: char) -> bool {
+    matches!(character.emoji_status(),
+        EmojiStatus::EmojiPresentation
+        | EmojiStatus::EmojiPresentationAndModifierBase
+        | EmojiStatus::EmojiPresentationAndEmojiComponent
+        | EmojiStatus::EmojiPresentationAndModifierAndEmojiComponent
+    )
+}
+
+/// Returns true for characters that modify the previous character
+/// (combining marks, variation selectors, zero-width joiners, emoji modifiers and tags).
+fn continues_cluster(character: char) -> bool {
+    matches!(character,
+        '\u{0300}'..='\u{036F}'
+        | '\u{200C}'..='\u{200D}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0020}'..='\u{E007F}'
+        | '\u{E0100}'..='\u{E01EF}'
+    )
+}
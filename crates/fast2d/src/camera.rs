@@ -0,0 +1,193 @@
+use crate::{Bounds, Transform};
+
+/// A view of the scene panned and zoomed separately along the x and y axes, e.g. to zoom a waveform in time only.
+///
+/// Objects are positioned in world coordinates and the camera maps them to screen pixels,
+/// a point `(x, y)` is drawn at `((x - offset_x) * scale_x, (y - offset_y) * scale_y)`.
+/// The offset is the world point shown at the top-left corner of the canvas.
+/// The scales are always finite and positive, so screen points can always be mapped back to the world.
+///
+/// Line widths, borders and text are zoomed with the scene. With different scales along the axes
+/// they are stretched too, e.g. text and vertical lines become wider in a view zoomed in time only.
+/// Keep such labels in a layer drawn at a uniform scale, or scale their font size and widths
+/// by the inverse of the zoom.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub(crate) offset_x: f32,
+    pub(crate) offset_y: f32,
+    pub(crate) scale_x: f32,
+    pub(crate) scale_y: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Camera {
+    /// The camera showing world coordinates as screen pixels.
+    pub const IDENTITY: Self = Self { offset_x: 0.0, offset_y: 0.0, scale_x: 1.0, scale_y: 1.0 };
+
+    /// Creates a camera showing the world point `(offset_x, offset_y)` at the top-left corner, zoomed by the scales.
+    ///
+    /// Scales must be finite and positive, other scales are replaced by 1.0.
+    pub fn new(offset_x: f32, offset_y: f32, scale_x: f32, scale_y: f32) -> Self {
+        Self {
+            offset_x,
+            offset_y,
+            scale_x: valid_scale(scale_x, 1.0),
+            scale_y: valid_scale(scale_y, 1.0),
+        }
+    }
+
+    /// Returns the world point shown at the top-left corner.
+    pub fn offset(&self) -> (f32, f32) {
+        (self.offset_x, self.offset_y)
+    }
+
+    /// Returns the zoom along the x and y axes.
+    pub fn scale(&self) -> (f32, f32) {
+        (self.scale_x, self.scale_y)
+    }
+
+    /// Sets the world point shown at the top-left corner.
+    pub fn set_offset(&mut self, offset_x: f32, offset_y: f32) {
+        self.offset_x = offset_x;
+        self.offset_y = offset_y;
+    }
+
+    /// Sets the zoom along the x and y axes, a scale that isn't finite and positive keeps the current one.
+    pub fn set_scale(&mut self, scale_x: f32, scale_y: f32) {
+        self.scale_x = valid_scale(scale_x, self.scale_x);
+        self.scale_y = valid_scale(scale_y, self.scale_y);
+    }
+
+    /// Maps the world point `(x, y)` to screen pixels.
+    pub fn world_to_screen(&self, x: f32, y: f32) -> (f32, f32) {
+        ((x - self.offset_x) * self.scale_x, (y - self.offset_y) * self.scale_y)
+    }
+
+    /// Maps the screen point `(x, y)` in pixels to world coordinates, e.g. to find the object under the mouse.
+    pub fn screen_to_world(&self, x: f32, y: f32) -> (f32, f32) {
+        (x / self.scale_x + self.offset_x, y / self.scale_y + self.offset_y)
+    }
+
+    /// Moves the view so the scene follows a drag by `dx` and `dy` screen pixels.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.offset_x -= dx / self.scale_x;
+        self.offset_y -= dy / self.scale_y;
+    }
+
+    /// Zooms by the factors while keeping the world point under the screen point `(x, y)` in place, e.g. the mouse on wheel events.
+    ///
+    /// Factors above 1.0 zoom in, use 1.0 to keep an axis unchanged.
+    /// Non-positive factors and factors zooming beyond the range of `f32` are ignored.
+    pub fn zoom_at(&mut self, factor_x: f32, factor_y: f32, x: f32, y: f32) {
+        let (world_x, world_y) = self.screen_to_world(x, y);
+        if factor_x > 0.0 {
+            self.scale_x = valid_scale(self.scale_x * factor_x, self.scale_x);
+        }
+        if factor_y > 0.0 {
+            self.scale_y = valid_scale(self.scale_y * factor_y, self.scale_y);
+        }
+        self.offset_x = world_x - x / self.scale_x;
+        self.offset_y = world_y - y / self.scale_y;
+    }
+
+    /// Zooms and pans so the world rectangle fills a view of the given size in pixels.
+    ///
+    /// An axis along which the rectangle or the view has no size keeps its scale and centers the rectangle.
+    pub fn zoom_to_fit(&mut self, x: f32, y: f32, width: f32, height: f32, view_width: f32, view_height: f32) {
+        if width > 0.0 && view_width > 0.0 {
+            self.scale_x = valid_scale(view_width / width, self.scale_x);
+        }
+        if height > 0.0 && view_height > 0.0 {
+            self.scale_y = valid_scale(view_height / height, self.scale_y);
+        }
+        self.offset_x = x + width / 2.0 - view_width / 2.0 / self.scale_x;
+        self.offset_y = y + height / 2.0 - view_height / 2.0 / self.scale_y;
+    }
+
+    /// Returns the world area shown in a view of the given size in pixels.
+    pub fn view_bounds(&self, view_width: f32, view_height: f32) -> Bounds {
+        Bounds::from_corners(self.screen_to_world(0.0, 0.0), self.screen_to_world(view_width, view_height))
+    }
+
+    /// Returns the camera as a transform from world coordinates to screen pixels.
+    pub fn transform(&self) -> Transform {
+        Transform::translate(-self.offset_x, -self.offset_y).then(Transform::scale(self.scale_x, self.scale_y))
+    }
+}
+
+/// Returns the scale if it's finite and positive, otherwise the fallback.
+fn valid_scale(scale: f32, fallback: f32) -> f32 {
+    if scale.is_finite() && scale > 0.0 { scale } else { fallback }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near((x, y): (f32, f32), expected: (f32, f32)) {
+        assert!((x - expected.0).abs() < 1e-3 && (y - expected.1).abs() < 1e-3, "{:?} is not near {expected:?}", (x, y));
+    }
+
+    #[test]
+    fn screen_and_world_points_map_back_and_forth() {
+        let camera = Camera::new(100.0, -50.0, 2.0, 0.5);
+        assert_eq!(camera.world_to_screen(110.0, -40.0), (20.0, 5.0));
+        assert_eq!(camera.screen_to_world(20.0, 5.0), (110.0, -40.0));
+        assert_eq!(camera.transform().transform_point(110.0, -40.0), (20.0, 5.0));
+    }
+
+    #[test]
+    fn invalid_scales_are_replaced() {
+        assert_eq!(Camera::new(0.0, 0.0, 0.0, f32::NAN).scale(), (1.0, 1.0));
+        let mut camera = Camera::new(0.0, 0.0, 2.0, 3.0);
+        camera.set_scale(-1.0, f32::INFINITY);
+        assert_eq!(camera.scale(), (2.0, 3.0));
+        camera.set_scale(4.0, 0.5);
+        assert_eq!(camera.scale(), (4.0, 0.5));
+    }
+
+    #[test]
+    fn pan_follows_the_drag() {
+        let mut camera = Camera::new(0.0, 0.0, 2.0, 4.0);
+        let before = camera.screen_to_world(10.0, 10.0);
+        camera.pan(20.0, -8.0);
+        assert_eq!(camera.screen_to_world(30.0, 2.0), before);
+    }
+
+    #[test]
+    fn zoom_at_keeps_the_point_under_the_cursor() {
+        let mut camera = Camera::new(10.0, 20.0, 1.0, 1.0);
+        let world = camera.screen_to_world(200.0, 100.0);
+        camera.zoom_at(4.0, 1.0, 200.0, 100.0);
+        assert_eq!(camera.scale(), (4.0, 1.0));
+        assert_near(camera.screen_to_world(200.0, 100.0), world);
+        // Non-positive factors and zooms beyond the range of f32 keep the scale
+        camera.zoom_at(0.0, -2.0, 200.0, 100.0);
+        camera.zoom_at(f32::MAX, 1.0, 200.0, 100.0);
+        assert_eq!(camera.scale(), (4.0, 1.0));
+        assert_near(camera.screen_to_world(200.0, 100.0), world);
+    }
+
+    #[test]
+    fn zoom_to_fit_fills_the_view() {
+        let mut camera = Camera::IDENTITY;
+        camera.zoom_to_fit(100.0, 50.0, 200.0, 10.0, 800.0, 600.0);
+        assert_eq!(camera.scale(), (4.0, 60.0));
+        assert_near(camera.world_to_screen(100.0, 50.0), (0.0, 0.0));
+        assert_near(camera.world_to_screen(300.0, 60.0), (800.0, 600.0));
+        assert_eq!(camera.view_bounds(800.0, 600.0), Bounds::new(100.0, 50.0, 200.0, 10.0));
+    }
+
+    #[test]
+    fn zoom_to_fit_centers_axes_without_size() {
+        let mut camera = Camera::new(0.0, 0.0, 2.0, 2.0);
+        camera.zoom_to_fit(10.0, 10.0, 0.0, -5.0, 100.0, 100.0);
+        assert_eq!(camera.scale(), (2.0, 2.0));
+        assert_near(camera.world_to_screen(10.0, 7.5), (50.0, 50.0));
+    }
+}
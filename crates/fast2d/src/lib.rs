@@ -16,7 +16,12 @@ compile_error!("Only one rendering backend feature ('webgl', 'webgpu', 'webgpu-b
 compile_error!("One rendering backend feature ('webgl', 'webgpu', 'webgpu-blade', 'canvas', or 'native') must be enabled.");
 
 mod backend;
-pub use backend::{register_fonts, CanvasWrapper, RegisterFontsError};
+pub use backend::{register_fonts, CanvasWrapper, RegisterFontsError, FontDescriptor, registered_fonts, registered_families, resolve_font, unregister_font};
+pub use backend::{register_icon, unregister_icon, RegisterIconError};
+pub use backend::{DEFAULT_LAYER, ObjectId, RegionMode};
+pub use backend::{GpuMemoryUsage, MemoryBudget};
+#[cfg(feature = "native")]
+pub use backend::{register_font_paths, load_system_fonts};
 
 #[cfg(feature = "web")]
 mod fetch_file;
@@ -25,3 +30,6 @@ pub use fetch_file::{fetch_file, FetchFileError};
 
 pub mod object2d;
 pub use object2d::*;
+
+mod camera;
+pub use camera::Camera;
@@ -1,10 +1,17 @@
 //! 2D object primitives for Fast2D.
 //!
-//! This module provides types for representing 2D graphical objects such as text, rectangles, circles, and lines.
+//! This module provides types for representing 2D graphical objects such as text, rectangles, circles, lines, sample plots, waveforms, and groups of them.
 
 mod text;
 pub use text::{Text, FontWeight, Family};
 
+mod icon;
+pub use icon::{Icon, icon_char};
+pub(crate) use icon::{icon_id, parse_svg_path, PathCommand};
+
+mod transform;
+pub use transform::Transform;
+
 mod rectangle;
 pub use rectangle::Rectangle;
 
@@ -14,6 +21,29 @@ pub use circle::Circle;
 mod line; 
 pub use line::Line;
 
+mod sample_plot;
+pub use sample_plot::{SamplePlot, PlotStyle};
+
+mod waveform;
+pub use waveform::{Waveform, WaveValue};
+
+mod mask;
+pub use mask::{Mask, MaskError};
+#[cfg(not(feature = "webgpu-blade"))]
+pub(crate) use mask::MaskShape;
+
+mod hit_test;
+
+mod bounds;
+pub use bounds::Bounds;
+
+mod group;
+pub use group::Group;
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+pub(crate) use group::{flatten, FlatStep};
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+pub(crate) use group::{ClipBox, FlatObject};
+
 /// Represents a 2D object that can be rendered.
 #[derive(Debug, Clone)]
 pub enum Object2d {
@@ -25,4 +55,8 @@ pub enum Object2d {
     Circle(Circle),
     /// A line object.
     Line(Line),
+    /// A plot of evenly spaced samples.
+    SamplePlot(SamplePlot),
+    /// A group of objects with a shared transform, opacity and clip rectangle.
+    Group(Group),
 }
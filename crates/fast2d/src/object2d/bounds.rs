@@ -0,0 +1,278 @@
+//! Axis-aligned bounding boxes of objects, for layout, zoom-to-fit and finding objects in a region of the scene.
+
+use crate::backend::text_size;
+use super::{Circle, Group, Line, Object2d, Rectangle, SamplePlot, Text, Transform};
+
+/// An axis-aligned rectangle given by its edges, `left <= right` and `top <= bottom`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Bounds {
+    /// Creates the bounds of the rectangle at `(x, y)` with the given size, negative sizes extend left or up.
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self::from_corners((x, y), (x + width, y + height))
+    }
+
+    /// Creates the bounds of the rectangle spanned by the two corners, in any order.
+    pub fn from_corners((x1, y1): (f32, f32), (x2, y2): (f32, f32)) -> Self {
+        Self { left: x1.min(x2), top: y1.min(y2), right: x1.max(x2), bottom: y1.max(y2) }
+    }
+
+    /// Returns the bounds covering all the objects, or `None` if none of them covers an area.
+    ///
+    /// E.g. `CanvasWrapper::zoom_to_fit` with the bounds of a scene shows all of it.
+    pub fn of_objects<'a>(objects: impl IntoIterator<Item = &'a Object2d>) -> Option<Self> {
+        objects.into_iter().filter_map(Object2d::bounds).reduce(|bounds, object_bounds| bounds.union(&object_bounds))
+    }
+
+    /// Returns the width, `right - left`.
+    pub fn width(&self) -> f32 {
+        self.right - self.left
+    }
+
+    /// Returns the height, `bottom - top`.
+    pub fn height(&self) -> f32 {
+        self.bottom - self.top
+    }
+
+    /// Returns the bounds grown by `x` on the left and right and by `y` on the top and bottom.
+    pub fn expanded(&self, x: f32, y: f32) -> Self {
+        Self { left: self.left - x, top: self.top - y, right: self.right + x, bottom: self.bottom + y }
+    }
+
+    /// Returns the bounds covering both bounds.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    /// Returns the area covered by both bounds, or `None` if they don't overlap.
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        self.intersects(other).then(|| Self {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        })
+    }
+
+    /// Returns true if the bounds overlap or touch.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.left <= other.right && other.left <= self.right && self.top <= other.bottom && other.top <= self.bottom
+    }
+
+    /// Returns true if the other bounds lie completely inside these bounds.
+    pub fn contains(&self, other: &Self) -> bool {
+        self.left <= other.left && other.right <= self.right && self.top <= other.top && other.bottom <= self.bottom
+    }
+
+    /// Returns the bounding box of these bounds mapped through the transform.
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        if transform.is_identity() {
+            return *self;
+        }
+        let corners = [
+            transform.transform_point(self.left, self.top),
+            transform.transform_point(self.right, self.top),
+            transform.transform_point(self.left, self.bottom),
+            transform.transform_point(self.right, self.bottom),
+        ];
+        corners[1..].iter().fold(Self::from_corners(corners[0], corners[0]), |bounds, corner| {
+            bounds.union(&Self::from_corners(*corner, *corner))
+        })
+    }
+}
+
+impl Object2d {
+    /// Returns the bounding box of the area the object is drawn in, in the coordinates the object is positioned in,
+    /// or `None` if it covers no area, e.g. a line with fewer than two points or an empty group.
+    ///
+    /// Borders are inside the shapes, lines are extended by half their width for their round caps,
+    /// and text covers its laid-out lines, limited by its size. Rotated and skewed objects are bounded
+    /// by the box around their transformed bounds.
+    pub fn bounds(&self) -> Option<Bounds> {
+        match self {
+            Object2d::Rectangle(rect) => Some(rect.bounds()),
+            Object2d::Circle(circle) => Some(circle.bounds()),
+            Object2d::Line(line) => line.bounds(),
+            Object2d::SamplePlot(plot) => plot.bounds(),
+            Object2d::Text(text) => text.bounds(),
+            Object2d::Group(group) => group.bounds(),
+        }
+    }
+}
+
+impl Rectangle {
+    /// Returns the bounding box of the rectangle including its inner border, after its transform.
+    pub fn bounds(&self) -> Bounds {
+        Bounds::new(self.position.x, self.position.y, self.size.width, self.size.height).transformed(&self.transform)
+    }
+}
+
+impl Circle {
+    /// Returns the bounding box of the circle including its inner border, after its transform.
+    pub fn bounds(&self) -> Bounds {
+        let diameter = self.radius * 2.0;
+        Bounds::new(self.center.x - self.radius, self.center.y - self.radius, diameter, diameter).transformed(&self.transform)
+    }
+}
+
+impl Line {
+    /// Returns the bounding box of the line including half its width around the points for the round caps,
+    /// after its transform, or `None` if it has fewer than two points and is not drawn.
+    pub fn bounds(&self) -> Option<Bounds> {
+        if self.points.len() < 2 {
+            return None;
+        }
+        let half_width = self.width / 2.0;
+        let bounds = self.points.iter()
+            .map(|point| Bounds::from_corners((point.x - half_width, point.y - half_width), (point.x + half_width, point.y + half_width)))
+            .reduce(|bounds, point_bounds| bounds.union(&point_bounds))?;
+        Some(bounds.transformed(&self.transform))
+    }
+}
+
+impl SamplePlot {
+    /// Returns the bounding box of the samples including half the line width, after the transform,
+    /// or `None` if there are fewer than two samples and nothing is drawn.
+    pub fn bounds(&self) -> Option<Bounds> {
+        let (min, max) = self.value_range;
+        if self.samples.len() < 2 || min > max {
+            return None;
+        }
+        let half_width = self.width / 2.0;
+        let last_x = self.x + (self.samples.len() - 1) as f32 * self.x_step;
+        let (top, bottom) = (self.y + min * self.y_scale, self.y + max * self.y_scale);
+        let bounds = Bounds::from_corners((self.x, top), (last_x, bottom)).expanded(half_width, half_width);
+        Some(bounds.transformed(&self.transform))
+    }
+}
+
+impl Text {
+    /// Returns the bounding box of the laid-out lines limited by the text size, after its transform,
+    /// or `None` if the text is empty.
+    ///
+    /// The text is laid out with the registered fonts like when drawn, so the bounds change when fonts are registered.
+    pub fn bounds(&self) -> Option<Bounds> {
+        let (width, height) = text_size(self);
+        if width <= 0.0 || height <= 0.0 {
+            return None;
+        }
+        Some(Bounds::new(self.left, self.top, width, height).transformed(&self.transform))
+    }
+}
+
+impl Group {
+    /// Returns the bounding box of the children within the clip rectangle and the mask, after the group transform,
+    /// or `None` if no child covers an area inside them.
+    pub fn bounds(&self) -> Option<Bounds> {
+        let mut bounds = Bounds::of_objects(&self.children)?;
+        // Children are only drawn inside the clip rectangle and the mask
+        if let Some((position, size)) = self.clip {
+            bounds = bounds.intersection(&Bounds::new(position.x, position.y, size.width, size.height))?;
+        }
+        if let Some(mask) = &self.mask {
+            let (x, y, width, height) = mask.bounds();
+            bounds = bounds.intersection(&Bounds::new(x, y, width, height))?;
+        }
+        Some(bounds.transformed(&self.transform))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+    use super::*;
+
+    fn assert_near(bounds: Bounds, expected: Bounds) {
+        let edges = |bounds: Bounds| [bounds.left, bounds.top, bounds.right, bounds.bottom];
+        let near = edges(bounds).iter().zip(edges(expected)).all(|(edge, expected)| (edge - expected).abs() < 1e-4);
+        assert!(near, "{bounds:?} is not near {expected:?}");
+    }
+
+    #[test]
+    fn negative_sizes_extend_left_and_up() {
+        assert_eq!(Bounds::new(10.0, 10.0, -4.0, -6.0), Bounds { left: 6.0, top: 4.0, right: 10.0, bottom: 10.0 });
+        assert_eq!(Bounds::from_corners((10.0, 0.0), (0.0, 5.0)), Bounds::new(0.0, 0.0, 10.0, 5.0));
+    }
+
+    #[test]
+    fn touching_bounds_intersect() {
+        let bounds = Bounds::new(0.0, 0.0, 10.0, 10.0);
+        let touching = Bounds::new(10.0, 10.0, 5.0, 5.0);
+        assert!(bounds.intersects(&touching));
+        assert_eq!(bounds.intersection(&touching), Some(Bounds::new(10.0, 10.0, 0.0, 0.0)));
+        assert!(!bounds.intersects(&Bounds::new(10.5, 0.0, 5.0, 5.0)));
+        assert_eq!(bounds.intersection(&Bounds::new(10.5, 0.0, 5.0, 5.0)), None);
+    }
+
+    #[test]
+    fn contains_and_union() {
+        let bounds = Bounds::new(0.0, 0.0, 10.0, 10.0);
+        assert!(bounds.contains(&bounds));
+        assert!(bounds.contains(&Bounds::new(2.0, 2.0, 2.0, 2.0)));
+        assert!(!bounds.contains(&Bounds::new(8.0, 8.0, 4.0, 2.0)));
+        assert_eq!(bounds.union(&Bounds::new(-5.0, 5.0, 1.0, 20.0)), Bounds { left: -5.0, top: 0.0, right: 10.0, bottom: 25.0 });
+    }
+
+    #[test]
+    fn transformed_bounds_cover_the_rotated_corners() {
+        let bounds = Bounds::new(-1.0, -1.0, 2.0, 2.0);
+        let diagonal = 2.0f32.sqrt();
+        assert_near(bounds.transformed(&Transform::rotate(FRAC_PI_4)), Bounds::new(-diagonal, -diagonal, diagonal * 2.0, diagonal * 2.0));
+        // Mirroring swaps the edges, the bounds stay ordered
+        assert_eq!(bounds.transformed(&Transform::scale(-2.0, 1.0).then(Transform::translate(1.0, 0.0))), Bounds::new(-1.0, -1.0, 4.0, 2.0));
+    }
+
+    #[test]
+    fn shapes_are_bounded_after_their_transform() {
+        let rect = Rectangle::new().position(0.0, 0.0).size(10.0, 20.0).transform(Transform::translate(5.0, 5.0));
+        assert_eq!(rect.bounds(), Bounds::new(5.0, 5.0, 10.0, 20.0));
+        let circle = Circle::new().center(10.0, 10.0).radius(5.0).transform(Transform::scale(2.0, 1.0));
+        assert_eq!(circle.bounds(), Bounds::new(10.0, 5.0, 20.0, 10.0));
+    }
+
+    #[test]
+    fn lines_are_extended_by_half_their_width() {
+        let line = Line::new().points(&[(0.0, 0.0), (10.0, 5.0)]).width(2.0);
+        assert_eq!(line.bounds(), Some(Bounds::new(-1.0, -1.0, 12.0, 7.0)));
+        assert_eq!(Line::new().points(&[(0.0, 0.0)]).bounds(), None);
+    }
+
+    #[test]
+    fn sample_plots_are_bounded_by_their_finite_samples() {
+        let plot = SamplePlot::new(vec![1.0, f32::NAN, -2.0, f32::INFINITY]).position(10.0, 0.0).x_step(2.0).y_scale(-1.0).width(0.0);
+        assert_eq!(plot.bounds(), Some(Bounds::new(10.0, -1.0, 6.0, 3.0)));
+        assert_eq!(SamplePlot::new(vec![f32::NAN, f32::NAN]).bounds(), None);
+        assert_eq!(SamplePlot::new(vec![1.0]).bounds(), None);
+    }
+
+    #[test]
+    fn groups_are_bounded_by_their_clip() {
+        let rect = Rectangle::new().position(0.0, 0.0).size(100.0, 100.0);
+        let group = Group::new().child(rect).clip(50.0, 50.0, 100.0, 100.0).transform(Transform::translate(10.0, 0.0));
+        assert_eq!(group.bounds(), Some(Bounds::new(60.0, 50.0, 50.0, 50.0)));
+        assert_eq!(Group::new().child(rect).clip(200.0, 0.0, 10.0, 10.0).bounds(), None);
+        assert_eq!(Group::new().bounds(), None);
+    }
+
+    #[test]
+    fn objects_are_bounded_together() {
+        let objects: Vec<Object2d> = vec![
+            Rectangle::new().position(0.0, 0.0).size(10.0, 10.0).into(),
+            Line::new().points(&[(0.0, 0.0)]).into(),
+            Circle::new().center(50.0, 50.0).radius(10.0).into(),
+        ];
+        assert_eq!(Bounds::of_objects(&objects), Some(Bounds::new(0.0, 0.0, 60.0, 60.0)));
+        assert_eq!(Bounds::of_objects(&objects[1..2]), None);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::backend::{Point, Color};
-use super::Object2d;
+use super::{Object2d, Transform};
 
 /// A circle shape with optional border.
 #[derive(Clone, Debug)]
@@ -14,6 +14,8 @@ pub struct Circle {
     pub(crate) border_width: Option<f32>,
     /// The color of the border, if any.
     pub(crate) border_color: Option<Color>,
+    /// The transform applied to the circle.
+    pub(crate) transform: Transform,
 }
 
 impl Default for Circle {
@@ -24,6 +26,7 @@ impl Default for Circle {
             color: Color::default(),
             border_width: None,
             border_color: None,
+            transform: Transform::IDENTITY,
         }
     }
 }
@@ -58,6 +61,12 @@ impl Circle {
         self.border_color = Some(Color::new(r, g, b, a));
         self
     }
+
+    /// Sets the transform applied to the circle, e.g. `Transform::rotate_around(angle, x, y)`.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
 }
 
 /// Converts a Circle into an Object2d.
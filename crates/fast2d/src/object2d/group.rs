@@ -0,0 +1,257 @@
+use crate::backend::{Point, Size};
+use super::{Mask, Object2d, Transform};
+
+/// A group of objects sharing a transform, opacity and clip rectangle.
+///
+/// Children are drawn in order and may be groups themselves, nested to any depth.
+/// Moving a composite widget only requires changing the group transform.
+#[derive(Debug, Clone)]
+pub struct Group {
+    /// The objects in the group, drawn in order.
+    pub(crate) children: Vec<Object2d>,
+    /// The transform applied to all children, after their own transforms.
+    pub(crate) transform: Transform,
+    /// The opacity multiplied with the opacity of all children (0.0 - 1.0).
+    pub(crate) opacity: f32,
+    /// The rectangle outside of which children are not drawn, in the group's coordinates.
+    pub(crate) clip: Option<(Point, Size)>,
+    /// The shape outside of which children are not drawn, in the group's coordinates.
+    pub(crate) mask: Option<Mask>,
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Self {
+            children: Vec::new(),
+            transform: Transform::IDENTITY,
+            opacity: 1.0,
+            clip: None,
+            mask: None,
+        }
+    }
+}
+
+impl Group {
+    /// Creates a new empty group.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the objects in the group, drawn in order.
+    pub fn children(mut self, children: impl IntoIterator<Item = impl Into<Object2d>>) -> Self {
+        self.children = children.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Appends an object to the group, drawn over the previous children.
+    pub fn child(mut self, child: impl Into<Object2d>) -> Self {
+        self.children.push(child.into());
+        self
+    }
+
+    /// Sets the transform applied to all children, e.g. `Transform::translate(x, y)` to move the whole group.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Sets the opacity of the group (0.0 = invisible, 1.0 = opaque).
+    ///
+    /// The opacity is multiplied into every child, overlapping children are not composited as one layer.
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the rectangle outside of which children are not drawn, in the group's coordinates.
+    ///
+    /// Clip rectangles of nested groups intersect, text is clipped by the intersection of its own bounds
+    /// and the clip rectangles of its groups, e.g. to cut the rows of a scrolled list at the panel edges.
+    /// On the wgpu backends, clip rectangles of rotated or skewed groups are replaced by their bounding box,
+    /// use [`Group::mask`] with [`Mask::rectangle`] to clip them exactly.
+    pub fn clip(mut self, x: f32, y: f32, width: f32, height: f32) -> Self {
+        self.clip = Some((Point { x, y }, Size { width: width.max(0.0), height: height.max(0.0) }));
+        self
+    }
+
+    /// Sets the shape outside of which children are not drawn, e.g. a circle for an avatar image.
+    ///
+    /// Masks of nested groups intersect, and intersect with the clip rectangles.
+    /// Unlike clip rectangles, masks follow rotated and skewed group transforms exactly.
+    /// On the wgpu backends, masked text is drawn from glyph outlines, without inline icons and with monochrome emoji.
+    /// The `webgpu-blade` backend doesn't support masks, it only clips the children to the mask bounding box.
+    pub fn mask(mut self, mask: Mask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+}
+
+/// Converts a Group into an Object2d.
+impl From<Group> for Object2d {
+    fn from(group: Group) -> Self {
+        Object2d::Group(group)
+    }
+}
+
+/// An axis-aligned clip rectangle in canvas pixels.
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ClipBox {
+    pub(crate) left: f32,
+    pub(crate) top: f32,
+    pub(crate) right: f32,
+    pub(crate) bottom: f32,
+}
+
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+impl ClipBox {
+    /// Returns the bounding box of the rectangle mapped through the transform.
+    pub(crate) fn transformed(x: f32, y: f32, width: f32, height: f32, transform: &Transform) -> Self {
+        let corners = [
+            transform.transform_point(x, y),
+            transform.transform_point(x + width, y),
+            transform.transform_point(x, y + height),
+            transform.transform_point(x + width, y + height),
+        ];
+        corners.iter().fold(
+            Self { left: f32::MAX, top: f32::MAX, right: f32::MIN, bottom: f32::MIN },
+            |clip, (x, y)| Self { left: clip.left.min(*x), top: clip.top.min(*y), right: clip.right.max(*x), bottom: clip.bottom.max(*y) },
+        )
+    }
+
+    /// Returns the bounding box of the box mapped through the transform, empty boxes stay empty.
+    pub(crate) fn mapped(&self, transform: &Transform) -> Self {
+        if self.right <= self.left || self.bottom <= self.top {
+            return *self;
+        }
+        Self::transformed(self.left, self.top, self.right - self.left, self.bottom - self.top, transform)
+    }
+
+    /// Returns the area covered by both boxes, empty boxes have `right <= left` or `bottom <= top`.
+    pub(crate) fn intersect(&self, other: &Self) -> Self {
+        Self {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        }
+    }
+
+    /// Returns the box as a scissor rectangle `(x, y, width, height)` in whole pixels within the surface.
+    pub(crate) fn scissor_rect(&self, surface_width: u32, surface_height: u32) -> (u32, u32, u32, u32) {
+        let left = self.left.floor().clamp(0.0, surface_width as f32) as u32;
+        let top = self.top.floor().clamp(0.0, surface_height as f32) as u32;
+        let right = self.right.ceil().clamp(0.0, surface_width as f32) as u32;
+        let bottom = self.bottom.ceil().clamp(0.0, surface_height as f32) as u32;
+        (left, top, right.saturating_sub(left), bottom.saturating_sub(top))
+    }
+}
+
+/// A step of drawing a flattened scene.
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+pub(crate) enum FlatStep<'a> {
+    /// Draws the object, never a group.
+    Draw(&'a Object2d),
+    /// Starts masking the following steps by the shape, within the masks already active.
+    #[cfg(not(feature = "webgpu-blade"))]
+    PushMask(&'a Mask),
+    /// Stops masking by the shape pushed before.
+    #[cfg(not(feature = "webgpu-blade"))]
+    PopMask,
+}
+
+/// A drawing step with the transform, opacity, clip and mask depth accumulated from its groups.
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+pub(crate) struct FlatObject<'a> {
+    /// The object to draw or the mask to push or pop.
+    pub(crate) step: FlatStep<'a>,
+    /// The object's own transform followed by the transforms of its groups.
+    pub(crate) transform: Transform,
+    /// The product of the opacities of its groups.
+    pub(crate) opacity: f32,
+    /// The intersection of the clip rectangles and the mask bounding boxes of its groups.
+    pub(crate) clip: Option<ClipBox>,
+    /// The number of masks active for the object, for masks the number of masks active around them.
+    #[cfg(not(feature = "webgpu-blade"))]
+    pub(crate) mask_depth: u32,
+}
+
+/// Objects flattened so far and the number of masks active around the next ones.
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+struct Flattened<'a> {
+    objects: Vec<FlatObject<'a>>,
+    #[cfg(not(feature = "webgpu-blade"))]
+    mask_depth: u32,
+}
+
+/// Flattens the groups into a list of objects in drawing order, for backends without a state stack.
+///
+/// The `webgpu-blade` backend has no stencil, masked groups only clip their children
+/// by the mask bounding box there.
+#[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+pub(crate) fn flatten(objects: &[Object2d]) -> Vec<FlatObject<'_>> {
+    fn visit<'a>(objects: &'a [Object2d], transform: Transform, opacity: f32, clip: Option<ClipBox>, flat: &mut Flattened<'a>) {
+        for object in objects {
+            let own_transform = match object {
+                Object2d::Text(text) => text.transform,
+                Object2d::Rectangle(rect) => rect.transform,
+                Object2d::Circle(circle) => circle.transform,
+                Object2d::Line(line) => line.transform,
+                Object2d::SamplePlot(plot) => plot.transform,
+                Object2d::Group(group) => group.transform,
+            };
+            let transform = own_transform.then(transform);
+            match object {
+                Object2d::Group(group) => {
+                    // Fully transparent groups are skipped with all their children
+                    let opacity = opacity * group.opacity;
+                    if opacity <= 0.0 {
+                        continue;
+                    }
+                    let clip = match group.clip {
+                        Some((position, size)) => {
+                            let group_clip = ClipBox::transformed(position.x, position.y, size.width, size.height, &transform);
+                            Some(clip.map_or(group_clip, |clip| group_clip.intersect(&clip)))
+                        }
+                        None => clip,
+                    };
+                    let Some(mask) = &group.mask else {
+                        visit(&group.children, transform, opacity, clip, flat);
+                        continue;
+                    };
+                    // Children are also clipped by the mask bounding box, so masked areas are small on the GPU
+                    let (x, y, width, height) = mask.bounds();
+                    let mask_clip = ClipBox::transformed(x, y, width, height, &transform);
+                    let children_clip = Some(clip.map_or(mask_clip, |clip| mask_clip.intersect(&clip)));
+                    #[cfg(not(feature = "webgpu-blade"))]
+                    {
+                        flat.objects.push(FlatObject { step: FlatStep::PushMask(mask), transform, opacity, clip, mask_depth: flat.mask_depth });
+                        flat.mask_depth += 1;
+                    }
+                    visit(&group.children, transform, opacity, children_clip, flat);
+                    #[cfg(not(feature = "webgpu-blade"))]
+                    {
+                        flat.mask_depth -= 1;
+                        flat.objects.push(FlatObject { step: FlatStep::PopMask, transform, opacity, clip, mask_depth: flat.mask_depth });
+                    }
+                }
+                _ => flat.objects.push(FlatObject {
+                    step: FlatStep::Draw(object),
+                    transform,
+                    opacity,
+                    clip,
+                    #[cfg(not(feature = "webgpu-blade"))]
+                    mask_depth: flat.mask_depth,
+                }),
+            }
+        }
+    }
+
+    let mut flat = Flattened {
+        objects: Vec::with_capacity(objects.len()),
+        #[cfg(not(feature = "webgpu-blade"))]
+        mask_depth: 0,
+    };
+    visit(objects, Transform::IDENTITY, 1.0, None, &mut flat);
+    flat.objects
+}
@@ -0,0 +1,359 @@
+//! Testing whether points lie on objects, e.g. to find the object under the mouse.
+
+use crate::backend::{text_size, RoundedCorners};
+use super::mask::MaskShape;
+use super::{Mask, Object2d, PathCommand, PlotStyle, Transform};
+
+/// Number of straight segments a curve or an arc of a mask path is flattened into.
+const CURVE_SEGMENTS: usize = 16;
+
+impl Object2d {
+    /// Returns true if the point `(x, y)` lies on the object, in the coordinates the object is positioned in.
+    ///
+    /// Rectangles and circles are hit within their outline including their inner border,
+    /// lines and sample plots within half their width of the points (with round caps and joins, like they are drawn),
+    /// and text within its laid-out lines, limited by its size. The object's transform is applied.
+    /// A group is hit where one of its children is, inside its clip rectangle and mask.
+    /// Colors and opacity are ignored, so fully transparent objects can serve as hit areas.
+    pub fn contains_point(&self, x: f32, y: f32) -> bool {
+        let transform = match self {
+            Object2d::Text(text) => text.transform,
+            Object2d::Rectangle(rect) => rect.transform,
+            Object2d::Circle(circle) => circle.transform,
+            Object2d::Line(line) => line.transform,
+            Object2d::SamplePlot(plot) => plot.transform,
+            Object2d::Group(group) => group.transform,
+        };
+        // Collapsed objects are drawn as lines or points at most, they can't be hit
+        let Some((x, y)) = local_point(&transform, x, y) else {
+            return false;
+        };
+        match self {
+            Object2d::Rectangle(rect) => {
+                rounded_rect_contains(rect.position.x, rect.position.y, rect.size.width, rect.size.height, &rect.rounded_corners, x, y)
+            }
+            Object2d::Circle(circle) => {
+                (x - circle.center.x).hypot(y - circle.center.y) <= circle.radius
+            }
+            Object2d::Line(line) => {
+                let half_width = line.width / 2.0;
+                line.points.len() >= 2 && line.points.windows(2).any(|segment| {
+                    segment_distance((segment[0].x, segment[0].y), (segment[1].x, segment[1].y), (x, y)) <= half_width
+                })
+            }
+            Object2d::SamplePlot(plot) => {
+                // Only the samples around the point can be hit, long plots are not searched
+                let half_width = plot.width / 2.0;
+                let range = plot.sample_range(x - half_width, x + half_width);
+                range.clone().zip(range.skip(1)).any(|(index, next)| {
+                    if !plot.samples[index].is_finite() || !plot.samples[next].is_finite() {
+                        return false;
+                    }
+                    let (start, end) = (plot.sample_point(index), plot.sample_point(next));
+                    match plot.style {
+                        PlotStyle::Polyline => segment_distance((start.x, start.y), (end.x, end.y), (x, y)) <= half_width,
+                        PlotStyle::Step => {
+                            segment_distance((start.x, start.y), (end.x, start.y), (x, y)) <= half_width
+                                || segment_distance((end.x, start.y), (end.x, end.y), (x, y)) <= half_width
+                        }
+                    }
+                })
+            }
+            Object2d::Text(text) => {
+                let (width, height) = text_size(text);
+                x >= text.left && x <= text.left + width && y >= text.top && y <= text.top + height
+            }
+            Object2d::Group(group) => {
+                let in_clip = group.clip.is_none_or(|(position, size)| {
+                    x >= position.x && x <= position.x + size.width && y >= position.y && y <= position.y + size.height
+                });
+                let in_mask = group.mask.as_ref().is_none_or(|mask| mask_contains(mask, x, y));
+                in_clip && in_mask && group.children.iter().any(|child| child.contains_point(x, y))
+            }
+        }
+    }
+}
+
+/// Maps the point back through the transform, or returns `None` if the transform collapses the plane.
+fn local_point(transform: &Transform, x: f32, y: f32) -> Option<(f32, f32)> {
+    if transform.is_identity() {
+        return Some((x, y));
+    }
+    Some(transform.inverse()?.transform_point(x, y))
+}
+
+/// Returns true if the point is inside the rectangle with rounded corners.
+///
+/// Radii are limited to half the width and height, like when the rectangle is drawn.
+fn rounded_rect_contains(left: f32, top: f32, width: f32, height: f32, radii: &RoundedCorners, x: f32, y: f32) -> bool {
+    let (right, bottom) = (left + width, top + height);
+    if x < left || x > right || y < top || y > bottom {
+        return false;
+    }
+    let max_radius = (width / 2.0).min(height / 2.0);
+    let [top_left, top_right, bottom_left, bottom_right] =
+        [radii.top_left, radii.top_right, radii.bottom_left, radii.bottom_right].map(|radius| radius.clamp(0.0, max_radius));
+    // Corner arcs are centered inside the rectangle, points beyond the center of a corner along both axes must be within its radius
+    let corners = [
+        (top_left, left + top_left, top + top_left, x < left + top_left && y < top + top_left),
+        (top_right, right - top_right, top + top_right, x > right - top_right && y < top + top_right),
+        (bottom_left, left + bottom_left, bottom - bottom_left, x < left + bottom_left && y > bottom - bottom_left),
+        (bottom_right, right - bottom_right, bottom - bottom_right, x > right - bottom_right && y > bottom - bottom_right),
+    ];
+    corners.into_iter().all(|(radius, center_x, center_y, in_corner)| !in_corner || (x - center_x).hypot(y - center_y) <= radius)
+}
+
+/// Returns the distance of the point from the segment between `start` and `end`.
+fn segment_distance(start: (f32, f32), end: (f32, f32), point: (f32, f32)) -> f32 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared > 0.0 {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (point.0 - (start.0 + t * dx)).hypot(point.1 - (start.1 + t * dy))
+}
+
+/// Returns true if the point is inside the mask shape, path masks are filled with the nonzero rule.
+fn mask_contains(mask: &Mask, x: f32, y: f32) -> bool {
+    match &mask.shape {
+        MaskShape::Rectangle { position, size, rounded_corners } => {
+            rounded_rect_contains(position.x, position.y, size.width, size.height, rounded_corners, x, y)
+        }
+        MaskShape::Circle { center, radius } => (x - center.x).hypot(y - center.y) <= *radius,
+        MaskShape::Path { commands, .. } => winding_number(&path_polygons(commands), x, y) != 0,
+    }
+}
+
+/// Flattens the path into closed polygons, one for each subpath.
+fn path_polygons(commands: &[PathCommand]) -> Vec<Vec<(f32, f32)>> {
+    let mut polygons: Vec<Vec<(f32, f32)>> = Vec::new();
+    let mut current = (0.0f32, 0.0f32);
+    for command in commands {
+        match *command {
+            PathCommand::MoveTo { x, y } => {
+                polygons.push(vec![(x, y)]);
+                current = (x, y);
+                continue;
+            }
+            PathCommand::Close => {
+                // The next subpath starts at the start of the closed one, unless it moves
+                if let Some(start) = polygons.last().map(|polygon| polygon[0]) {
+                    polygons.push(vec![start]);
+                    current = start;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if polygons.is_empty() {
+            polygons.push(vec![current]);
+        }
+        let polygon = polygons.last_mut().unwrap();
+        match *command {
+            PathCommand::LineTo { x, y } => polygon.push((x, y)),
+            PathCommand::QuadraticTo { x1, y1, x, y } => {
+                for step in 1..=CURVE_SEGMENTS {
+                    let t = step as f32 / CURVE_SEGMENTS as f32;
+                    let u = 1.0 - t;
+                    polygon.push((
+                        u * u * current.0 + 2.0 * u * t * x1 + t * t * x,
+                        u * u * current.1 + 2.0 * u * t * y1 + t * t * y,
+                    ));
+                }
+            }
+            PathCommand::CubicTo { x1, y1, x2, y2, x, y } => {
+                for step in 1..=CURVE_SEGMENTS {
+                    let t = step as f32 / CURVE_SEGMENTS as f32;
+                    let u = 1.0 - t;
+                    polygon.push((
+                        u * u * u * current.0 + 3.0 * u * u * t * x1 + 3.0 * u * t * t * x2 + t * t * t * x,
+                        u * u * u * current.1 + 3.0 * u * u * t * y1 + 3.0 * u * t * t * y2 + t * t * t * y,
+                    ));
+                }
+            }
+            PathCommand::ArcTo { rx, ry, x_axis_rotation, large_arc, sweep, x, y } => {
+                push_arc(polygon, current, (x, y), (rx, ry), x_axis_rotation, (large_arc, sweep));
+            }
+            PathCommand::MoveTo { .. } | PathCommand::Close => unreachable!(),
+        }
+        current = polygon[polygon.len() - 1];
+    }
+    polygons
+}
+
+/// Pushes the points of an SVG elliptical arc from `from` to `to`, converted to its center parameterization.
+fn push_arc(polygon: &mut Vec<(f32, f32)>, from: (f32, f32), to: (f32, f32), radii: (f32, f32), x_axis_rotation: f32, (large_arc, sweep): (bool, bool)) {
+    let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+    // Arcs without radii are straight lines, like in SVG
+    if from == to || rx == 0.0 || ry == 0.0 {
+        polygon.push(to);
+        return;
+    }
+    let (sin, cos) = x_axis_rotation.to_radians().sin_cos();
+    let (half_dx, half_dy) = ((from.0 - to.0) / 2.0, (from.1 - to.1) / 2.0);
+    let x1 = cos * half_dx + sin * half_dy;
+    let y1 = -sin * half_dx + cos * half_dy;
+    // Radii too small to reach the end point are scaled up
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coefficient = sign * (numerator / denominator).max(0.0).sqrt();
+    let center_x1 = coefficient * rx * y1 / ry;
+    let center_y1 = -coefficient * ry * x1 / rx;
+    let center_x = cos * center_x1 - sin * center_y1 + (from.0 + to.0) / 2.0;
+    let center_y = sin * center_x1 + cos * center_y1 + (from.1 + to.1) / 2.0;
+    let start_angle = ((y1 - center_y1) / ry).atan2((x1 - center_x1) / rx);
+    let end_angle = ((-y1 - center_y1) / ry).atan2((-x1 - center_x1) / rx);
+    let mut sweep_angle = end_angle - start_angle;
+    if sweep && sweep_angle < 0.0 {
+        sweep_angle += std::f32::consts::TAU;
+    } else if !sweep && sweep_angle > 0.0 {
+        sweep_angle -= std::f32::consts::TAU;
+    }
+    for step in 1..=CURVE_SEGMENTS {
+        let angle = start_angle + sweep_angle * step as f32 / CURVE_SEGMENTS as f32;
+        let (angle_sin, angle_cos) = angle.sin_cos();
+        polygon.push((
+            cos * rx * angle_cos - sin * ry * angle_sin + center_x,
+            sin * rx * angle_cos + cos * ry * angle_sin + center_y,
+        ));
+    }
+}
+
+/// Returns the winding number of the polygons around the point, polygons are closed implicitly.
+fn winding_number(polygons: &[Vec<(f32, f32)>], x: f32, y: f32) -> i32 {
+    let mut winding = 0;
+    for polygon in polygons {
+        for (index, start) in polygon.iter().enumerate() {
+            let end = polygon[(index + 1) % polygon.len()];
+            // Positive if the point is left of the edge
+            let side = (end.0 - start.0) * (y - start.1) - (x - start.0) * (end.1 - start.1);
+            if start.1 <= y {
+                if end.1 > y && side > 0.0 {
+                    winding += 1;
+                }
+            } else if end.1 <= y && side < 0.0 {
+                winding -= 1;
+            }
+        }
+    }
+    winding
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+    use super::*;
+    use crate::object2d::{Circle, Group, Line, Rectangle, SamplePlot};
+
+    #[test]
+    fn rotated_rectangles_are_hit_inside_their_rotated_outline() {
+        // A square rotated into a diamond around its center
+        let rect: Object2d = Rectangle::new().position(0.0, 0.0).size(10.0, 10.0)
+            .transform(Transform::rotate_around(FRAC_PI_4, 5.0, 5.0)).into();
+        assert!(rect.contains_point(5.0, 5.0));
+        // The tips of the diamond lie outside the unrotated square
+        assert!(rect.contains_point(5.0, -1.5));
+        assert!(rect.contains_point(11.5, 5.0));
+        // The corners of the unrotated square are outside the diamond
+        assert!(!rect.contains_point(0.5, 0.5));
+        assert!(!rect.contains_point(9.5, 9.5));
+    }
+
+    #[test]
+    fn collapsed_objects_are_never_hit() {
+        let rect: Object2d = Rectangle::new().position(0.0, 0.0).size(10.0, 10.0).transform(Transform::scale(0.0, 1.0)).into();
+        assert!(!rect.contains_point(0.0, 5.0));
+    }
+
+    #[test]
+    fn rounded_corners_are_not_hit_outside_their_arc() {
+        let rect: Object2d = Rectangle::new().position(0.0, 0.0).size(20.0, 20.0).rounded_corners(10.0, 0.0, 0.0, 0.0).into();
+        assert!(!rect.contains_point(1.0, 1.0));
+        assert!(rect.contains_point(5.0, 5.0));
+        assert!(rect.contains_point(19.0, 1.0));
+    }
+
+    #[test]
+    fn circles_are_hit_within_their_radius() {
+        let circle: Object2d = Circle::new().center(10.0, 10.0).radius(5.0).transform(Transform::scale(2.0, 1.0)).into();
+        assert!(circle.contains_point(29.0, 10.0));
+        assert!(!circle.contains_point(28.0, 14.0));
+    }
+
+    #[test]
+    fn lines_are_hit_within_half_their_width() {
+        let line: Object2d = Line::new().points(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]).width(2.0).into();
+        assert!(line.contains_point(5.0, 0.9));
+        assert!(!line.contains_point(5.0, 1.1));
+        // Round caps extend beyond the ends
+        assert!(line.contains_point(-0.9, 0.0));
+        assert!(line.contains_point(10.0, 10.9));
+    }
+
+    #[test]
+    fn sample_plots_are_not_hit_across_missing_samples() {
+        let plot: Object2d = SamplePlot::new(vec![0.0, 0.0, f32::NAN, 0.0, 0.0]).x_step(10.0).width(2.0).into();
+        assert!(plot.contains_point(5.0, 0.0));
+        assert!(!plot.contains_point(20.0, 0.0));
+        assert!(plot.contains_point(35.0, 0.0));
+    }
+
+    #[test]
+    fn step_plots_are_hit_on_their_steps() {
+        let plot: Object2d = SamplePlot::new(vec![0.0, 10.0]).x_step(10.0).style(PlotStyle::Step).into();
+        assert!(plot.contains_point(5.0, 0.0));
+        assert!(plot.contains_point(10.0, 5.0));
+        assert!(!plot.contains_point(5.0, 5.0));
+    }
+
+    #[test]
+    fn groups_are_hit_on_their_children_inside_the_clip_and_mask() {
+        let group: Object2d = Group::new()
+            .child(Rectangle::new().position(0.0, 0.0).size(100.0, 100.0))
+            .clip(0.0, 0.0, 50.0, 100.0)
+            .mask(Mask::circle(25.0, 25.0, 20.0))
+            .transform(Transform::translate(100.0, 0.0))
+            .into();
+        assert!(group.contains_point(125.0, 25.0));
+        // Outside the mask, outside the clip, and untransformed
+        assert!(!group.contains_point(125.0, 60.0));
+        assert!(!group.contains_point(160.0, 25.0));
+        assert!(!group.contains_point(25.0, 25.0));
+    }
+
+    #[test]
+    fn path_masks_are_filled_with_the_nonzero_rule() {
+        // Two squares of the same winding, the inner one is still filled
+        let commands = [
+            PathCommand::MoveTo { x: 0.0, y: 0.0 },
+            PathCommand::LineTo { x: 10.0, y: 0.0 },
+            PathCommand::LineTo { x: 10.0, y: 10.0 },
+            PathCommand::LineTo { x: 0.0, y: 10.0 },
+            PathCommand::Close,
+            PathCommand::MoveTo { x: 2.0, y: 2.0 },
+            PathCommand::LineTo { x: 8.0, y: 2.0 },
+            PathCommand::LineTo { x: 8.0, y: 8.0 },
+            PathCommand::LineTo { x: 2.0, y: 8.0 },
+            PathCommand::Close,
+        ];
+        let polygons = path_polygons(&commands);
+        assert_eq!(winding_number(&polygons, 5.0, 5.0), 2);
+        assert_eq!(winding_number(&polygons, 1.0, 5.0), 1);
+        assert_eq!(winding_number(&polygons, 11.0, 5.0), 0);
+    }
+
+    #[test]
+    fn segment_distance_is_measured_to_the_closest_end() {
+        assert_eq!(segment_distance((0.0, 0.0), (10.0, 0.0), (5.0, 3.0)), 3.0);
+        assert_eq!(segment_distance((0.0, 0.0), (10.0, 0.0), (13.0, 4.0)), 5.0);
+        assert_eq!(segment_distance((1.0, 1.0), (1.0, 1.0), (4.0, 5.0)), 5.0);
+    }
+}
@@ -0,0 +1,324 @@
+//! Icons rendered inline with text.
+//!
+//! Icons are registered by id with [`register_icon`](crate::register_icon) and placed into
+//! [`Text`](crate::Text) content as private use characters, see [`icon_char`] and [`Text::icon`](crate::Text::icon).
+
+use std::borrow::Cow;
+
+/// First character of the range used to reference icons in text (Supplementary Private Use Area-A).
+const ICON_CHAR_BASE: u32 = 0xF0000;
+
+/// An icon that can be rendered inline with text.
+///
+/// Icons are scaled to the font size of the text (height) keeping their aspect ratio.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Icon {
+    /// A vector icon filled with the text color.
+    Path {
+        /// SVG path data (the `d` attribute of an SVG `<path>` element).
+        data: Cow<'static, str>,
+        /// Width of the view box the path data is defined in.
+        width: f32,
+        /// Height of the view box the path data is defined in.
+        height: f32,
+    },
+    /// A color bitmap icon.
+    Bitmap {
+        /// Width in pixels.
+        width: u32,
+        /// Height in pixels.
+        height: u32,
+        /// Pixel data, 4 bytes (red, green, blue, alpha) per pixel, row by row.
+        rgba: Vec<u8>,
+    },
+}
+
+impl Icon {
+    /// Creates a vector icon from SVG path data defined in a `width` x `height` view box.
+    pub fn svg_path(data: impl Into<Cow<'static, str>>, width: f32, height: f32) -> Self {
+        Icon::Path { data: data.into(), width, height }
+    }
+
+    /// Creates a color icon from RGBA pixel data.
+    pub fn bitmap(width: u32, height: u32, rgba: Vec<u8>) -> Self {
+        Icon::Bitmap { width, height, rgba }
+    }
+
+    /// Returns the width of the icon relative to its height.
+    pub(crate) fn aspect_ratio(&self) -> f32 {
+        let (width, height) = match self {
+            Icon::Path { width, height, .. } => (*width, *height),
+            Icon::Bitmap { width, height, .. } => (*width as f32, *height as f32),
+        };
+        if height > 0.0 { width / height } else { 1.0 }
+    }
+}
+
+/// Returns the character referencing the icon registered with the given id.
+///
+/// Insert it into text content to render the icon inline, e.g. `format!("{} Signals", icon_char(EXPAND))`.
+pub fn icon_char(id: u16) -> char {
+    char::from_u32(ICON_CHAR_BASE + id as u32).expect("icon characters are valid private use characters")
+}
+
+/// Returns the icon id referenced by the character, if it is an icon character.
+pub(crate) fn icon_id(character: char) -> Option<u16> {
+    (character as u32).checked_sub(ICON_CHAR_BASE).and_then(|id| u16::try_from(id).ok())
+}
+
+/// A command of a parsed SVG path, with absolute coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PathCommand {
+    MoveTo { x: f32, y: f32 },
+    LineTo { x: f32, y: f32 },
+    QuadraticTo { x1: f32, y1: f32, x: f32, y: f32 },
+    CubicTo { x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32 },
+    ArcTo { rx: f32, ry: f32, x_axis_rotation: f32, large_arc: bool, sweep: bool, x: f32, y: f32 },
+    Close,
+}
+
+/// Parses SVG path data into absolute path commands.
+///
+/// Supports all SVG path commands (M, L, H, V, C, S, Q, T, A, Z), relative and absolute.
+pub(crate) fn parse_svg_path(data: &str) -> Result<Vec<PathCommand>, String> {
+    let mut parser = PathParser { data: data.as_bytes(), position: 0 };
+    let mut commands = Vec::new();
+    let (mut x, mut y) = (0.0, 0.0);
+    let (mut start_x, mut start_y) = (0.0, 0.0);
+    // Control point of the previous curve, reflected by the smooth curve commands
+    let mut last_control: Option<(f32, f32)> = None;
+    let mut command = None;
+
+    loop {
+        parser.skip_separators();
+        let Some(next) = parser.peek() else { break };
+        if next.is_ascii_alphabetic() {
+            command = Some(next);
+            parser.position += 1;
+        } else if command.is_none() {
+            return Err(format!("Path data must start with a command at byte {}", parser.position));
+        }
+        let letter = command.unwrap_or(b'M');
+        let relative = letter.is_ascii_lowercase();
+        let (offset_x, offset_y) = if relative { (x, y) } else { (0.0, 0.0) };
+        let previous_control = last_control.take();
+
+        match letter.to_ascii_uppercase() {
+            b'M' => {
+                x = offset_x + parser.number()?;
+                y = offset_y + parser.number()?;
+                (start_x, start_y) = (x, y);
+                commands.push(PathCommand::MoveTo { x, y });
+                // Coordinates following a move are implicit line commands
+                command = Some(if relative { b'l' } else { b'L' });
+            }
+            b'L' => {
+                x = offset_x + parser.number()?;
+                y = offset_y + parser.number()?;
+                commands.push(PathCommand::LineTo { x, y });
+            }
+            b'H' => {
+                x = offset_x + parser.number()?;
+                commands.push(PathCommand::LineTo { x, y });
+            }
+            b'V' => {
+                y = offset_y + parser.number()?;
+                commands.push(PathCommand::LineTo { x, y });
+            }
+            upper @ (b'C' | b'S') => {
+                let (x1, y1) = if upper == b'C' {
+                    (offset_x + parser.number()?, offset_y + parser.number()?)
+                } else {
+                    previous_control.map_or((x, y), |(control_x, control_y)| (2.0 * x - control_x, 2.0 * y - control_y))
+                };
+                let x2 = offset_x + parser.number()?;
+                let y2 = offset_y + parser.number()?;
+                x = offset_x + parser.number()?;
+                y = offset_y + parser.number()?;
+                commands.push(PathCommand::CubicTo { x1, y1, x2, y2, x, y });
+                last_control = Some((x2, y2));
+            }
+            upper @ (b'Q' | b'T') => {
+                let (x1, y1) = if upper == b'Q' {
+                    (offset_x + parser.number()?, offset_y + parser.number()?)
+                } else {
+                    previous_control.map_or((x, y), |(control_x, control_y)| (2.0 * x - control_x, 2.0 * y - control_y))
+                };
+                x = offset_x + parser.number()?;
+                y = offset_y + parser.number()?;
+                commands.push(PathCommand::QuadraticTo { x1, y1, x, y });
+                last_control = Some((x1, y1));
+            }
+            b'A' => {
+                let rx = parser.number()?;
+                let ry = parser.number()?;
+                let x_axis_rotation = parser.number()?;
+                let large_arc = parser.flag()?;
+                let sweep = parser.flag()?;
+                x = offset_x + parser.number()?;
+                y = offset_y + parser.number()?;
+                commands.push(PathCommand::ArcTo { rx, ry, x_axis_rotation, large_arc, sweep, x, y });
+            }
+            b'Z' => {
+                (x, y) = (start_x, start_y);
+                commands.push(PathCommand::Close);
+                // Z takes no arguments, the next command must be explicit
+                command = None;
+            }
+            _ => return Err(format!("Unknown path command '{}'", letter as char)),
+        }
+    }
+
+    if commands.is_empty() {
+        return Err("Path data is empty".to_owned());
+    }
+    Ok(commands)
+}
+
+/// Reads numbers and flags from SVG path data.
+struct PathParser<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl PathParser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    fn skip_separators(&mut self) {
+        while self.peek().is_some_and(|byte| byte.is_ascii_whitespace() || byte == b',') {
+            self.position += 1;
+        }
+    }
+
+    fn number(&mut self) -> Result<f32, String> {
+        self.skip_separators();
+        let start = self.position;
+        if matches!(self.peek(), Some(b'+' | b'-')) {
+            self.position += 1;
+        }
+        let mut seen_dot = false;
+        let mut seen_exponent = false;
+        while let Some(byte) = self.peek() {
+            match byte {
+                b'0'..=b'9' => {}
+                // A second dot starts the next number, e.g. "0.5.5"
+                b'.' if !seen_dot && !seen_exponent => seen_dot = true,
+                b'e' | b'E' if !seen_exponent && self.position > start => {
+                    seen_exponent = true;
+                    if matches!(self.data.get(self.position + 1), Some(b'+' | b'-')) {
+                        self.position += 1;
+                    }
+                }
+                _ => break,
+            }
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.position])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .ok_or_else(|| format!("Expected a number at byte {start}"))
+    }
+
+    /// Arc flags may be written without separators, e.g. "a1 1 0 011 1".
+    fn flag(&mut self) -> Result<bool, String> {
+        self.skip_separators();
+        let flag = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(format!("Expected an arc flag at byte {}", self.position)),
+        };
+        self.position += 1;
+        Ok(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PathCommand::*;
+
+    #[test]
+    fn icon_chars_map_back_to_their_ids() {
+        assert_eq!(icon_id(icon_char(0)), Some(0));
+        assert_eq!(icon_id(icon_char(u16::MAX)), Some(u16::MAX));
+        assert_eq!(icon_id('a'), None);
+        assert_eq!(icon_id(char::from_u32(ICON_CHAR_BASE + 0x10000).unwrap()), None);
+    }
+
+    #[test]
+    fn aspect_ratio_falls_back_to_square() {
+        assert_eq!(Icon::svg_path("M0 0", 24.0, 12.0).aspect_ratio(), 2.0);
+        assert_eq!(Icon::bitmap(16, 0, Vec::new()).aspect_ratio(), 1.0);
+    }
+
+    #[test]
+    fn relative_commands_continue_from_the_current_point() {
+        assert_eq!(parse_svg_path("M10 10 l5 0 h5 v-5 L0 0 H3 V4").unwrap(), vec![
+            MoveTo { x: 10.0, y: 10.0 },
+            LineTo { x: 15.0, y: 10.0 },
+            LineTo { x: 20.0, y: 10.0 },
+            LineTo { x: 20.0, y: 5.0 },
+            LineTo { x: 0.0, y: 0.0 },
+            LineTo { x: 3.0, y: 0.0 },
+            LineTo { x: 3.0, y: 4.0 },
+        ]);
+    }
+
+    #[test]
+    fn coordinates_after_a_move_are_lines() {
+        assert_eq!(parse_svg_path("m1 1 2 2 3 3").unwrap(), vec![
+            MoveTo { x: 1.0, y: 1.0 },
+            LineTo { x: 3.0, y: 3.0 },
+            LineTo { x: 6.0, y: 6.0 },
+        ]);
+    }
+
+    #[test]
+    fn close_returns_to_the_start_of_the_subpath() {
+        assert_eq!(parse_svg_path("M1 1 L5 1 Z l1 1").unwrap(), vec![
+            MoveTo { x: 1.0, y: 1.0 },
+            LineTo { x: 5.0, y: 1.0 },
+            Close,
+            LineTo { x: 2.0, y: 2.0 },
+        ]);
+        assert!(parse_svg_path("M0 0 Z 1 1").is_err());
+    }
+
+    #[test]
+    fn smooth_curves_reflect_the_previous_control_point() {
+        assert_eq!(parse_svg_path("M0 0 C0 10 10 10 10 0 S20 -10 20 0").unwrap()[2], CubicTo { x1: 10.0, y1: -10.0, x2: 20.0, y2: -10.0, x: 20.0, y: 0.0 });
+        assert_eq!(parse_svg_path("M0 0 Q5 10 10 0 t10 0").unwrap()[2], QuadraticTo { x1: 15.0, y1: -10.0, x: 20.0, y: 0.0 });
+        // Without a previous curve the control point is the current point
+        assert_eq!(parse_svg_path("M1 2 T3 4").unwrap()[1], QuadraticTo { x1: 1.0, y1: 2.0, x: 3.0, y: 4.0 });
+        assert_eq!(parse_svg_path("M0 0 L1 1 S2 2 3 3").unwrap()[2], CubicTo { x1: 1.0, y1: 1.0, x2: 2.0, y2: 2.0, x: 3.0, y: 3.0 });
+    }
+
+    #[test]
+    fn arcs_accept_flags_without_separators() {
+        assert_eq!(parse_svg_path("M0 0 a1 1 0 011 1").unwrap()[1], ArcTo {
+            rx: 1.0, ry: 1.0, x_axis_rotation: 0.0, large_arc: false, sweep: true, x: 1.0, y: 1.0,
+        });
+        assert!(parse_svg_path("M0 0 A1 1 0 2 1 1 1").is_err());
+    }
+
+    #[test]
+    fn numbers_may_run_together() {
+        assert_eq!(parse_svg_path("M.5.5L-1-1e1,2E-1+3").unwrap(), vec![
+            MoveTo { x: 0.5, y: 0.5 },
+            LineTo { x: -1.0, y: -10.0 },
+            LineTo { x: 0.2, y: 3.0 },
+        ]);
+    }
+
+    #[test]
+    fn invalid_path_data_is_rejected() {
+        assert!(parse_svg_path("").is_err());
+        assert!(parse_svg_path("  ").is_err());
+        assert!(parse_svg_path("10 10").is_err());
+        assert!(parse_svg_path("M0 0 X1 1").is_err());
+        assert!(parse_svg_path("M0").is_err());
+        assert!(parse_svg_path("M0 0 L1 -").is_err());
+    }
+}
@@ -1,5 +1,6 @@
+use std::borrow::Cow;
 use crate::backend::{Point, Color};
-use super::Object2d;
+use super::{Object2d, Transform};
 
 /// A line shape defined by a sequence of points.
 #[derive(Debug, Clone)]
@@ -10,6 +11,10 @@ pub struct Line {
     pub(crate) width: f32,
     /// The color of the line.
     pub(crate) color: Color,
+    /// The transform applied to the line.
+    pub(crate) transform: Transform,
+    /// Whether the points are reduced to a few points per pixel column when drawn.
+    pub(crate) lod: bool,
 }
 
 impl Default for Line {
@@ -18,6 +23,8 @@ impl Default for Line {
             points: Vec::new(),
             width: 1.0,
             color: Color::default(),
+            transform: Transform::IDENTITY,
+            lod: false,
         }
     }
 }
@@ -45,6 +52,93 @@ impl Line {
         self.color = Color::new(r, g, b, a);
         self
     }
+
+    /// Sets the transform applied to the line, e.g. `Transform::rotate_around(angle, x, y)`.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Enables level of detail for lines with many more points than pixels, e.g. long signals.
+    ///
+    /// The points in each pixel column are reduced to the first, last, topmost and bottommost of them
+    /// at the current zoom, which draws the same pixels with at most four points per column.
+    /// Meant for lines whose points advance along the x axis, other lines are reduced less.
+    pub fn lod(mut self, enabled: bool) -> Self {
+        self.lod = enabled;
+        self
+    }
+
+    /// Returns the points to draw with `to_pixels` mapping the line to screen pixels,
+    /// reduced to the first, last, topmost and bottommost point of each pixel column if level of detail is enabled.
+    pub(crate) fn lod_points(&self, to_pixels: &Transform) -> Cow<'_, [Point]> {
+        if !self.lod || self.points.len() <= 4 {
+            return Cow::Borrowed(&self.points);
+        }
+        let mut reduced = Vec::new();
+        let mut column: Option<PixelColumn> = None;
+        for (index, point) in self.points.iter().enumerate() {
+            let (x, y) = to_pixels.transform_point(point.x, point.y);
+            let x = x.floor();
+            match &mut column {
+                Some(current) if current.x == x => current.add(index, y),
+                _ => {
+                    if let Some(previous) = column.replace(PixelColumn::new(x, index, y)) {
+                        previous.push_points(&self.points, &mut reduced);
+                    }
+                }
+            }
+        }
+        if let Some(last) = column {
+            last.push_points(&self.points, &mut reduced);
+        }
+        if reduced.len() == self.points.len() {
+            Cow::Borrowed(&self.points)
+        } else {
+            Cow::Owned(reduced)
+        }
+    }
+}
+
+/// Consecutive points of a line within one pixel column, by their index and screen y coordinate.
+struct PixelColumn {
+    /// The left edge of the column in screen pixels
+    x: f32,
+    first: usize,
+    last: usize,
+    min_y: (usize, f32),
+    max_y: (usize, f32),
+}
+
+impl PixelColumn {
+    /// Creates a column with its first point.
+    fn new(x: f32, index: usize, y: f32) -> Self {
+        Self { x, first: index, last: index, min_y: (index, y), max_y: (index, y) }
+    }
+
+    /// Adds the next point of the line within the column.
+    fn add(&mut self, index: usize, y: f32) {
+        self.last = index;
+        if y < self.min_y.1 {
+            self.min_y = (index, y);
+        }
+        if y > self.max_y.1 {
+            self.max_y = (index, y);
+        }
+    }
+
+    /// Pushes the first, last, topmost and bottommost point of the column in the order of the line, each once.
+    fn push_points(&self, points: &[Point], reduced: &mut Vec<Point>) {
+        let mut indices = [self.first, self.min_y.0, self.max_y.0, self.last];
+        indices.sort_unstable();
+        let mut previous = None;
+        for index in indices {
+            if previous != Some(index) {
+                reduced.push(points[index]);
+                previous = Some(index);
+            }
+        }
+    }
 }
 
 /// Converts a Line into an Object2d.
@@ -53,3 +147,70 @@ impl From<Line> for Object2d {
         Object2d::Line(line)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xs(points: &[Point]) -> Vec<(f32, f32)> {
+        points.iter().map(|point| (point.x, point.y)).collect()
+    }
+
+    #[test]
+    fn lod_points_keep_the_extremes_of_each_pixel_column() {
+        // Ten points per pixel, the extremes in the middle of the column
+        let points: Vec<(f32, f32)> = (0..20).map(|i| (i as f32 / 10.0, [0.0, 5.0, -5.0][i % 3])).collect();
+        let line = Line::new().points(&points).lod(true);
+        let reduced = line.lod_points(&Transform::IDENTITY);
+        assert_eq!(xs(&reduced), vec![(0.0, 0.0), (0.1, 5.0), (0.2, -5.0), (0.9, 0.0), (1.0, 5.0), (1.1, -5.0), (1.9, 5.0)]);
+    }
+
+    #[test]
+    fn lod_points_follow_the_transform_to_pixels() {
+        let points: Vec<(f32, f32)> = (0..8).map(|i| (i as f32, i as f32)).collect();
+        let line = Line::new().points(&points).lod(true);
+        // One pixel per point keeps every point
+        assert!(matches!(line.lod_points(&Transform::IDENTITY), Cow::Borrowed(_)));
+        // Zoomed out, all points fall into the first column
+        let reduced = line.lod_points(&Transform::scale(0.1, 1.0));
+        assert_eq!(xs(&reduced), vec![(0.0, 0.0), (7.0, 7.0)]);
+    }
+
+    #[test]
+    fn lod_points_are_unchanged_without_lod_or_with_few_points() {
+        let line = Line::new().points(&[(0.0, 0.0), (0.1, 1.0), (0.2, 2.0), (0.3, 3.0), (0.4, 4.0)]);
+        assert_eq!(line.lod_points(&Transform::IDENTITY).len(), 5);
+        let line = Line::new().points(&[(0.0, 0.0), (0.1, 1.0), (0.2, 2.0), (0.3, 3.0)]).lod(true);
+        assert_eq!(line.lod_points(&Transform::IDENTITY).len(), 4);
+    }
+
+    #[test]
+    fn lod_points_keep_lines_whose_x_is_not_monotonic() {
+        // A line going back and forth between two columns is reduced per run of points in a column,
+        // so every turn is kept
+        let points: Vec<(f32, f32)> = (0..12).map(|i| (if i % 4 < 2 { 0.5 } else { 1.5 }, i as f32)).collect();
+        let line = Line::new().points(&points).lod(true);
+        assert_eq!(xs(&line.lod_points(&Transform::IDENTITY)), points);
+    }
+
+    #[test]
+    fn pixel_column_pushes_each_point_once_in_line_order() {
+        let points: Vec<Point> = (0..5).map(|i| Point { x: 0.0, y: i as f32 }).collect();
+        let mut column = PixelColumn::new(0.0, 0, 0.0);
+        for (index, point) in points.iter().enumerate().skip(1) {
+            column.add(index, point.y);
+        }
+        let mut reduced = Vec::new();
+        column.push_points(&points, &mut reduced);
+        // The first point is the topmost and the last the bottommost
+        assert_eq!(xs(&reduced), vec![(0.0, 0.0), (0.0, 4.0)]);
+
+        let mut column = PixelColumn::new(0.0, 0, 2.0);
+        column.add(1, 0.0);
+        column.add(2, 4.0);
+        column.add(3, 1.0);
+        let mut reduced = Vec::new();
+        column.push_points(&points, &mut reduced);
+        assert_eq!(xs(&reduced), vec![(0.0, 0.0), (0.0, 1.0), (0.0, 2.0), (0.0, 3.0)]);
+    }
+}
@@ -0,0 +1,138 @@
+use std::borrow::Cow;
+use crate::backend::{Point, Size, RoundedCorners};
+use super::PathCommand;
+use super::parse_svg_path;
+
+/// A shape masking the children of a [`Group`](super::Group), only the parts inside the shape are drawn.
+///
+/// Used e.g. for rounded panels and circular avatars. The shape is defined in the group's coordinates.
+#[derive(Debug, Clone)]
+pub struct Mask {
+    pub(crate) shape: MaskShape,
+}
+
+/// The shape of a mask.
+#[derive(Debug, Clone)]
+pub(crate) enum MaskShape {
+    Rectangle {
+        position: Point,
+        size: Size,
+        rounded_corners: RoundedCorners,
+    },
+    Circle {
+        center: Point,
+        radius: f32,
+    },
+    Path {
+        /// SVG path data, filled directly by the canvas backend
+        #[cfg(feature = "canvas")]
+        data: Cow<'static, str>,
+        /// Parsed path commands for the GPU backends and hit testing
+        commands: Vec<PathCommand>,
+    },
+}
+
+impl Mask {
+    /// Creates a rectangular mask, see [`Mask::rounded_corners`].
+    pub fn rectangle(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            shape: MaskShape::Rectangle {
+                position: Point { x, y },
+                size: Size { width: width.max(0.0), height: height.max(0.0) },
+                rounded_corners: RoundedCorners::default(),
+            },
+        }
+    }
+
+    /// Sets the radii for the corners of a rectangular mask, e.g. for a rounded panel.
+    ///
+    /// Has no effect on circle and path masks.
+    pub fn rounded_corners(mut self, top_left: f32, top_right: f32, bottom_left: f32, bottom_right: f32) -> Self {
+        if let MaskShape::Rectangle { rounded_corners, .. } = &mut self.shape {
+            *rounded_corners = RoundedCorners {
+                top_left: top_left.max(0.0),
+                top_right: top_right.max(0.0),
+                bottom_left: bottom_left.max(0.0),
+                bottom_right: bottom_right.max(0.0),
+            };
+        }
+        self
+    }
+
+    /// Creates a circular mask.
+    pub fn circle(center_x: f32, center_y: f32, radius: f32) -> Self {
+        Self { shape: MaskShape::Circle { center: Point { x: center_x, y: center_y }, radius: radius.max(0.0) } }
+    }
+
+    /// Creates a mask from SVG path data (the `d` attribute of an SVG `<path>` element), filled with the nonzero rule.
+    pub fn svg_path(data: impl Into<Cow<'static, str>>) -> Result<Self, MaskError> {
+        let data = data.into();
+        let commands = parse_svg_path(&data).map_err(MaskError::InvalidPath)?;
+        Ok(Self {
+            shape: MaskShape::Path {
+                #[cfg(feature = "canvas")]
+                data,
+                commands,
+            },
+        })
+    }
+
+    /// Returns the bounding box `(x, y, width, height)` of the shape, curves are bounded by their control points.
+    pub(crate) fn bounds(&self) -> (f32, f32, f32, f32) {
+        match &self.shape {
+            MaskShape::Rectangle { position, size, .. } => (position.x, position.y, size.width, size.height),
+            MaskShape::Circle { center, radius } => (center.x - radius, center.y - radius, radius * 2.0, radius * 2.0),
+            MaskShape::Path { commands, .. } => {
+                let mut points: Vec<(f32, f32)> = Vec::new();
+                let mut current = (0.0f32, 0.0f32);
+                let mut start = (0.0f32, 0.0f32);
+                for command in commands {
+                    match *command {
+                        PathCommand::MoveTo { x, y } => {
+                            points.push((x, y));
+                            start = (x, y);
+                        }
+                        PathCommand::LineTo { x, y } => points.push((x, y)),
+                        PathCommand::QuadraticTo { x1, y1, x, y } => points.extend([(x1, y1), (x, y)]),
+                        PathCommand::CubicTo { x1, y1, x2, y2, x, y } => points.extend([(x1, y1), (x2, y2), (x, y)]),
+                        // An arc stays within its radii around the segment end points
+                        PathCommand::ArcTo { rx, ry, x, y, .. } => {
+                            let radius = rx.abs().max(ry.abs());
+                            points.extend([
+                                (current.0.min(x) - radius, current.1.min(y) - radius),
+                                (current.0.max(x) + radius, current.1.max(y) + radius),
+                                (x, y),
+                            ]);
+                        }
+                        PathCommand::Close => {
+                            current = start;
+                            continue;
+                        }
+                    }
+                    current = points[points.len() - 1];
+                }
+                let (left, top, right, bottom) = points.iter().fold(
+                    (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+                    |(left, top, right, bottom), (x, y)| (left.min(*x), top.min(*y), right.max(*x), bottom.max(*y)),
+                );
+                (left, top, (right - left).max(0.0), (bottom - top).max(0.0))
+            }
+        }
+    }
+}
+
+/// Errors that can happen when creating a [`Mask`].
+#[derive(Debug)]
+pub enum MaskError {
+    InvalidPath(String),
+}
+
+impl std::fmt::Display for MaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidPath(error) => write!(f, "Invalid path data: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for MaskError {}
@@ -1,5 +1,5 @@
 use crate::backend::{Point, Size, Color, RoundedCorners};
-use super::Object2d;
+use super::{Object2d, Transform};
 
 /// A rectangle shape with optional border and rounded corners.
 #[derive(Debug, Clone, Copy)]
@@ -16,6 +16,8 @@ pub struct Rectangle {
     pub(crate) border_width: Option<f32>,
     /// The color of the border, if any.
     pub(crate) border_color: Option<Color>,
+    /// The transform applied to the rectangle.
+    pub(crate) transform: Transform,
 }
 
 impl Default for Rectangle {
@@ -27,6 +29,7 @@ impl Default for Rectangle {
             rounded_corners: RoundedCorners::default(),
             border_width: None,
             border_color: None,
+            transform: Transform::IDENTITY,
         }
     }
 }
@@ -72,6 +75,12 @@ impl Rectangle {
         self.border_color = Some(Color::new(r, g, b, a));
         self
     }
+
+    /// Sets the transform applied to the rectangle, e.g. `Transform::rotate_around(angle, x, y)`.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
 }
 
 /// Converts a Rectangle into an Object2d.
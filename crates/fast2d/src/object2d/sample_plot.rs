@@ -0,0 +1,232 @@
+use std::ops::Range;
+use std::sync::Arc;
+use crate::backend::{Point, Color};
+use super::{Line, Object2d, Transform};
+
+/// How the samples of a [`SamplePlot`] are connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlotStyle {
+    /// Straight lines between consecutive samples.
+    #[default]
+    Polyline,
+    /// Each sample keeps its value until the next sample, with a vertical step to its value.
+    Step,
+}
+
+/// A plot of evenly spaced samples, e.g. a long analog signal.
+///
+/// Sample `i` is drawn at `x + i * x_step` and `y + value * y_scale`.
+/// The wgpu backends upload the samples to a GPU storage buffer once and draw the plot in a shader,
+/// so panning and zooming cost no geometry work on the CPU. Plots sharing the same samples,
+/// e.g. clones of a plot drawn again with another color, share the uploaded buffer too.
+/// Other backends and devices without storage buffers draw the plot as [`Line`]s with level of detail.
+#[derive(Debug, Clone)]
+pub struct SamplePlot {
+    /// The sample values, uploaded to the GPU once.
+    pub(crate) samples: Arc<[f32]>,
+    /// The smallest and largest finite sample value.
+    pub(crate) value_range: (f32, f32),
+    /// The position of the first sample with the value 0.
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    /// The horizontal distance between consecutive samples.
+    pub(crate) x_step: f32,
+    /// The vertical distance of a sample value of 1.
+    pub(crate) y_scale: f32,
+    /// How consecutive samples are connected.
+    pub(crate) style: PlotStyle,
+    /// The width of the line.
+    pub(crate) width: f32,
+    /// The color of the line.
+    pub(crate) color: Color,
+    /// The transform applied to the plot.
+    pub(crate) transform: Transform,
+}
+
+impl SamplePlot {
+    /// Creates a plot of the samples, one unit apart starting at the origin.
+    ///
+    /// Pass an `Arc<[f32]>` to share the samples with other plots without copying them.
+    pub fn new(samples: impl Into<Arc<[f32]>>) -> Self {
+        let samples: Arc<[f32]> = samples.into();
+        let value_range = samples.iter()
+            .filter(|value| value.is_finite())
+            .fold((f32::MAX, f32::MIN), |(min, max), value| (min.min(*value), max.max(*value)));
+        Self {
+            samples,
+            value_range,
+            x: 0.0,
+            y: 0.0,
+            x_step: 1.0,
+            y_scale: 1.0,
+            style: PlotStyle::default(),
+            width: 1.0,
+            color: Color::default(),
+            transform: Transform::IDENTITY,
+        }
+    }
+
+    /// Sets the position of the first sample with the value 0.
+    pub fn position(mut self, x: f32, y: f32) -> Self {
+        self.x = x;
+        self.y = y;
+        self
+    }
+
+    /// Sets the horizontal distance between consecutive samples.
+    pub fn x_step(mut self, x_step: f32) -> Self {
+        self.x_step = x_step;
+        self
+    }
+
+    /// Sets the vertical distance of a sample value of 1, negative to draw larger values higher up.
+    pub fn y_scale(mut self, y_scale: f32) -> Self {
+        self.y_scale = y_scale;
+        self
+    }
+
+    /// Sets how consecutive samples are connected.
+    pub fn style(mut self, style: PlotStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Sets the width of the line.
+    pub fn width(mut self, width: f32) -> Self {
+        self.width = width.max(0.0);
+        self
+    }
+
+    /// Sets the color of the line.
+    pub fn color(mut self, r: u8, g: u8, b: u8, a: f32) -> Self {
+        self.color = Color::new(r, g, b, a);
+        self
+    }
+
+    /// Sets the transform applied to the plot, e.g. `Transform::scale(zoom, 1.0)`.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Returns the position of the sample at the index, before the transform.
+    pub(crate) fn sample_point(&self, index: usize) -> Point {
+        Point { x: self.x + index as f32 * self.x_step, y: self.y + self.samples[index] * self.y_scale }
+    }
+
+    /// Returns the indices of the samples drawn within the x range of the area given before the transform,
+    /// with one more sample on each side, so the lines leaving the area are drawn too.
+    pub(crate) fn sample_range(&self, left: f32, right: f32) -> Range<usize> {
+        if self.x_step == 0.0 || !left.is_finite() || !right.is_finite() {
+            return 0..self.samples.len();
+        }
+        let (start, end) = ((left - self.x) / self.x_step, (right - self.x) / self.x_step);
+        let (start, end) = (start.min(end).floor() - 1.0, start.max(end).ceil() + 2.0);
+        let count = self.samples.len() as f32;
+        start.clamp(0.0, count) as usize..end.clamp(0.0, count) as usize
+    }
+
+    /// Returns the samples in the range as lines with level of detail, for backends drawing the plot on the CPU.
+    ///
+    /// Samples without a value break the plot into separate lines, like the shader leaves a gap for them.
+    pub(crate) fn to_lines(&self, range: Range<usize>) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut points: Vec<Point> = Vec::new();
+        for index in range {
+            if !self.samples[index].is_finite() {
+                lines.push(std::mem::take(&mut points));
+                continue;
+            }
+            let point = self.sample_point(index);
+            // A step holds the value of the previous sample until the sample
+            if self.style == PlotStyle::Step && let Some(previous) = points.last() {
+                points.push(Point { x: point.x, y: previous.y });
+            }
+            points.push(point);
+        }
+        lines.push(points);
+        // A single sample has no segment to draw
+        lines.into_iter()
+            .filter(|points| points.len() >= 2)
+            .map(|points| Line { points, width: self.width, color: self.color, transform: self.transform, lod: true })
+            .collect()
+    }
+}
+
+/// Converts a SamplePlot into an Object2d.
+impl From<SamplePlot> for Object2d {
+    fn from(plot: SamplePlot) -> Self {
+        Object2d::SamplePlot(plot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(line: &Line) -> Vec<(f32, f32)> {
+        line.points.iter().map(|point| (point.x, point.y)).collect()
+    }
+
+    #[test]
+    fn sample_range_covers_the_area_with_a_sample_on_each_side() {
+        let plot = SamplePlot::new(vec![0.0; 100]).position(10.0, 0.0).x_step(2.0);
+        assert_eq!(plot.sample_range(20.0, 30.0), 4..12);
+        // The edges may be given in any order
+        assert_eq!(plot.sample_range(30.0, 20.0), 4..12);
+        assert_eq!(plot.sample_range(-100.0, 1000.0), 0..100);
+    }
+
+    #[test]
+    fn sample_range_is_empty_outside_the_samples() {
+        let plot = SamplePlot::new(vec![0.0; 100]).position(10.0, 0.0).x_step(2.0);
+        assert!(plot.sample_range(-100.0, -50.0).is_empty());
+        assert!(plot.sample_range(500.0, 600.0).is_empty());
+    }
+
+    #[test]
+    fn sample_range_follows_negative_steps() {
+        let plot = SamplePlot::new(vec![0.0; 100]).position(0.0, 0.0).x_step(-1.0);
+        assert_eq!(plot.sample_range(-10.0, -5.0), 4..12);
+    }
+
+    #[test]
+    fn sample_range_covers_all_samples_for_degenerate_input() {
+        let plot = SamplePlot::new(vec![0.0; 10]).x_step(0.0);
+        assert_eq!(plot.sample_range(0.0, 1.0), 0..10);
+        let plot = SamplePlot::new(vec![0.0; 10]);
+        assert_eq!(plot.sample_range(f32::NAN, 1.0), 0..10);
+        assert_eq!(plot.sample_range(0.0, f32::INFINITY), 0..10);
+    }
+
+    #[test]
+    fn lines_are_broken_at_missing_samples() {
+        let plot = SamplePlot::new(vec![1.0, 2.0, f32::NAN, 3.0, f32::INFINITY, 4.0, 5.0]);
+        let lines = plot.to_lines(0..7);
+        // The single sample between the missing ones has no segment
+        assert_eq!(lines.iter().map(points).collect::<Vec<_>>(), vec![
+            vec![(0.0, 1.0), (1.0, 2.0)],
+            vec![(5.0, 4.0), (6.0, 5.0)],
+        ]);
+        assert!(lines.iter().all(|line| line.lod));
+        assert!(SamplePlot::new(vec![f32::NAN; 3]).to_lines(0..3).is_empty());
+    }
+
+    #[test]
+    fn lines_cover_only_the_range() {
+        let plot = SamplePlot::new(vec![0.0, 1.0, 2.0, 3.0]).position(5.0, 10.0).y_scale(-1.0);
+        let lines = plot.to_lines(1..3);
+        assert_eq!(lines.iter().map(points).collect::<Vec<_>>(), vec![vec![(6.0, 9.0), (7.0, 8.0)]]);
+        assert!(plot.to_lines(0..0).is_empty());
+    }
+
+    #[test]
+    fn step_lines_hold_the_previous_value() {
+        let plot = SamplePlot::new(vec![0.0, 2.0, f32::NAN, 1.0, 3.0]).style(PlotStyle::Step);
+        let lines = plot.to_lines(0..5);
+        assert_eq!(lines.iter().map(points).collect::<Vec<_>>(), vec![
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 2.0)],
+            vec![(3.0, 1.0), (4.0, 1.0), (4.0, 3.0)],
+        ]);
+    }
+}
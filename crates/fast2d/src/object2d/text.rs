@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 use crate::backend::Color;
-use super::Object2d;
+use super::{Object2d, Transform};
 
 mod family;
 pub use family::Family;
@@ -22,8 +22,8 @@ pub struct Text {
     pub(crate) line_height_multiplier: f32,
     /// The fill color of the text.
     pub(crate) color: Color,
-    /// The font family used for rendering.
-    pub(crate) family: Family,
+    /// The font families used for rendering, in fallback order (never empty).
+    pub(crate) families: Vec<Family>,
     /// The maximum width for text layout.
     pub(crate) width: f32,
     /// The maximum height for text layout.
@@ -32,6 +32,8 @@ pub struct Text {
     pub(crate) italic: bool,
     /// The font weight (thickness).
     pub(crate) weight: FontWeight,
+    /// The transform applied to the text.
+    pub(crate) transform: Transform,
 }
 
 impl Default for Text {
@@ -43,11 +45,12 @@ impl Default for Text {
             font_size: 16.0,
             line_height_multiplier: 1.0,
             color: Color::default(),
-            family: Family::SansSerif,
+            families: vec![Family::SansSerif],
             width: f32::MAX,
             height: f32::MAX,
             italic: false,
             weight: FontWeight::Regular,
+            transform: Transform::IDENTITY,
         }
     }
 }
@@ -89,9 +92,31 @@ impl Text {
         self
     }
 
+    /// Appends an inline icon registered with [`register_icon`](crate::register_icon) to the text content.
+    ///
+    /// The icon is as tall as the font size, e.g. `Text::new().text("Expand ").icon(EXPAND_ICON)`.
+    /// Use [`icon_char`](crate::icon_char) to place icons anywhere in the content.
+    pub fn icon(mut self, id: u16) -> Self {
+        self.text.to_mut().push(super::icon_char(id));
+        self
+    }
+
     /// Sets the font family.
     pub fn family(mut self, family: Family) -> Self {
-        self.family = family;
+        self.families = vec![family];
+        self
+    }
+
+    /// Sets an ordered list of font families used as a fallback chain.
+    ///
+    /// Each character is rendered with the first family that contains its glyph,
+    /// so e.g. `[Family::name("Inter"), Family::name("Noto Sans CJK"), Family::SansSerif]`
+    /// renders mixed Latin and CJK labels correctly. An empty list means [`Family::SansSerif`].
+    pub fn families(mut self, families: impl IntoIterator<Item = Family>) -> Self {
+        self.families = families.into_iter().collect();
+        if self.families.is_empty() {
+            self.families.push(Family::SansSerif);
+        }
         self
     }
 
@@ -113,6 +138,16 @@ impl Text {
         self.weight = weight;
         self
     }
+
+    /// Sets the transform applied to the text, e.g. `Transform::rotate_around(angle, x, y)`.
+    ///
+    /// The text is laid out first and the transform is applied to the whole text box.
+    /// On the wgpu backends, rotated, skewed or non-uniformly scaled text is drawn from glyph outlines,
+    /// without inline icons and with monochrome emoji.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
 }
 
 /// Converts a Text object into an Object2d.
@@ -144,3 +179,20 @@ pub enum FontWeight {
     /// Black (900)
     Black,
 }
+
+impl FontWeight {
+    /// Returns the numeric weight (100 - 900), as used by CSS and OpenType.
+    pub fn value(&self) -> u16 {
+        match self {
+            FontWeight::Thin => 100,
+            FontWeight::ExtraLight => 200,
+            FontWeight::Light => 300,
+            FontWeight::Regular => 400,
+            FontWeight::Medium => 500,
+            FontWeight::SemiBold => 600,
+            FontWeight::Bold => 700,
+            FontWeight::ExtraBold => 800,
+            FontWeight::Black => 900,
+        }
+    }
+}
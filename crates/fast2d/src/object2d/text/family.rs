@@ -34,6 +34,20 @@ impl Family {
         Family::Name(s.into())
     }
 
+    /// Returns the font family as an entry of a CSS `font-family` list.
+    ///
+    /// Named families are quoted, generic families are returned as keywords.
+    pub fn to_css(&self) -> String {
+        match self {
+            Family::Name(name) => format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\"")),
+            generic => generic.as_str().to_string(),
+        }
+    }
+
+    /// Joins the font families into a CSS `font-family` list (e.g. `"Inter", "Noto Sans CJK", sans-serif`).
+    pub fn css_list(families: &[Family]) -> String {
+        families.iter().map(Family::to_css).collect::<Vec<_>>().join(", ")
+    }
 }
 
 /// Formats the font family as a string for display purposes.
@@ -0,0 +1,175 @@
+/// A 2D affine transform of object coordinates (translation, rotation, scale, skew).
+///
+/// Stored as a 2x3 matrix in the order used by the canvas `setTransform` method,
+/// a point `(x, y)` is mapped to `(a * x + c * y + e, b * x + d * y + f)`.
+/// Angles are in radians, positive angles rotate clockwise on screen (the y axis points down).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    /// The transform that leaves all points unchanged.
+    pub const IDENTITY: Self = Self { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    /// Creates a transform from a full 2x3 matrix in canvas `setTransform` order.
+    pub fn matrix(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    /// Creates a translation by `x` and `y` pixels.
+    pub fn translate(x: f32, y: f32) -> Self {
+        Self { e: x, f: y, ..Self::IDENTITY }
+    }
+
+    /// Creates a rotation around the origin (the top-left corner of the canvas).
+    pub fn rotate(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self { a: cos, b: sin, c: -sin, d: cos, e: 0.0, f: 0.0 }
+    }
+
+    /// Creates a rotation around the point `(x, y)`, e.g. the center of a shape.
+    pub fn rotate_around(angle: f32, x: f32, y: f32) -> Self {
+        Self::translate(-x, -y).then(Self::rotate(angle)).then(Self::translate(x, y))
+    }
+
+    /// Creates a scale relative to the origin.
+    pub fn scale(x: f32, y: f32) -> Self {
+        Self { a: x, d: y, ..Self::IDENTITY }
+    }
+
+    /// Creates a skew with the angles between the skewed and the original x and y axes.
+    pub fn skew(x_angle: f32, y_angle: f32) -> Self {
+        Self { b: y_angle.tan(), c: x_angle.tan(), ..Self::IDENTITY }
+    }
+
+    /// Returns a transform applying `self` first and then `next`.
+    ///
+    /// E.g. `Transform::scale(2.0, 2.0).then(Transform::translate(100.0, 0.0))` scales and then moves.
+    pub fn then(&self, next: Transform) -> Self {
+        Self {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    /// Returns the transform mapping transformed points back, or `None` if the transform collapses the plane.
+    /// Tiny scales still invert, as long as the inverse fits in `f32`.
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.a * self.d - self.b * self.c;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let a = self.d / determinant;
+        let b = -self.b / determinant;
+        let c = -self.c / determinant;
+        let d = self.a / determinant;
+        let inverse = Self { a, b, c, d, e: -(a * self.e + c * self.f), f: -(b * self.e + d * self.f) };
+        [inverse.a, inverse.b, inverse.c, inverse.d, inverse.e, inverse.f].iter().all(|value| value.is_finite()).then_some(inverse)
+    }
+
+    /// Maps the point `(x, y)` through the transform.
+    pub fn transform_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+
+    /// Returns true if the transform leaves all points unchanged.
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    /// Returns the translation and scale if the transform only translates and scales uniformly.
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native"))]
+    pub(crate) fn as_translate_scale(&self) -> Option<(f32, f32, f32)> {
+        (self.b == 0.0 && self.c == 0.0 && self.a == self.d && self.a > 0.0).then_some((self.e, self.f, self.a))
+    }
+
+    /// Returns the largest factor lengths are scaled by, used to keep curve tessellation smooth.
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+    pub(crate) fn max_scale(&self) -> f32 {
+        self.a.hypot(self.b).max(self.c.hypot(self.d))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+    use super::*;
+
+    fn assert_near((x, y): (f32, f32), expected: (f32, f32)) {
+        assert!((x - expected.0).abs() < 1e-4 && (y - expected.1).abs() < 1e-4, "{:?} is not near {expected:?}", (x, y));
+    }
+
+    #[test]
+    fn then_applies_self_first() {
+        let transform = Transform::scale(2.0, 3.0).then(Transform::translate(10.0, 20.0));
+        assert_eq!(transform.transform_point(1.0, 1.0), (12.0, 23.0));
+        let transform = Transform::translate(10.0, 20.0).then(Transform::scale(2.0, 3.0));
+        assert_eq!(transform.transform_point(1.0, 1.0), (22.0, 63.0));
+    }
+
+    #[test]
+    fn rotations_turn_clockwise_on_screen() {
+        // With y pointing down, a positive angle turns the x axis towards the y axis
+        assert_near(Transform::rotate(FRAC_PI_2).transform_point(1.0, 0.0), (0.0, 1.0));
+        assert_near(Transform::rotate_around(FRAC_PI_2, 5.0, 5.0).transform_point(10.0, 5.0), (5.0, 10.0));
+        assert_near(Transform::rotate_around(FRAC_PI_2, 5.0, 5.0).transform_point(5.0, 5.0), (5.0, 5.0));
+    }
+
+    #[test]
+    fn inverse_maps_points_back() {
+        let transform = Transform::rotate_around(0.7, 3.0, -2.0)
+            .then(Transform::skew(0.2, -0.1))
+            .then(Transform::scale(2.0, -0.5))
+            .then(Transform::translate(7.0, 11.0));
+        let inverse = transform.inverse().unwrap();
+        let (x, y) = transform.transform_point(4.0, -9.0);
+        assert_near(inverse.transform_point(x, y), (4.0, -9.0));
+        assert_near(transform.then(inverse).transform_point(1.0, 2.0), (1.0, 2.0));
+    }
+
+    #[test]
+    fn collapsing_transforms_have_no_inverse() {
+        assert_eq!(Transform::scale(0.0, 1.0).inverse(), None);
+        assert_eq!(Transform::matrix(1.0, 2.0, 2.0, 4.0, 5.0, 5.0).inverse(), None);
+        assert_eq!(Transform::IDENTITY.inverse(), Some(Transform::IDENTITY));
+        assert_eq!(Transform::scale(1e-30, 1e-30).inverse(), None);
+    }
+
+    #[test]
+    fn tiny_scales_have_an_inverse() {
+        let transform = Transform::scale(1e-4, 1e-4).then(Transform::translate(3.0, -2.0));
+        let inverse = transform.inverse().unwrap();
+        let (x, y) = inverse.transform_point(3.0 + 5e-4, -2.0 - 2e-4);
+        assert!((x - 5.0).abs() < 1e-2 && (y + 2.0).abs() < 1e-2, "{:?} is not near (5.0, -2.0)", (x, y));
+    }
+
+    #[test]
+    fn identity_is_detected() {
+        assert!(Transform::IDENTITY.is_identity());
+        assert!(Transform::translate(0.0, 0.0).is_identity());
+        assert!(!Transform::translate(0.0, 1.0).is_identity());
+    }
+
+    #[cfg(any(feature = "webgl", feature = "webgpu", feature = "native", feature = "webgpu-blade"))]
+    #[test]
+    fn max_scale_is_the_longest_scaled_axis() {
+        assert_eq!(Transform::scale(2.0, -3.0).max_scale(), 3.0);
+        assert!((Transform::rotate(0.5).then(Transform::scale(4.0, 4.0)).max_scale() - 4.0).abs() < 1e-4);
+    }
+}
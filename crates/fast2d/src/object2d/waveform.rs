@@ -0,0 +1,480 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use crate::backend::{text_size, Color, Point};
+use super::{Family, Group, Line, Object2d, Rectangle, Text, Transform};
+
+/// The value of a digital signal from a transition until the next one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WaveValue {
+    /// Logic 0, drawn at the bottom of a single-bit trace.
+    Low,
+    /// Logic 1, drawn at the top of a single-bit trace.
+    High,
+    /// Unknown (X), drawn in the X color.
+    Unknown,
+    /// High impedance (Z), drawn in the Z color, in the middle of a single-bit trace.
+    HighImpedance,
+    /// A multi-bit value, drawn as a bus segment labeled with the text.
+    Bus(Cow<'static, str>),
+}
+
+impl WaveValue {
+    /// Creates a multi-bit value labeled with the text, e.g. `WaveValue::bus(format!("{value:#x}"))`.
+    pub fn bus(label: impl Into<Cow<'static, str>>) -> Self {
+        WaveValue::Bus(label.into())
+    }
+}
+
+/// A digital signal drawn from its transitions, the way waveform viewers show it.
+///
+/// A signal with only `Low`, `High`, `Unknown` and `HighImpedance` values is drawn as a single-bit trace:
+/// high and low levels joined by vertical edges, Z in the middle and X as a filled box.
+/// A signal with any `Bus` value is drawn as a bus: each value is a hexagon whose slanted ends cross at
+/// the transitions, labeled with the value centered in it. Labels that don't fit are shortened with an
+/// ellipsis or left out, and X and Z segments are drawn in their colors without a label.
+///
+/// The time range in view is mapped to the width of the waveform, and only the transitions in it
+/// are drawn, so a long signal can be drawn again for every zoom. Converted into an [`Object2d`],
+/// the waveform becomes a group of lines, boxes and text clipped to its area.
+#[derive(Debug, Clone)]
+pub struct Waveform {
+    /// Times sorted in ascending order, each with the value until the next time
+    pub(crate) transitions: Vec<(f64, WaveValue)>,
+    /// The times at the left and right edge, None for the first and last transition
+    pub(crate) time_range: Option<(f64, f64)>,
+    /// The top-left corner of the area of the waveform
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+    /// The width of the trace lines
+    pub(crate) line_width: f32,
+    /// The horizontal length of the slanted ends of bus segments
+    pub(crate) transition_width: f32,
+    /// The color of high and low levels and bus values
+    pub(crate) color: Color,
+    /// The color of unknown (X) values
+    pub(crate) x_color: Color,
+    /// The color of high impedance (Z) values
+    pub(crate) z_color: Color,
+    /// The color of the bus value labels
+    pub(crate) label_color: Color,
+    /// The font size of the labels
+    pub(crate) font_size: f32,
+    /// The font families of the labels, in fallback order (never empty)
+    pub(crate) families: Vec<Family>,
+}
+
+impl Waveform {
+    /// Creates a waveform of the transitions, each a time with the value until the next one,
+    /// sorted by time. The last value lasts until the end of the time range.
+    pub fn new(transitions: impl IntoIterator<Item = (f64, WaveValue)>) -> Self {
+        Self {
+            transitions: transitions.into_iter().collect(),
+            time_range: None,
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 20.0,
+            line_width: 1.0,
+            transition_width: 4.0,
+            color: Color::new(0, 220, 120, 1.0),
+            x_color: Color::new(255, 70, 70, 1.0),
+            z_color: Color::new(230, 200, 60, 1.0),
+            label_color: Color::default(),
+            font_size: 12.0,
+            families: vec![Family::SansSerif],
+        }
+    }
+
+    /// Sets the top-left corner of the waveform.
+    pub fn position(mut self, x: f32, y: f32) -> Self {
+        self.x = x;
+        self.y = y;
+        self
+    }
+
+    /// Sets the width the time range is drawn in and the height between the high and low levels.
+    pub fn size(mut self, width: f32, height: f32) -> Self {
+        self.width = width.max(0.0);
+        self.height = height.max(0.0);
+        self
+    }
+
+    /// Sets the times at the left and right edge, by default the first and last transition.
+    pub fn time_range(mut self, start: f64, end: f64) -> Self {
+        self.time_range = Some((start, end));
+        self
+    }
+
+    /// Sets the width of the trace lines.
+    pub fn line_width(mut self, width: f32) -> Self {
+        self.line_width = width.max(0.0);
+        self
+    }
+
+    /// Sets the horizontal length of the slanted ends of bus segments, shorter for short segments.
+    pub fn transition_width(mut self, width: f32) -> Self {
+        self.transition_width = width.max(0.0);
+        self
+    }
+
+    /// Sets the color of high and low levels and bus values.
+    pub fn color(mut self, r: u8, g: u8, b: u8, a: f32) -> Self {
+        self.color = Color::new(r, g, b, a);
+        self
+    }
+
+    /// Sets the color of unknown (X) values.
+    pub fn x_color(mut self, r: u8, g: u8, b: u8, a: f32) -> Self {
+        self.x_color = Color::new(r, g, b, a);
+        self
+    }
+
+    /// Sets the color of high impedance (Z) values.
+    pub fn z_color(mut self, r: u8, g: u8, b: u8, a: f32) -> Self {
+        self.z_color = Color::new(r, g, b, a);
+        self
+    }
+
+    /// Sets the color of the bus value labels.
+    pub fn label_color(mut self, r: u8, g: u8, b: u8, a: f32) -> Self {
+        self.label_color = Color::new(r, g, b, a);
+        self
+    }
+
+    /// Sets the font size of the bus value labels.
+    pub fn font_size(mut self, size: f32) -> Self {
+        self.font_size = size.max(0.0);
+        self
+    }
+
+    /// Sets the font family of the bus value labels.
+    pub fn family(mut self, family: Family) -> Self {
+        self.families = vec![family];
+        self
+    }
+
+    /// Returns the segments of the values in the time range, with their edges mapped to x coordinates.
+    fn segments(&self) -> Vec<Segment<'_>> {
+        let (start, end) = self.time_range.unwrap_or_else(|| {
+            let first = self.transitions.first().map_or(0.0, |(time, _)| *time);
+            let last = self.transitions.last().map_or(0.0, |(time, _)| *time);
+            (first, last)
+        });
+        // Empty, reversed and NaN ranges show nothing
+        if end.partial_cmp(&start) != Some(Ordering::Greater) || self.width <= 0.0 {
+            return Vec::new();
+        }
+        let to_x = |time: f64| self.x + ((time - start) / (end - start) * self.width as f64) as f32;
+        // The value at the start of the range is the value of the last transition before it
+        let first = self.transitions.partition_point(|(time, _)| *time <= start).saturating_sub(1);
+        let mut segments = Vec::new();
+        for (index, (time, value)) in self.transitions.iter().enumerate().skip(first) {
+            if *time >= end {
+                break;
+            }
+            let next = self.transitions.get(index + 1).map(|(next, _)| *next).filter(|next| *next < end);
+            let right = next.map_or(self.x + self.width, to_x);
+            // A transition before the range sets the value at its start
+            let (left, starts) = if *time < start { (self.x, false) } else { (to_x(*time), true) };
+            segments.push(Segment { left, right, value, starts, ends: next.is_some() });
+        }
+        segments
+    }
+
+    /// Returns the lines and boxes of a single-bit trace.
+    fn bit_objects(&self, segments: &[Segment]) -> Vec<Object2d> {
+        let (top, middle, bottom) = (self.y, self.y + self.height / 2.0, self.y + self.height);
+        let mut objects = Vec::new();
+        // Consecutive values of the same color are drawn as one line, the level of the previous value starts the edge
+        let mut run: Option<(Color, Vec<Point>)> = None;
+        let mut previous_level: Option<f32> = None;
+        for segment in segments {
+            let (level, color) = match segment.value {
+                WaveValue::High => (top, self.color),
+                WaveValue::Low => (bottom, self.color),
+                WaveValue::HighImpedance => (middle, self.z_color),
+                WaveValue::Unknown | WaveValue::Bus(_) => {
+                    objects.extend(run.take().map(|(color, points)| self.trace(points, color)));
+                    previous_level = None;
+                    let x = self.x_color;
+                    let unknown = Rectangle::new()
+                        .position(segment.left, top)
+                        .size(segment.right - segment.left, self.height)
+                        .color(x.r, x.g, x.b, x.a * UNKNOWN_FILL_OPACITY)
+                        .inner_border(self.line_width.min((segment.right - segment.left) / 2.0), x.r, x.g, x.b, x.a);
+                    objects.push(unknown.into());
+                    continue;
+                }
+            };
+            if run.as_ref().is_some_and(|(run_color, _)| *run_color != color) {
+                objects.extend(run.take().map(|(color, points)| self.trace(points, color)));
+            }
+            let (_, points) = run.get_or_insert_with(|| (color, Vec::new()));
+            if points.is_empty() && let Some(previous_level) = previous_level {
+                points.push(Point { x: segment.left, y: previous_level });
+            }
+            points.push(Point { x: segment.left, y: level });
+            points.push(Point { x: segment.right, y: level });
+            previous_level = Some(level);
+        }
+        objects.extend(run.take().map(|(color, points)| self.trace(points, color)));
+        objects
+    }
+
+    /// Returns the lines and labels of a bus.
+    ///
+    /// The top and bottom edges of consecutive segments are drawn as two zigzag lines crossing at the transitions.
+    fn bus_objects(&self, segments: &[Segment]) -> Vec<Object2d> {
+        let (top, middle, bottom) = (self.y, self.y + self.height / 2.0, self.y + self.height);
+        // The slanted ends at each transition are as long as the shorter of the segments allows
+        let slant = |left: &Segment, right: &Segment| {
+            self.transition_width.min((left.right - left.left) / 2.0).min((right.right - right.left) / 2.0)
+        };
+        let mut objects = Vec::new();
+        let mut labels = Vec::new();
+        let mut run: Option<(Color, Vec<Point>, Vec<Point>)> = None;
+        for (index, segment) in segments.iter().enumerate() {
+            let left_slant = if segment.starts && index > 0 { slant(&segments[index - 1], segment) } else { 0.0 };
+            let right_slant = match segments.get(index + 1) {
+                Some(next) if segment.ends => slant(segment, next),
+                _ => 0.0,
+            };
+            let (color, label) = match segment.value {
+                WaveValue::Unknown => (self.x_color, None),
+                WaveValue::HighImpedance => (self.z_color, None),
+                WaveValue::Low => (self.color, Some("0")),
+                WaveValue::High => (self.color, Some("1")),
+                WaveValue::Bus(label) => (self.color, Some(label.as_ref())),
+            };
+            if run.as_ref().is_some_and(|(run_color, _, _)| *run_color != color) {
+                objects.extend(run.take().into_iter().flat_map(|(color, upper, lower)| [self.trace(upper, color), self.trace(lower, color)]));
+            }
+            let (_, upper, lower) = run.get_or_insert_with(|| (color, Vec::new(), Vec::new()));
+            for (points, level) in [(&mut *upper, top), (&mut *lower, bottom)] {
+                // The previous segment ends in the crossing this segment starts from
+                if points.is_empty() && left_slant > 0.0 {
+                    points.push(Point { x: segment.left, y: middle });
+                }
+                points.push(Point { x: segment.left + left_slant, y: level });
+                points.push(Point { x: segment.right - right_slant, y: level });
+                if right_slant > 0.0 {
+                    points.push(Point { x: segment.right, y: middle });
+                }
+            }
+            if let Some(label) = label && self.label_color.a > 0.0 {
+                let padding = self.font_size * LABEL_PADDING;
+                let (left, right) = (segment.left + left_slant + padding, segment.right - right_slant - padding);
+                labels.extend(self.label(label, left, right));
+            }
+        }
+        objects.extend(run.take().into_iter().flat_map(|(color, upper, lower)| [self.trace(upper, color), self.trace(lower, color)]));
+        // Labels are drawn over the lines
+        objects.extend(labels);
+        objects
+    }
+
+    /// Returns a line through the points with level of detail, as dense traces have many points per pixel.
+    fn trace(&self, points: Vec<Point>, color: Color) -> Object2d {
+        Line { points, width: self.line_width, color, transform: Transform::IDENTITY, lod: true }.into()
+    }
+
+    /// Returns the label centered between `left` and `right` and vertically, shortened with an ellipsis to fit,
+    /// or `None` if not even one character fits.
+    fn label(&self, label: &str, left: f32, right: f32) -> Option<Object2d> {
+        let available = right - left;
+        // Narrow segments can't fit a character, they are not measured
+        if available < self.font_size * MIN_CHARACTER_WIDTH || label.is_empty() {
+            return None;
+        }
+        let text = Text::new()
+            .font_size(self.font_size)
+            .families(self.families.iter().cloned())
+            .color(self.label_color.r, self.label_color.g, self.label_color.b, self.label_color.a);
+        let measure = |label: String| {
+            let text = text.clone().text(label);
+            (text_size(&text).0, text)
+        };
+        let (width, mut fitting) = measure(label.to_owned());
+        let mut fitting_width = width;
+        if width > available {
+            // Find the longest prefix fitting with the ellipsis
+            let ends: Vec<usize> = label.char_indices().map(|(index, _)| index).skip(1).collect();
+            let (mut low, mut high) = (0, ends.len());
+            let mut best = None;
+            while low < high {
+                let middle = (low + high) / 2;
+                let (width, text) = measure(format!("{}\u{2026}", &label[..ends[middle]]));
+                if width <= available {
+                    best = Some((width, text));
+                    low = middle + 1;
+                } else {
+                    high = middle;
+                }
+            }
+            (fitting_width, fitting) = best?;
+        }
+        let top = self.y + (self.height - self.font_size * fitting.line_height_multiplier) / 2.0;
+        Some(fitting.position(left + (available - fitting_width) / 2.0, top).into())
+    }
+}
+
+/// Opacity of the fill of unknown values of a single-bit trace, relative to the X color.
+const UNKNOWN_FILL_OPACITY: f32 = 0.25;
+
+/// Space between a label and the ends of its bus segment, relative to the font size.
+const LABEL_PADDING: f32 = 0.25;
+
+/// Width of the narrowest character relative to the font size, narrower segments have no label.
+const MIN_CHARACTER_WIDTH: f32 = 0.3;
+
+/// A value of the waveform in the time range.
+struct Segment<'a> {
+    left: f32,
+    right: f32,
+    value: &'a WaveValue,
+    /// Whether the segment starts with a transition, or at the start of the range
+    starts: bool,
+    /// Whether the segment ends with a transition, or at the end of the range
+    ends: bool,
+}
+
+/// Converts a Waveform into a group of its lines, boxes and labels, clipped to its area.
+impl From<Waveform> for Object2d {
+    fn from(waveform: Waveform) -> Self {
+        let segments = waveform.segments();
+        let is_bus = waveform.transitions.iter().any(|(_, value)| matches!(value, WaveValue::Bus(_)));
+        let children = if is_bus { waveform.bus_objects(&segments) } else { waveform.bit_objects(&segments) };
+        // Lines at the high and low levels extend by half their width beyond the area
+        let half_width = waveform.line_width / 2.0;
+        Group::new()
+            .children(children)
+            .clip(waveform.x, waveform.y - half_width, waveform.width, waveform.height + waveform.line_width)
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waveform(transitions: &[(f64, WaveValue)]) -> Waveform {
+        Waveform::new(transitions.iter().cloned()).size(100.0, 20.0)
+    }
+
+    fn summary(segments: &[Segment]) -> Vec<(f32, f32, WaveValue, bool, bool)> {
+        segments.iter().map(|segment| (segment.left, segment.right, segment.value.clone(), segment.starts, segment.ends)).collect()
+    }
+
+    fn line_points(object: &Object2d) -> Vec<(f32, f32)> {
+        match object {
+            Object2d::Line(line) => line.points.iter().map(|point| (point.x, point.y)).collect(),
+            _ => panic!("Expected a line, got {object:?}"),
+        }
+    }
+
+    #[test]
+    fn segments_map_the_time_range_to_the_width() {
+        let wave = waveform(&[(0.0, WaveValue::Low), (10.0, WaveValue::High), (20.0, WaveValue::Low)]).time_range(5.0, 15.0);
+        assert_eq!(summary(&wave.segments()), vec![
+            (0.0, 50.0, WaveValue::Low, false, true),
+            (50.0, 100.0, WaveValue::High, true, false),
+        ]);
+    }
+
+    #[test]
+    fn segments_start_with_the_value_of_a_transition_before_the_range() {
+        let wave = waveform(&[(0.0, WaveValue::High)]).time_range(100.0, 200.0);
+        assert_eq!(summary(&wave.segments()), vec![(0.0, 100.0, WaveValue::High, false, false)]);
+    }
+
+    #[test]
+    fn segments_leave_out_the_time_before_the_first_transition() {
+        let wave = waveform(&[(10.0, WaveValue::High)]).time_range(0.0, 20.0);
+        assert_eq!(summary(&wave.segments()), vec![(50.0, 100.0, WaveValue::High, true, false)]);
+        let wave = waveform(&[(10.0, WaveValue::High)]).time_range(0.0, 5.0);
+        assert!(wave.segments().is_empty());
+    }
+
+    #[test]
+    fn segments_default_to_the_first_and_last_transition() {
+        let wave = waveform(&[(0.0, WaveValue::Low), (10.0, WaveValue::High)]);
+        assert_eq!(summary(&wave.segments()), vec![(0.0, 100.0, WaveValue::Low, true, false)]);
+    }
+
+    #[test]
+    fn segments_are_empty_for_empty_reversed_and_nan_ranges() {
+        let transitions = [(0.0, WaveValue::Low), (10.0, WaveValue::High)];
+        assert!(waveform(&transitions).time_range(5.0, 5.0).segments().is_empty());
+        assert!(waveform(&transitions).time_range(10.0, 0.0).segments().is_empty());
+        assert!(waveform(&transitions).time_range(f64::NAN, 10.0).segments().is_empty());
+        assert!(waveform(&transitions).size(0.0, 20.0).segments().is_empty());
+        assert!(waveform(&[]).time_range(0.0, 10.0).segments().is_empty());
+    }
+
+    #[test]
+    fn bit_objects_join_levels_and_box_unknown_values() {
+        let wave = waveform(&[
+            (0.0, WaveValue::Low),
+            (1.0, WaveValue::High),
+            (2.0, WaveValue::Unknown),
+            (3.0, WaveValue::High),
+        ]).time_range(0.0, 4.0);
+        let objects = wave.bit_objects(&wave.segments());
+        assert_eq!(objects.len(), 3);
+        assert_eq!(line_points(&objects[0]), vec![(0.0, 20.0), (25.0, 20.0), (25.0, 0.0), (50.0, 0.0)]);
+        assert!(matches!(objects[1], Object2d::Rectangle(_)));
+        assert_eq!(line_points(&objects[2]), vec![(75.0, 0.0), (100.0, 0.0)]);
+    }
+
+    #[test]
+    fn bit_objects_start_a_new_color_from_the_previous_level() {
+        let wave = waveform(&[(0.0, WaveValue::Low), (1.0, WaveValue::HighImpedance)]).time_range(0.0, 2.0);
+        let objects = wave.bit_objects(&wave.segments());
+        assert_eq!(objects.len(), 2);
+        assert_eq!(line_points(&objects[0]), vec![(0.0, 20.0), (50.0, 20.0)]);
+        assert_eq!(line_points(&objects[1]), vec![(50.0, 20.0), (50.0, 10.0), (100.0, 10.0)]);
+    }
+
+    #[test]
+    fn bus_objects_cross_at_transitions() {
+        let wave = waveform(&[(0.0, WaveValue::bus("a")), (10.0, WaveValue::bus("b"))])
+            .time_range(0.0, 20.0)
+            .transition_width(4.0)
+            .label_color(0, 0, 0, 0.0);
+        let objects = wave.bus_objects(&wave.segments());
+        assert_eq!(objects.len(), 2);
+        assert_eq!(line_points(&objects[0]), vec![(0.0, 0.0), (46.0, 0.0), (50.0, 10.0), (54.0, 0.0), (100.0, 0.0)]);
+        assert_eq!(line_points(&objects[1]), vec![(0.0, 20.0), (46.0, 20.0), (50.0, 10.0), (54.0, 20.0), (100.0, 20.0)]);
+    }
+
+    #[test]
+    fn bus_objects_shorten_slants_for_short_segments() {
+        let wave = waveform(&[(0.0, WaveValue::bus("a")), (1.0, WaveValue::bus("b")), (100.0, WaveValue::Unknown)])
+            .time_range(0.0, 100.0)
+            .transition_width(4.0)
+            .label_color(0, 0, 0, 0.0);
+        let objects = wave.bus_objects(&wave.segments());
+        // The first segment is one unit wide, its slants are half as long
+        assert_eq!(line_points(&objects[0])[..4], [(0.0, 0.0), (0.5, 0.0), (1.0, 10.0), (1.5, 0.0)]);
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn labels_are_shortened_with_an_ellipsis_or_left_out() {
+        crate::register_font_paths([concat!(env!("CARGO_MANIFEST_DIR"), "/../../examples/native_tao_example/assets/fonts/FiraCode-Regular.ttf")])
+            .expect("Failed to register the test font");
+        let wave = waveform(&[]).family(Family::Name("Fira Code".into())).font_size(10.0);
+        let text = |object: Option<Object2d>| match object {
+            Some(Object2d::Text(text)) => Some(text.text.into_owned()),
+            None => None,
+            Some(object) => panic!("Expected a label, got {object:?}"),
+        };
+        assert_eq!(text(wave.label("0xdeadbeef", 0.0, 100.0)).as_deref(), Some("0xdeadbeef"));
+        let shortened = text(wave.label("0xdeadbeef", 0.0, 40.0)).expect("A shortened label");
+        assert!(shortened.ends_with('\u{2026}') && "0xdeadbeef".starts_with(shortened.trim_end_matches('\u{2026}')), "{shortened}");
+        assert_eq!(text(wave.label("0xdeadbeef", 0.0, 1.0)), None);
+        assert_eq!(text(wave.label("", 0.0, 100.0)), None);
+    }
+}